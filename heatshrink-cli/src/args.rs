@@ -0,0 +1,104 @@
+//! Разбор аргументов в стиле getopt(3): ключи можно склеивать (`-dv`),
+//! значение ключа слитно или отдельным аргументом (`-w8`, `-w 8`), `--` - конец ключей.
+
+#[derive(Debug, PartialEq)]
+pub enum Arg {
+    Opt(char),
+    OptValue(char, String),
+    Positional(String),
+}
+
+pub struct Getopt<I: Iterator<Item = String>> {
+    args: I,
+    // ключи, которым нужно значение
+    with_value: &'static str,
+    // необработанный остаток склеенных ключей
+    pending: String,
+    only_positional: bool,
+}
+
+impl<I: Iterator<Item = String>> Getopt<I> {
+    pub fn new(args: I, with_value: &'static str) -> Self {
+        Self {
+            args,
+            with_value,
+            pending: String::new(),
+            only_positional: false,
+        }
+    }
+}
+
+impl<I: Iterator<Item = String>> Iterator for Getopt<I> {
+    type Item = Result<Arg, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(opt) = self.pending.chars().next() {
+            let rest = self.pending.split_off(opt.len_utf8());
+            self.pending.clear();
+            if !self.with_value.contains(opt) {
+                self.pending = rest;
+                return Some(Ok(Arg::Opt(opt)));
+            }
+            let value = if rest.is_empty() {
+                self.args.next()
+            } else {
+                Some(rest)
+            };
+            return Some(
+                value
+                    .map(|v| Arg::OptValue(opt, v))
+                    .ok_or_else(|| format!("option -{} requires an argument", opt)),
+            );
+        }
+
+        let arg = self.args.next()?;
+        if self.only_positional || arg == "-" || !arg.starts_with('-') {
+            return Some(Ok(Arg::Positional(arg)));
+        }
+        if arg == "--" {
+            self.only_positional = true;
+        } else {
+            self.pending = arg[1..].to_string();
+        }
+        self.next()
+    }
+}
+
+/// Значение `-w`/`-l` и им подобных
+pub fn parse_bits(opt: char, value: &str) -> Result<u8, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for -{}: {}", opt, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::args::{Arg, Getopt};
+
+    fn getopt(args: &[&str]) -> Vec<Result<Arg, String>> {
+        Getopt::new(args.iter().map(|s| s.to_string()), "wl").collect()
+    }
+
+    #[test]
+    fn getopt_like() {
+        assert_eq!(
+            getopt(&["-dv", "-w8", "in", "-l", "5", "-", "--", "-e"]),
+            [
+                Ok(Arg::Opt('d')),
+                Ok(Arg::Opt('v')),
+                Ok(Arg::OptValue('w', "8".to_string())),
+                Ok(Arg::Positional("in".to_string())),
+                Ok(Arg::OptValue('l', "5".to_string())),
+                Ok(Arg::Positional("-".to_string())),
+                Ok(Arg::Positional("-e".to_string())),
+            ]
+        );
+        assert_eq!(
+            getopt(&["-vw"]),
+            [
+                Ok(Arg::Opt('v')),
+                Err("option -w requires an argument".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! `heatshrink blocks [-e|-d] [-v] [-b BLOCK_SIZE] [IN_FILE] [OUT_FILE]` - формат с
+//! независимыми блоками ([`heatshrink_rust::seekable`]), с feature `rayon` - параллельно
+
+use std::io::{self, Read, Write};
+use std::process::exit;
+
+use heatshrink_rust::seekable::{self, SeekableReader};
+
+use crate::args::{Arg, Getopt};
+use crate::{open_input, open_output, ArgsError, Mode};
+
+const DEF_BLOCK_SIZE: u32 = 64 * 1024;
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), ArgsError> {
+    let mut mode = Mode::Encode;
+    let mut verbose = false;
+    let mut block_size = DEF_BLOCK_SIZE;
+    let mut positional = Vec::new();
+
+    for arg in Getopt::new(args, "b") {
+        match arg.map_err(ArgsError::Invalid)? {
+            Arg::Opt('h') => return Err(ArgsError::Help),
+            Arg::Opt('e') => mode = Mode::Encode,
+            Arg::Opt('d') => mode = Mode::Decode,
+            Arg::Opt('v') => verbose = true,
+            Arg::OptValue('b', v) => {
+                block_size = v
+                    .parse()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| ArgsError::Invalid(format!("invalid value for -b: {}", v)))?
+            }
+            Arg::Positional(p) => positional.push(p),
+            Arg::Opt(opt) | Arg::OptValue(opt, _) => {
+                return Err(ArgsError::Invalid(format!("unknown option -{}", opt)))
+            }
+        }
+    }
+    if positional.len() > 2 {
+        return Err(ArgsError::Invalid(format!(
+            "unexpected argument: {}",
+            positional[2]
+        )));
+    }
+    let mut positional = positional.into_iter();
+    let in_fname = positional.next().unwrap_or_else(|| "-".to_string());
+    let out_fname = positional.next().unwrap_or_else(|| "-".to_string());
+
+    let res = open_input(&in_fname)
+        .and_then(|mut f| {
+            let mut data = Vec::new();
+            f.read_to_end(&mut data).map(|_| data)
+        })
+        .and_then(|data| {
+            let out = process(mode, block_size, &data)?;
+            open_output(&out_fname).and_then(|mut f| f.write_all(&out).and_then(|_| f.flush()))?;
+            Ok((data.len(), out.len()))
+        });
+    match res {
+        Ok((inb, outb)) if verbose => {
+            let report = format!("{} {} -> {} bytes", in_fname, inb, outb);
+            if out_fname == "-" {
+                eprintln!("{}", report);
+            } else {
+                println!("{}", report);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("heatshrink: {}", e);
+            exit(1);
+        }
+    }
+    Ok(())
+}
+
+fn process(mode: Mode, block_size: u32, data: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+    match mode {
+        Mode::Encode => {
+            if data.len() > u32::MAX as usize {
+                return Err(invalid(seekable::Error::Unsupported));
+            }
+            #[cfg(feature = "rayon")]
+            return Ok(seekable::compress_parallel(data, block_size));
+            #[cfg(not(feature = "rayon"))]
+            return Ok(seekable::compress(data, block_size));
+        }
+        Mode::Decode => {
+            let reader = SeekableReader::new(data).map_err(invalid)?;
+            #[cfg(feature = "rayon")]
+            return reader.decompress_parallel().map_err(invalid);
+            #[cfg(not(feature = "rayon"))]
+            return reader.decompress().map_err(invalid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blocks::process;
+    use crate::Mode;
+
+    #[test]
+    fn encode_decode() {
+        let src = b"blocks blocks blocks".repeat(1000);
+        let packed = process(Mode::Encode, 1000, &src).unwrap();
+        assert_eq!(packed, heatshrink_rust::seekable::compress(&src, 1000));
+        assert_eq!(process(Mode::Decode, 0, &packed).unwrap(), src);
+        assert!(process(Mode::Decode, 0, &src).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+//! `heatshrink inspect [-w SIZE] [-l BITS] [IN_FILE]` - разбор сжатого потока на токены
+
+use std::io::{self, BufWriter, Read, Write};
+use std::process::exit;
+
+use heatshrink_rust::inspect::tokens;
+
+use crate::args::{Arg, Getopt};
+use crate::{open_input, parse_bits, ArgsError, DEF_LOOKAHEAD_SZ2, DEF_WINDOW_SZ2};
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), ArgsError> {
+    let mut window_sz2 = DEF_WINDOW_SZ2;
+    let mut lookahead_sz2 = DEF_LOOKAHEAD_SZ2;
+    let mut in_fname = None;
+
+    for arg in Getopt::new(args, "wl") {
+        match arg.map_err(ArgsError::Invalid)? {
+            Arg::Opt('h') => return Err(ArgsError::Help),
+            Arg::OptValue('w', v) => window_sz2 = parse_bits('w', &v)?,
+            Arg::OptValue('l', v) => lookahead_sz2 = parse_bits('l', &v)?,
+            Arg::Positional(p) if in_fname.is_none() => in_fname = Some(p),
+            Arg::Positional(p) => {
+                return Err(ArgsError::Invalid(format!("unexpected argument: {}", p)))
+            }
+            Arg::Opt(opt) | Arg::OptValue(opt, _) => {
+                return Err(ArgsError::Invalid(format!("unknown option -{}", opt)))
+            }
+        }
+    }
+    let in_fname = in_fname.unwrap_or_else(|| "-".to_string());
+
+    let mut data = Vec::new();
+    if let Err(e) = open_input(&in_fname).and_then(|mut f| f.read_to_end(&mut data)) {
+        eprintln!("heatshrink: {}: {}", in_fname, e);
+        exit(1);
+    }
+
+    let tokens = tokens(&data, window_sz2, lookahead_sz2).ok_or_else(|| {
+        ArgsError::Invalid(format!(
+            "invalid window/lookahead: -w {} -l {}",
+            window_sz2, lookahead_sz2
+        ))
+    })?;
+
+    if let Err(e) = dump(tokens, &mut BufWriter::new(io::stdout())) {
+        // например, stdout закрыт (| head)
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("heatshrink: {}", e);
+            exit(1);
+        }
+    }
+    Ok(())
+}
+
+fn dump(mut tokens: heatshrink_rust::inspect::Tokens, out: &mut dyn Write) -> io::Result<()> {
+    for token in tokens.by_ref() {
+        writeln!(out, "{}", token)?;
+    }
+    writeln!(out, "{}", tokens.summary())?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use heatshrink_rust::inspect::tokens;
+
+    use crate::inspect::dump;
+
+    #[test]
+    fn dump_zeros() {
+        let mut out = Vec::new();
+        dump(tokens(&[0x00, 0x38], 8, 4).unwrap(), &mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("backref  offset     1 length     8"));
+        assert!(lines[0].ends_with("<-- before start of output"));
+        assert_eq!(
+            lines[1],
+            "literals: 0, backrefs: 1 (1 before start), output: 8 bytes, trailing bits: 3"
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! `heatshrink log [IN_FILE] [OUT_FILE]` - текст из сжатого потока логгера
+//! ([`heatshrink_rust::logger`]), строка на запись. Вход читается по частям, строки блока
+//! выводятся, как только блок пришел: можно читать живой захват из трубы
+
+use std::io::{self, Read, Write};
+use std::process::exit;
+
+use heatshrink_rust::logger::LineDecoder;
+use heatshrink_rust::record_stream::Error;
+
+use crate::args::{Arg, Getopt};
+use crate::{open_input, open_output, ArgsError, DEF_BUFFER_SIZE};
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), ArgsError> {
+    let mut positional = Vec::new();
+    for arg in Getopt::new(args, "") {
+        match arg.map_err(ArgsError::Invalid)? {
+            Arg::Opt('h') => return Err(ArgsError::Help),
+            Arg::Positional(p) => positional.push(p),
+            Arg::Opt(opt) | Arg::OptValue(opt, _) => {
+                return Err(ArgsError::Invalid(format!("unknown option -{}", opt)))
+            }
+        }
+    }
+    if positional.len() > 2 {
+        return Err(ArgsError::Invalid(format!(
+            "unexpected argument: {}",
+            positional[2]
+        )));
+    }
+    let mut positional = positional.into_iter();
+    let in_fname = positional.next().unwrap_or_else(|| "-".to_string());
+    let out_fname = positional.next().unwrap_or_else(|| "-".to_string());
+
+    let error = match open_input(&in_fname)
+        .and_then(|input| Ok((input, open_output(&out_fname)?)))
+        .and_then(|(input, output)| process(input, output))
+    {
+        Ok(error) => error,
+        Err(e) => {
+            eprintln!("heatshrink: {}", e);
+            exit(1);
+        }
+    };
+    match error {
+        // захват еще идет или оборван: последний блок недописан, остальное выведено
+        Some(Error::Truncated) => eprintln!("heatshrink: warning: last block is incomplete"),
+        Some(e) => {
+            eprintln!("heatshrink: {}", e);
+            exit(1);
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+// Строки до ошибки выведены, ошибка потока - в результате
+fn process<R: Read, W: Write>(mut input: R, mut output: W) -> io::Result<Option<Error>> {
+    let mut buf = vec![0u8; DEF_BUFFER_SIZE];
+    let mut decoder = LineDecoder::new();
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        decoder.push(&buf[..n]);
+        while let Some(lines) = decoder.next_block() {
+            let lines = match lines {
+                Ok(lines) => lines,
+                Err(e) => {
+                    output.flush()?;
+                    return Ok(Some(e));
+                }
+            };
+            for line in lines {
+                writeln!(output, "{}", line)?;
+            }
+        }
+        output.flush()?;
+    }
+    Ok(decoder.finish().err())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+
+    use heatshrink_rust::record_stream::{Error, RecordEncoder};
+
+    use crate::log::process;
+
+    struct Drip<'a>(&'a [u8]);
+
+    impl Read for Drip<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(1);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn decode() {
+        let mut encoder = RecordEncoder::new().with_sync_interval(2);
+        for line in [
+            "INFO  app: started",
+            "WARN  app: low battery",
+            "ERROR app: halt",
+        ] {
+            encoder.push(line.as_bytes());
+        }
+        let packed = encoder.finish();
+        let mut text = Vec::new();
+        assert_eq!(process(&packed[..], &mut text).unwrap(), None);
+        assert_eq!(
+            text,
+            b"INFO  app: started\nWARN  app: low battery\nERROR app: halt\n"
+        );
+
+        let mut text = Vec::new();
+        let error = process(&packed[..packed.len() - 1], &mut text).unwrap();
+        assert_eq!(text, b"INFO  app: started\nWARN  app: low battery\n");
+        assert_eq!(error, Some(Error::Truncated));
+
+        // вход по байту, как из трубы
+        let mut text = Vec::new();
+        assert_eq!(process(Drip(&packed), &mut text).unwrap(), None);
+        assert_eq!(text.iter().filter(|&&b| b == b'\n').count(), 3);
+    }
+}
@@ -0,0 +1,410 @@
+//! Утилита командной строки, совместимая с `heatshrink` из heatshrink-dist:
+//! те же ключи, те же значения по умолчанию, побайтно тот же результат.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::process::exit;
+
+use heatshrink_rust::io::{DecoderWriter, EncoderWriter};
+use heatshrink_rust::Level;
+
+use args::{Arg, Getopt};
+
+mod args;
+mod blocks;
+mod inspect;
+mod log;
+mod train;
+mod tune;
+
+const DEF_WINDOW_SZ2: u8 = 11;
+const DEF_LOOKAHEAD_SZ2: u8 = 4;
+const DEF_BUFFER_SIZE: usize = 64 * 1024;
+
+const USAGE: &str = "\
+Usage:
+  heatshrink [-h] [-e|-d] [-v] [-F] [-w SIZE] [-l BITS] [-L LEVEL] [-D DICT_FILE] [IN_FILE] [OUT_FILE]
+  heatshrink inspect [-w SIZE] [-l BITS] [IN_FILE]
+  heatshrink tune [-m MAX_RAM] [SAMPLE_FILE...]
+  heatshrink train [-w SIZE] [-l BITS] [-o DICT_FILE] SAMPLE_FILE...
+  heatshrink blocks [-e|-d] [-v] [-b BLOCK_SIZE] [IN_FILE] [OUT_FILE]
+  heatshrink log [IN_FILE] [OUT_FILE]
+
+heatshrink compresses or decompresses byte streams using LZSS, and is
+designed especially for embedded, low-memory, and/or hard real-time
+systems.
+
+ -h        print help
+ -e        encode (compress, default)
+ -d        decode (decompress)
+ -v        verbose (print input & output sizes, compression ratio, etc.)
+ -F        with -d: the stream has flush points (heatshrink_rust::framing,
+           EncoderWriter::framed), each segment is written out as soon
+           as it is complete
+
+ -w SIZE   Base-2 log of LZSS sliding window size
+
+    A larger value allows searches a larger history of the data for repeated
+    patterns, potentially compressing more effectively, but will use
+    more memory and processing time.
+    Recommended default: -w 8 (embedded systems), -w 10 (elsewhere)
+
+ -l BITS   Number of bits used for back-reference lengths
+
+    A larger value allows longer substitutions, but since all
+    back-references must use -w + -l bits, larger -w or -l can be
+    counterproductive if most patterns are small and/or local.
+    Recommended default: -l 4
+
+ -L LEVEL  compression level: fast, default (greedy, as heatshrink-dist) or
+           best (optimal parsing: smallest output, much slower, reads the
+           whole input into memory). fast is the encoder without search
+           index: less RAM, the same output (with -w 15 it may differ
+           slightly). Decoding is the same for all levels.
+
+ -D FILE   preset dictionary: the window starts filled with the end of FILE.
+           Data compressed with a dictionary decompresses only with the same one.
+
+ If IN_FILE or OUT_FILE are unspecified, they will default to
+ \"-\" for standard input and standard output, respectively.
+
+ inspect   print every literal and back-reference of a compressed stream
+           with its bit position and output position, then a summary.
+           Back-references pointing before the start of output are flagged.
+
+ tune      compress the sample files with every valid -w/-l combination and
+           report ratio, encoder/decoder RAM and throughput, then recommend
+           the best combination whose encoder + decoder RAM fits in
+           MAX_RAM bytes (unlimited by default).
+
+ train     build a dictionary for -D from sample messages (one per file) and
+           compare the compressed size of the samples with and without it.
+           The dictionary goes to DICT_FILE (standard output by default).
+
+ blocks    compress into independent BLOCK_SIZE byte blocks (64 KiB by
+           default) with an index for random access, or decompress such a
+           file. Always -w 8 -l 4. Blocks are processed on all CPU cores
+           (RAYON_NUM_THREADS limits the number of threads); the output is
+           the same as with a single thread.
+
+ log       print the text of a stream written by the heatshrink_rust log
+           backend (logger::HeatshrinkLogger), one line per record. An
+           incomplete last block (capture still running) is reported as a
+           warning; everything before it is printed.";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Mode {
+    Encode,
+    Decode,
+}
+
+#[derive(Debug, PartialEq)]
+struct Config {
+    mode: Mode,
+    verbose: bool,
+    window_sz2: u8,
+    lookahead_sz2: u8,
+    level: Level,
+    dictionary: Option<String>,
+    framed: bool,
+    in_fname: String,
+    out_fname: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum ArgsError {
+    Help,
+    Invalid(String),
+}
+
+fn parse_bits(opt: char, value: &str) -> Result<u8, ArgsError> {
+    args::parse_bits(opt, value).map_err(ArgsError::Invalid)
+}
+
+// getopt("hedvFw:l:L:D:")
+fn parse_args<I: Iterator<Item = String>>(args: I) -> Result<Config, ArgsError> {
+    let mut cfg = Config {
+        mode: Mode::Encode,
+        verbose: false,
+        window_sz2: DEF_WINDOW_SZ2,
+        lookahead_sz2: DEF_LOOKAHEAD_SZ2,
+        level: Level::Default,
+        dictionary: None,
+        framed: false,
+        in_fname: "-".to_string(),
+        out_fname: "-".to_string(),
+    };
+    let mut positional = Vec::new();
+
+    for arg in Getopt::new(args, "wlLD") {
+        match arg.map_err(ArgsError::Invalid)? {
+            Arg::Opt('h') => return Err(ArgsError::Help),
+            Arg::Opt('e') => cfg.mode = Mode::Encode,
+            Arg::Opt('d') => cfg.mode = Mode::Decode,
+            Arg::Opt('v') => cfg.verbose = true,
+            Arg::Opt('F') => cfg.framed = true,
+            Arg::OptValue('w', v) => cfg.window_sz2 = parse_bits('w', &v)?,
+            Arg::OptValue('l', v) => cfg.lookahead_sz2 = parse_bits('l', &v)?,
+            Arg::OptValue('L', v) => {
+                cfg.level = v
+                    .parse()
+                    .map_err(|_| ArgsError::Invalid(format!("unknown level: {}", v)))?
+            }
+            Arg::OptValue('D', v) => cfg.dictionary = Some(v),
+            Arg::Positional(p) => positional.push(p),
+            Arg::Opt(opt) | Arg::OptValue(opt, _) => {
+                return Err(ArgsError::Invalid(format!("unknown option -{}", opt)))
+            }
+        }
+    }
+
+    if positional.len() > 2 {
+        return Err(ArgsError::Invalid(format!(
+            "unexpected argument: {}",
+            positional[2]
+        )));
+    }
+    let mut positional = positional.into_iter();
+    if let Some(in_fname) = positional.next() {
+        cfg.in_fname = in_fname;
+    }
+    if let Some(out_fname) = positional.next() {
+        cfg.out_fname = out_fname;
+    }
+
+    if !heatshrink_rust::valid_params(cfg.window_sz2, cfg.lookahead_sz2) {
+        return Err(ArgsError::Invalid(format!(
+            "invalid window/lookahead: -w {} -l {} (4 <= w <= 15, 3 <= l < w)",
+            cfg.window_sz2, cfg.lookahead_sz2
+        )));
+    }
+
+    if cfg.framed && cfg.mode == Mode::Encode {
+        return Err(ArgsError::Invalid(
+            "-F is only supported with -d".to_string(),
+        ));
+    }
+
+    Ok(cfg)
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "heatshrink version {} by Scott Vokes <vokes.s@gmail.com>",
+        env!("CARGO_PKG_VERSION")
+    );
+    eprintln!("Home page: https://github.com/atomicobject/heatshrink\n");
+    eprintln!("{}", USAGE);
+    exit(1);
+}
+
+fn open_input(fname: &str) -> io::Result<Box<dyn Read>> {
+    Ok(if fname == "-" {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(fname)?)
+    })
+}
+
+fn open_output(fname: &str) -> io::Result<Box<dyn Write>> {
+    Ok(if fname == "-" {
+        Box::new(io::stdout())
+    } else {
+        Box::new(File::create(fname)?)
+    })
+}
+
+// прокачать input через кодер/декодер, вернуть (прочитано, записано)
+fn process<W: Write>(cfg: &Config, input: &mut dyn Read, output: W) -> io::Result<(u64, u64)> {
+    let mut buf = vec![0u8; DEF_BUFFER_SIZE];
+    let dict = cfg.dictionary.as_ref().map(std::fs::read).transpose()?;
+
+    macro_rules! pump {
+        ($writer:expr) => {{
+            let mut writer = $writer;
+            loop {
+                let n = match input.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+                writer.write_all(&buf[..n])?;
+            }
+            writer.try_finish()?;
+            Ok((writer.total_in(), writer.total_out()))
+        }};
+    }
+
+    match cfg.mode {
+        Mode::Encode => {
+            let writer =
+                EncoderWriter::with_level(output, cfg.window_sz2, cfg.lookahead_sz2, cfg.level)?;
+            pump!(match &dict {
+                Some(dict) => writer.with_dictionary(dict),
+                None => writer,
+            })
+        }
+        Mode::Decode => {
+            let writer = DecoderWriter::new(output, cfg.window_sz2, cfg.lookahead_sz2)?;
+            let writer = if cfg.framed { writer.framed() } else { writer };
+            pump!(match &dict {
+                Some(dict) => writer.with_dictionary(dict),
+                None => writer,
+            })
+        }
+    }
+}
+
+fn report(cfg: &Config, inb: u64, outb: u64) -> String {
+    format!(
+        "{} {:.2} %\t {} -> {} (-w {} -l {})",
+        cfg.in_fname,
+        100.0 - (100.0 * outb as f64) / inb as f64,
+        inb,
+        outb,
+        cfg.window_sz2,
+        cfg.lookahead_sz2
+    )
+}
+
+fn run(cfg: &Config) -> io::Result<()> {
+    let mut input = open_input(&cfg.in_fname)?;
+    let output = BufWriter::new(open_output(&cfg.out_fname)?);
+
+    let (inb, outb) = process(cfg, &mut input, output)?;
+
+    if cfg.verbose {
+        // как в оригинале: отчет в stderr, если результат идет в stdout
+        if cfg.out_fname == "-" {
+            eprintln!("{}", report(cfg, inb, outb));
+        } else {
+            println!("{}", report(cfg, inb, outb));
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1).peekable();
+    let result = match args.peek().map(String::as_str) {
+        Some("inspect") => inspect::main(args.skip(1)),
+        Some("tune") => tune::main(args.skip(1)),
+        Some("train") => train::main(args.skip(1)),
+        Some("blocks") => blocks::main(args.skip(1)),
+        Some("log") => log::main(args.skip(1)),
+        _ => compress_main(args),
+    };
+
+    match result {
+        Ok(()) => {}
+        Err(ArgsError::Help) => usage(),
+        Err(ArgsError::Invalid(msg)) => {
+            eprintln!("heatshrink: {}", msg);
+            usage()
+        }
+    }
+}
+
+fn compress_main<I: Iterator<Item = String>>(args: I) -> Result<(), ArgsError> {
+    let cfg = parse_args(args)?;
+    if let Err(e) = run(&cfg) {
+        eprintln!("heatshrink: {}", e);
+        exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use heatshrink_rust::Level;
+
+    use crate::{parse_args, process, report, ArgsError, Config, Mode};
+
+    fn parse(args: &[&str]) -> Result<Config, ArgsError> {
+        parse_args(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn defaults() {
+        assert_eq!(
+            parse(&[]).unwrap(),
+            Config {
+                mode: Mode::Encode,
+                verbose: false,
+                window_sz2: 11,
+                lookahead_sz2: 4,
+                level: Level::Default,
+                dictionary: None,
+                framed: false,
+                in_fname: "-".to_string(),
+                out_fname: "-".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn options() {
+        let cfg = parse(&["-dv", "-w8", "-l", "5", "in.hs", "out"]).unwrap();
+        assert_eq!(cfg.mode, Mode::Decode);
+        assert!(cfg.verbose);
+        assert_eq!((cfg.window_sz2, cfg.lookahead_sz2), (8, 5));
+        assert_eq!(
+            (cfg.in_fname.as_str(), cfg.out_fname.as_str()),
+            ("in.hs", "out")
+        );
+
+        let cfg = parse(&["-", "out", "-e", "-w", "10"]).unwrap();
+        assert_eq!(cfg.mode, Mode::Encode);
+        assert_eq!(cfg.window_sz2, 10);
+        assert_eq!(cfg.in_fname, "-");
+
+        let cfg = parse(&["--", "-d"]).unwrap();
+        assert_eq!(cfg.mode, Mode::Encode);
+        assert_eq!(cfg.in_fname, "-d");
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(parse(&["-h"]), Err(ArgsError::Help));
+        assert!(parse(&["-x"]).is_err());
+        assert!(parse(&["-w"]).is_err());
+        assert!(parse(&["-w", "abc"]).is_err());
+        assert!(parse(&["-w", "16"]).is_err());
+        assert!(parse(&["-w", "8", "-l", "8"]).is_err());
+        assert!(parse(&["a", "b", "c"]).is_err());
+        assert!(parse(&["-L", "fastest"]).is_err());
+        assert!(parse(&["-F"]).is_err());
+        assert!(parse(&["-dF"]).unwrap().framed);
+        assert_eq!(parse(&["-L", "fast"]).unwrap().level, Level::Fast);
+    }
+
+    #[test]
+    fn encode_decode() {
+        let src = b"heatshrink heatshrink heatshrink heatshrink".repeat(100);
+
+        let mut cfg = parse(&["-w", "8", "-l", "4"]).unwrap();
+        let mut encoded = Vec::new();
+        let (inb, outb) = process(&cfg, &mut src.as_slice(), &mut encoded).unwrap();
+        assert_eq!((inb, outb), (src.len() as u64, encoded.len() as u64));
+
+        cfg.mode = Mode::Decode;
+        let mut decoded = Vec::new();
+        process(&cfg, &mut encoded.as_slice(), &mut decoded).unwrap();
+        assert_eq!(decoded, src);
+
+        let mut cfg = parse(&["-w", "8", "-l", "4", "-L", "best"]).unwrap();
+        let mut best = Vec::new();
+        process(&cfg, &mut src.as_slice(), &mut best).unwrap();
+        assert!(best.len() <= encoded.len());
+        cfg.mode = Mode::Decode;
+        let mut decoded = Vec::new();
+        process(&cfg, &mut best.as_slice(), &mut decoded).unwrap();
+        assert_eq!(decoded, src);
+
+        assert_eq!(
+            report(&cfg, 1000, 250),
+            "- 75.00 %\t 1000 -> 250 (-w 8 -l 4)"
+        );
+    }
+}
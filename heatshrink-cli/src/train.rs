@@ -0,0 +1,116 @@
+//! `heatshrink train [-w SIZE] [-l BITS] [-o DICT_FILE] SAMPLE_FILE...` - словарь по образцам
+
+use std::io::{self, Read, Write};
+use std::process::exit;
+
+use heatshrink_rust::train::{evaluate, train, Evaluation};
+
+use crate::args::{parse_bits, Arg, Getopt};
+use crate::{open_input, open_output, ArgsError, DEF_LOOKAHEAD_SZ2, DEF_WINDOW_SZ2};
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), ArgsError> {
+    let mut window_sz2 = DEF_WINDOW_SZ2;
+    let mut lookahead_sz2 = DEF_LOOKAHEAD_SZ2;
+    let mut out_fname = "-".to_string();
+    let mut files = Vec::new();
+
+    for arg in Getopt::new(args, "wlo") {
+        match arg.map_err(ArgsError::Invalid)? {
+            Arg::Opt('h') => return Err(ArgsError::Help),
+            Arg::OptValue('w', v) => {
+                window_sz2 = parse_bits('w', &v).map_err(ArgsError::Invalid)?
+            }
+            Arg::OptValue('l', v) => {
+                lookahead_sz2 = parse_bits('l', &v).map_err(ArgsError::Invalid)?
+            }
+            Arg::OptValue('o', v) => out_fname = v,
+            Arg::Positional(p) => files.push(p),
+            Arg::Opt(opt) | Arg::OptValue(opt, _) => {
+                return Err(ArgsError::Invalid(format!("unknown option -{}", opt)))
+            }
+        }
+    }
+    if files.is_empty() {
+        return Err(ArgsError::Invalid("no sample files".to_string()));
+    }
+
+    let mut samples = Vec::new();
+    for fname in &files {
+        let mut data = Vec::new();
+        if let Err(e) = open_input(fname).and_then(|mut f| f.read_to_end(&mut data)) {
+            eprintln!("heatshrink: {}: {}", fname, e);
+            exit(1);
+        }
+        samples.push(data);
+    }
+    let samples = samples.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+    let dict = train(&samples, window_sz2, lookahead_sz2).ok_or_else(|| {
+        ArgsError::Invalid(format!(
+            "invalid window/lookahead: -w {} -l {} (4 <= w <= 15, 3 <= l < w)",
+            window_sz2, lookahead_sz2
+        ))
+    })?;
+    let eval = evaluate(&samples, &dict, window_sz2, lookahead_sz2).unwrap();
+
+    let res = open_output(&out_fname)
+        .and_then(|mut out| out.write_all(&dict).and_then(|_| out.flush()))
+        .and_then(|_| {
+            // как у -v: отчет в stderr, если словарь идет в stdout
+            if out_fname == "-" {
+                print(&eval, &mut io::stderr())
+            } else {
+                print(&eval, &mut io::stdout())
+            }
+        });
+    if let Err(e) = res {
+        eprintln!("heatshrink: {}", e);
+        exit(1);
+    }
+    Ok(())
+}
+
+fn print(eval: &Evaluation, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "samples: {}, {} bytes", eval.samples, eval.input_size)?;
+    writeln!(out, "dictionary: {} bytes", eval.dictionary_size)?;
+    writeln!(
+        out,
+        "without dictionary: {} bytes ({:.2} %)",
+        eval.plain_size,
+        100.0 * eval.plain_ratio()
+    )?;
+    writeln!(
+        out,
+        "with dictionary:    {} bytes ({:.2} %)",
+        eval.with_dictionary_size,
+        100.0 * eval.dictionary_ratio()
+    )?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use heatshrink_rust::train::Evaluation;
+
+    use crate::train::print;
+
+    #[test]
+    fn report() {
+        let eval = Evaluation {
+            samples: 3,
+            input_size: 1000,
+            dictionary_size: 256,
+            plain_size: 600,
+            with_dictionary_size: 250,
+        };
+        let mut out = Vec::new();
+        print(&eval, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "samples: 3, 1000 bytes\n\
+             dictionary: 256 bytes\n\
+             without dictionary: 600 bytes (60.00 %)\n\
+             with dictionary:    250 bytes (25.00 %)\n"
+        );
+    }
+}
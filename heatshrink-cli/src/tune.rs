@@ -0,0 +1,114 @@
+//! `heatshrink tune [-m MAX_RAM] [FILE...]` - подбор -w/-l по образцам данных
+
+use std::io::{self, BufWriter, Read, Write};
+use std::process::exit;
+
+use heatshrink_rust::tune::{recommend, tune, Report};
+
+use crate::args::{Arg, Getopt};
+use crate::{open_input, ArgsError};
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), ArgsError> {
+    let mut max_ram = usize::MAX;
+    let mut files = Vec::new();
+
+    for arg in Getopt::new(args, "m") {
+        match arg.map_err(ArgsError::Invalid)? {
+            Arg::Opt('h') => return Err(ArgsError::Help),
+            Arg::OptValue('m', v) => {
+                max_ram = v
+                    .parse()
+                    .map_err(|_| ArgsError::Invalid(format!("invalid value for -m: {}", v)))?
+            }
+            Arg::Positional(p) => files.push(p),
+            Arg::Opt(opt) | Arg::OptValue(opt, _) => {
+                return Err(ArgsError::Invalid(format!("unknown option -{}", opt)))
+            }
+        }
+    }
+    if files.is_empty() {
+        files.push("-".to_string());
+    }
+
+    let mut samples = Vec::new();
+    for fname in &files {
+        let mut data = Vec::new();
+        if let Err(e) = open_input(fname).and_then(|mut f| f.read_to_end(&mut data)) {
+            eprintln!("heatshrink: {}: {}", fname, e);
+            exit(1);
+        }
+        samples.push(data);
+    }
+
+    let samples = samples.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    let reports = tune(&samples, max_ram);
+    if let Err(e) = print(&reports, max_ram, &mut BufWriter::new(io::stdout())) {
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("heatshrink: {}", e);
+            exit(1);
+        }
+    }
+    Ok(())
+}
+
+fn print(reports: &[Report], max_ram: usize, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(
+        out,
+        " -w  -l   ratio  compressed  enc RAM  dec RAM  enc MB/s  dec MB/s"
+    )?;
+    for r in reports {
+        writeln!(
+            out,
+            "{:3} {:3} {:6.2} % {:10} {:8} {:8} {:9.2} {:9.2}{}",
+            r.window_bits,
+            r.lookahead_bits,
+            100.0 * r.ratio(),
+            r.compressed_size,
+            r.encoder_ram,
+            r.decoder_ram,
+            r.encode_throughput() / 1e6,
+            r.decode_throughput() / 1e6,
+            if r.fits { "" } else { "  over budget" }
+        )?;
+    }
+
+    match recommend(reports) {
+        Some(r) => writeln!(
+            out,
+            "recommended: -w {} -l {} ({} bytes RAM, {:.2} %)",
+            r.window_bits,
+            r.lookahead_bits,
+            r.ram(),
+            100.0 * r.ratio()
+        )?,
+        None => writeln!(out, "no configuration fits in {} bytes of RAM", max_ram)?,
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use heatshrink_rust::tune::tune;
+
+    use crate::tune::print;
+
+    #[test]
+    fn table() {
+        let sample = b"tune tune tune tune".repeat(10);
+        let reports = tune(&[&sample], 2000);
+
+        let mut out = Vec::new();
+        print(&reports, 2000, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 1 + 78 + 1);
+        assert!(lines[1..79].iter().any(|l| l.ends_with("over budget")));
+        assert!(lines[79].starts_with("recommended: -w "));
+
+        let mut out = Vec::new();
+        print(&tune(&[&sample], 10), 10, &mut out).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with("no configuration fits in 10 bytes of RAM\n"));
+    }
+}
@@ -0,0 +1,246 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn heatshrink(args: &[&str], input: &[u8]) -> (Vec<u8>, String, bool) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_heatshrink"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        output.stdout,
+        String::from_utf8(output.stderr).unwrap(),
+        output.status.success(),
+    )
+}
+
+#[test]
+fn stdin_stdout() {
+    // те же байты, что выдает heatshrink -w 8 -l 4 для 8 нулей
+    let (out, _, ok) = heatshrink(&["-e", "-w", "8", "-l", "4"], &[0u8; 8]);
+    assert!(ok);
+    assert_eq!(out, [0x00, 0x38]);
+
+    let (out, _, ok) = heatshrink(&["-d", "-w", "8", "-l", "4"], &[0x00, 0x38]);
+    assert!(ok);
+    assert_eq!(out, [0u8; 8]);
+}
+
+#[test]
+fn files_and_verbose() {
+    let dir = std::env::temp_dir().join(format!("heatshrink-cli-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let src_path = dir.join("src.txt");
+    let packed_path = dir.join("src.txt.hs");
+
+    let src = include_bytes!("../src/main.rs");
+    std::fs::write(&src_path, &src[..]).unwrap();
+
+    let (stdout, _, ok) = heatshrink(
+        &[
+            "-v",
+            src_path.to_str().unwrap(),
+            packed_path.to_str().unwrap(),
+        ],
+        &[],
+    );
+    assert!(ok);
+    let packed = std::fs::read(&packed_path).unwrap();
+    let report = String::from_utf8(stdout).unwrap();
+    assert!(report.contains(&format!("{} -> {} (-w 11 -l 4)", src.len(), packed.len())));
+
+    let (out, report, ok) = heatshrink(&["-dv", packed_path.to_str().unwrap()], &[]);
+    assert!(ok);
+    assert_eq!(out, &src[..]);
+    assert!(report.contains(&format!("{} -> {}", packed.len(), src.len())));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn framed() {
+    use heatshrink_rust::io::EncoderWriter;
+
+    let mut encoder = EncoderWriter::new(Vec::new(), 8, 4).unwrap().framed();
+    for line in ["t=1 ok", "t=2 ok", "t=3 fail"] {
+        writeln!(encoder, "{}", line).unwrap();
+        encoder.flush().unwrap();
+    }
+    let packed = encoder.finish().unwrap();
+
+    let (out, _, ok) = heatshrink(&["-d", "-F", "-w", "8", "-l", "4"], &packed);
+    assert!(ok);
+    assert_eq!(out, b"t=1 ok\nt=2 ok\nt=3 fail\n");
+
+    let (_, err, ok) = heatshrink(
+        &["-d", "-F", "-w", "8", "-l", "4"],
+        &packed[..packed.len() - 1],
+    );
+    assert!(!ok);
+    assert!(err.contains("truncated"), "{}", err);
+}
+
+#[test]
+fn bad_args() {
+    let (_, stderr, ok) = heatshrink(&["-w", "3"], &[]);
+    assert!(!ok);
+    assert!(stderr.contains("Usage:"));
+}
+
+#[test]
+fn inspect() {
+    let (out, _, ok) = heatshrink(&["inspect", "-w", "8", "-l", "4"], &[0x00, 0x38]);
+    assert!(ok);
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("before start of output"));
+    assert!(out.ends_with("output: 8 bytes, trailing bits: 3\n"));
+
+    let (packed, _, _) = heatshrink(&[], b"inspect inspect inspect");
+    let (out, _, ok) = heatshrink(&["inspect", "-"], &packed);
+    assert!(ok);
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("literal  0x69 'i'"));
+    assert!(out.contains("output: 23 bytes"));
+}
+
+#[test]
+fn tune() {
+    let sample = b"tune tune tune tune".repeat(20);
+    let (out, _, ok) = heatshrink(&["tune", "-m", "4096"], &sample);
+    assert!(ok);
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.lines().last().unwrap().starts_with("recommended: -w "));
+}
+
+#[test]
+fn train() {
+    let dir = std::env::temp_dir().join(format!("heatshrink-cli-train-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dict_path = dir.join("dict");
+
+    let mut files = Vec::new();
+    for n in 0..20 {
+        let path = dir.join(format!("sample{}.json", n));
+        let sample = format!(r#"{{"id":{},"state":"idle","battery":{}}}"#, n, 90 - n);
+        std::fs::write(&path, sample).unwrap();
+        files.push(path.to_str().unwrap().to_string());
+    }
+
+    let mut args = vec![
+        "train",
+        "-w",
+        "8",
+        "-l",
+        "4",
+        "-o",
+        dict_path.to_str().unwrap(),
+    ];
+    args.extend(files.iter().map(String::as_str));
+    let (out, _, ok) = heatshrink(&args, &[]);
+    assert!(ok);
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("samples: 20, "));
+    assert!(out.contains("with dictionary:    "));
+
+    let message = br#"{"id":42,"state":"idle","battery":55}"#;
+    let (plain, _, _) = heatshrink(&["-w", "8", "-l", "4"], message);
+    let dict_arg = ["-w", "8", "-l", "4", "-D", dict_path.to_str().unwrap()];
+    let (packed, _, ok) = heatshrink(&dict_arg, message);
+    assert!(ok);
+    assert!(
+        packed.len() < plain.len() / 2,
+        "{} {}",
+        packed.len(),
+        plain.len()
+    );
+
+    let mut args = dict_arg.to_vec();
+    args.push("-d");
+    let (out, _, ok) = heatshrink(&args, &packed);
+    assert!(ok);
+    assert_eq!(out, message);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn blocks() {
+    let src = include_bytes!("../src/main.rs").repeat(20);
+    let (packed, _, ok) = heatshrink(&["blocks", "-b", "4096"], &src);
+    assert!(ok);
+    assert!(packed.starts_with(b"HK") && packed.len() < src.len());
+
+    let (out, report, ok) = heatshrink(&["blocks", "-dv"], &packed);
+    assert!(ok);
+    assert_eq!(out, src);
+    assert!(report.contains(&format!("{} -> {} bytes", packed.len(), src.len())));
+
+    let (_, stderr, ok) = heatshrink(&["blocks", "-d"], &src);
+    assert!(!ok);
+    assert!(stderr.contains("not a seekable heatshrink file"));
+    let (_, _, ok) = heatshrink(&["blocks", "-b", "0"], &[]);
+    assert!(!ok);
+}
+
+#[test]
+fn level_best() {
+    let src = include_bytes!("../src/main.rs");
+    let (default, _, ok) = heatshrink(&["-w", "10", "-l", "5"], src);
+    assert!(ok);
+    let (best, _, ok) = heatshrink(&["-w", "10", "-l", "5", "-L", "best"], src);
+    assert!(ok);
+    assert!(best.len() < default.len());
+
+    let (out, _, ok) = heatshrink(&["-d", "-w", "10", "-l", "5"], &best);
+    assert!(ok);
+    assert_eq!(out, &src[..]);
+
+    // без индекса поиска - тот же поток
+    let (fast, _, ok) = heatshrink(&["-w", "10", "-l", "5", "-L", "fast"], src);
+    assert!(ok);
+    assert_eq!(fast, default);
+}
+
+#[test]
+fn log() {
+    use heatshrink_rust::logger::HeatshrinkLogger;
+    use log::Log;
+    use std::sync::{Arc, Mutex};
+
+    let captured = Arc::new(Mutex::new(Vec::new()));
+    let sink = captured.clone();
+    let logger =
+        HeatshrinkLogger::new(move |data: &[u8]| sink.lock().unwrap().extend_from_slice(data));
+    for n in 0..40 {
+        logger.log(
+            &log::Record::builder()
+                .level(log::Level::Info)
+                .target("sensor")
+                .args(format_args!("temperature={}", 200 + n % 7))
+                .build(),
+        );
+    }
+    let packed = captured.lock().unwrap().clone();
+
+    // последние 8 строк еще в логгере
+    let (out, stderr, ok) = heatshrink(&["log"], &packed);
+    assert!(ok, "{}", stderr);
+    let text = String::from_utf8(out).unwrap();
+    assert_eq!(text.lines().count(), 32);
+    assert_eq!(text.lines().next(), Some("INFO  sensor: temperature=200"));
+
+    logger.flush();
+    let packed = captured.lock().unwrap().clone();
+    let (out, _, ok) = heatshrink(&["log"], &packed);
+    assert!(ok);
+    assert_eq!(String::from_utf8(out).unwrap().lines().count(), 40);
+
+    let (out, stderr, ok) = heatshrink(&["log"], &packed[..packed.len() - 2]);
+    assert!(ok);
+    assert_eq!(String::from_utf8(out).unwrap().lines().count(), 32);
+    assert!(stderr.contains("last block is incomplete"));
+}
@@ -1,14 +1,50 @@
 use std::path::PathBuf;
 
 use heatshrink_rust::encoder::HeatshrinkEncoder;
+use heatshrink_rust::Level;
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, LitByteStr, LitStr};
+use syn::parse::{Parse, ParseStream};
+use syn::{
+    parse_macro_input, Attribute, DeriveInput, Ident, Lit, LitByteStr, LitStr, Meta, NestedMeta,
+    Token,
+};
 
-fn puck<T: Iterator<Item = u8>>(iter: T, original_size: usize) -> TokenStream {
-    let encoder = HeatshrinkEncoder::source(iter);
+// `"..."` или `"...", level = fast|default|best`
+struct Input<T> {
+    value: T,
+    level: Level,
+}
+
+impl<T: Parse> Parse for Input<T> {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let value = input.parse()?;
+        let mut level = Level::Default;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let key: Ident = input.parse()?;
+            if key != "level" {
+                return Err(syn::Error::new_spanned(
+                    key,
+                    "expected level = fast|default|best",
+                ));
+            }
+            input.parse::<Token![=]>()?;
+            let value: Ident = input.parse()?;
+            level = value.to_string().parse().map_err(|_| {
+                syn::Error::new_spanned(&value, "unknown level, expected fast, default or best")
+            })?;
+            input.parse::<Option<Token![,]>>()?;
+        }
+        Ok(Self { value, level })
+    }
+}
 
-    let compressed = encoder.collect::<Vec<_>>();
+fn puck<T: Iterator<Item = u8>>(
+    iter: T,
+    original_size: usize,
+    level: Level,
+) -> proc_macro2::TokenStream {
+    let compressed = HeatshrinkEncoder::with_level(iter, level).collect::<Vec<_>>();
     // Эта штука правильно составит инстанс и правильно укажет тип элементов - u8.
     // Итерирование по образцу #(#_var_),* — the character before the asterisk is used as a separator
     quote! {
@@ -17,36 +53,136 @@ fn puck<T: Iterator<Item = u8>>(iter: T, original_size: usize) -> TokenStream {
             original_size: #original_size,
         }
     }
+}
+
+// LazyStr::new(CompressedData { .. }) и т.п.
+fn puck_lazy<T: Iterator<Item = u8>>(
+    lazy_type: &str,
+    iter: T,
+    original_size: usize,
+    level: Level,
+) -> TokenStream {
+    let lazy_type = proc_macro2::Ident::new(lazy_type, proc_macro2::Span::call_site());
+    let data = puck(iter, original_size, level);
+    quote! {
+        #lazy_type::new(#data)
+    }
     .into()
 }
 
+fn read_file(file: LitStr) -> Vec<u8> {
+    let path = PathBuf::from(file.value());
+    if !path.exists() {
+        panic!(
+            "file '{:?}' in '{:?}' not found",
+            path,
+            std::env::current_dir().unwrap()
+        );
+    }
+
+    std::fs::read(path).unwrap()
+}
+
+/// Во всех макросах уровень сжатия вторым аргументом: `packed_file!("path", level = best)`,
+/// см. [`heatshrink_rust::Level`], распаковывается тем же декодером
 #[proc_macro]
 pub fn packed_string(input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input as LitStr).value();
+    let Input { value, level } = parse_macro_input!(input as Input<LitStr>);
+    let input = value.value();
     let len = input.len();
-    puck(input.bytes().into_iter(), len)
+    puck(input.bytes(), len, level).into()
 }
 
 #[proc_macro]
 pub fn packed_bytes(input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input as LitByteStr).value();
+    let Input { value, level } = parse_macro_input!(input as Input<LitByteStr>);
+    let input = value.value();
     let len = input.len();
-    puck(input.into_iter(), len)
+    puck(input.into_iter(), len, level).into()
 }
 
 #[proc_macro]
 pub fn packed_file(file: TokenStream) -> TokenStream {
-    let infile = parse_macro_input!(file as LitStr).value();
-    let path = PathBuf::from(infile);
-    if !path.exists() {
-        panic!(
-            "file '{:?}' in '{:?}' not found",
-            path,
-            std::env::current_dir().unwrap()
-        );
-    }
+    let Input { value, level } = parse_macro_input!(file as Input<LitStr>);
+    let data = read_file(value);
+    let len = data.len();
+    puck(data.into_iter(), len, level).into()
+}
+
+/// `static HELP: LazyStr = packed_str_lazy!("...");`
+#[proc_macro]
+pub fn packed_str_lazy(input: TokenStream) -> TokenStream {
+    let Input { value, level } = parse_macro_input!(input as Input<LitStr>);
+    let input = value.value();
+    let len = input.len();
+    puck_lazy("LazyStr", input.bytes(), len, level)
+}
+
+/// `static DATA: LazyDecompressed = packed_bytes_lazy!(b"...");`
+#[proc_macro]
+pub fn packed_bytes_lazy(input: TokenStream) -> TokenStream {
+    let Input { value, level } = parse_macro_input!(input as Input<LitByteStr>);
+    let input = value.value();
+    let len = input.len();
+    puck_lazy("LazyDecompressed", input.into_iter(), len, level)
+}
 
-    let data = std::fs::read(path).unwrap();
+/// `static DATA: LazyDecompressed = packed_file_lazy!("path/to/file");`
+#[proc_macro]
+pub fn packed_file_lazy(file: TokenStream) -> TokenStream {
+    let Input { value, level } = parse_macro_input!(file as Input<LitStr>);
+    let data = read_file(value);
     let len = data.len();
-    puck(data.into_iter(), len)
+    puck_lazy("LazyDecompressed", data.into_iter(), len, level)
+}
+
+// #[heatshrink(version = N)], по умолчанию версия 0
+fn packed_version(attrs: &[Attribute]) -> syn::Result<u8> {
+    let mut version = 0;
+    for attr in attrs.iter().filter(|a| a.path.is_ident("heatshrink")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => {
+                return Err(syn::Error::new_spanned(
+                    meta,
+                    "expected #[heatshrink(version = N)]",
+                ))
+            }
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("version") => {
+                    match nv.lit {
+                        Lit::Int(v) => version = v.base10_parse()?,
+                        lit => return Err(syn::Error::new_spanned(lit, "version must be u8")),
+                    }
+                }
+                nested => {
+                    return Err(syn::Error::new_spanned(
+                        nested,
+                        "unknown heatshrink attribute",
+                    ))
+                }
+            }
+        }
+    }
+    Ok(version)
+}
+
+#[proc_macro_derive(HeatshrinkPacked, attributes(heatshrink))]
+pub fn derive_heatshrink_packed(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let version = match packed_version(&input.attrs) {
+        Ok(v) => v,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics heatshrink_rust::packed::HeatshrinkPacked for #name #ty_generics #where_clause {
+            const VERSION: u8 = #version;
+        }
+    }
+    .into()
 }
@@ -1,8 +1,14 @@
 
 mod tests {
     use heatshrink_rust::decoder::HeatshrinkDecoder;
+    use heatshrink_rust::packed::{self, HeatshrinkPacked};
+    use heatshrink_rust::lazy::{LazyDecompressed, LazyStr};
     use heatshrink_rust::CompressedData;
-    use heatshrink_rust_macro::{packed_string, packed_bytes, packed_file};
+    use heatshrink_rust_macro::{
+        packed_bytes, packed_bytes_lazy, packed_file, packed_file_lazy, packed_str_lazy,
+        packed_string, HeatshrinkPacked,
+    };
+    use serde::{Deserialize, Serialize};
 
     #[test]
     fn test_packed_string() {
@@ -22,10 +28,7 @@ mod tests {
 
     #[test]
     fn test_packed_file() {
-        #[cfg(not(target_os = "windows"))]
-        static FILE_DATA: &[u8; 1617] = include_bytes!("../src/lib.rs");
-        #[cfg(target_os = "windows")]
-        static FILE_DATA: &[u8; 1669] = include_bytes!("../src/lib.rs");
+        static FILE_DATA: &[u8] = include_bytes!("../src/lib.rs");
 
         static FILE_PACKED_DATA: CompressedData = packed_file!("heatshrink-rust-macro/src/lib.rs");
 
@@ -33,4 +36,71 @@ mod tests {
 
         assert_eq!(decoder.collect::<Vec<_>>().as_slice(), FILE_DATA);
     }
-}
\ No newline at end of file
+
+    #[derive(Serialize, Deserialize, HeatshrinkPacked, Debug, PartialEq)]
+    #[heatshrink(version = 3)]
+    struct Config {
+        name: [u8; 8],
+        baudrate: u32,
+        coeffs: [f32; 4],
+        enabled: bool,
+    }
+
+    #[derive(Serialize, Deserialize, HeatshrinkPacked, Debug, PartialEq)]
+    struct ConfigV0 {
+        baudrate: u32,
+    }
+
+    #[test]
+    fn test_derive_packed() {
+        let config = Config {
+            name: *b"uart0\0\0\0",
+            baudrate: 115200,
+            coeffs: [1.0, 0.0, 0.0, 1.0],
+            enabled: true,
+        };
+        let mut buf = [0u8; 64];
+
+        let size = config.pack(&mut buf).unwrap();
+        assert_eq!(buf[0], Config::VERSION);
+        assert_eq!(Config::unpack(&buf[..size]).unwrap(), config);
+
+        assert_eq!(ConfigV0::VERSION, 0);
+        assert_eq!(
+            ConfigV0::unpack(&buf[..size]),
+            Err(packed::Error::VersionMismatch {
+                expected: 0,
+                found: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_packed_lazy() {
+        static HELP: LazyStr = packed_str_lazy!("Использование: prog [-h] <файл>");
+        static BYTES: LazyDecompressed = packed_bytes_lazy!(b"my test string");
+        static FILE: LazyDecompressed = packed_file_lazy!("heatshrink-rust-macro/src/lib.rs");
+
+        assert_eq!(&*HELP, "Использование: prog [-h] <файл>");
+        assert!(HELP.starts_with("Использование"));
+        assert_eq!(&*BYTES, b"my test string");
+        assert_eq!(&*FILE, include_bytes!("../src/lib.rs"));
+    }
+
+    #[test]
+    fn test_level_best() {
+        static FILE_DATA: &[u8] = include_bytes!("../src/lib.rs");
+        static DEFAULT: CompressedData = packed_file!("heatshrink-rust-macro/src/lib.rs");
+        static BEST: CompressedData =
+            packed_file!("heatshrink-rust-macro/src/lib.rs", level = best);
+        static BYTES: CompressedData = packed_bytes!(b"abcabcabcabc", level = fast);
+        static LAZY: LazyStr = packed_str_lazy!("ля-ля-ля-ля-ля", level = best);
+
+        assert!(BEST.data.len() <= DEFAULT.data.len());
+        let decoder = HeatshrinkDecoder::source(BEST.data.iter().cloned());
+        assert_eq!(decoder.collect::<Vec<_>>().as_slice(), FILE_DATA);
+        let decoder = HeatshrinkDecoder::source(BYTES.data.iter().cloned());
+        assert_eq!(decoder.collect::<Vec<_>>(), b"abcabcabcabc");
+        assert_eq!(&*LAZY, "ля-ля-ля-ля-ля");
+    }
+}
@@ -1,3 +1,27 @@
+// Публичные символы heatshrink, для копии с HEATSHRINK_DYNAMIC_ALLOC=1 они получают префикс heatshrink_dyn_
+const DYNAMIC_SYMBOLS: [&str; 12] = [
+    "heatshrink_encoder_alloc",
+    "heatshrink_encoder_free",
+    "heatshrink_encoder_reset",
+    "heatshrink_encoder_sink",
+    "heatshrink_encoder_poll",
+    "heatshrink_encoder_finish",
+    "heatshrink_decoder_alloc",
+    "heatshrink_decoder_free",
+    "heatshrink_decoder_reset",
+    "heatshrink_decoder_sink",
+    "heatshrink_decoder_poll",
+    "heatshrink_decoder_finish",
+];
+
+// Кодер без индекса поиска для Level::Fast, префикс heatshrink_compact_
+const COMPACT_SYMBOLS: [&str; 4] = [
+    "heatshrink_encoder_reset",
+    "heatshrink_encoder_sink",
+    "heatshrink_encoder_poll",
+    "heatshrink_encoder_finish",
+];
+
 fn main() {
     let src = [
         "../heatshrink-dist/heatshrink_decoder.c",
@@ -15,4 +39,57 @@ fn main() {
     let builder = builder.flag("-Wno-implicit-fallthrough");
 
     builder.compile("heatshrink");
-}
\ No newline at end of file
+
+    // Тот же статический кодер без индекса: меньше RAM, поток тот же
+    let out_dir = std::path::PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
+    let mut builder = cc::Build::new();
+    let builder = builder
+        .file(src[1])
+        .out_dir(out_dir.join("compact"))
+        .opt_level_str("s")
+        .define("HEATSHRINK_DYNAMIC_ALLOC", Some("0"))
+        .define("HEATSHRINK_USE_INDEX", Some("0"));
+    for symbol in COMPACT_SYMBOLS.iter() {
+        let renamed = symbol.replacen("heatshrink_", "heatshrink_compact_", 1);
+        builder.define(symbol, Some(renamed.as_str()));
+    }
+    #[cfg(not(target_os = "windows"))]
+    let builder = builder.flag("-Wno-implicit-fallthrough");
+
+    builder.compile("heatshrink_compact");
+
+    // Размер окна и lookahead задаются в рантайме, память через malloc(), только для std
+    if std::env::var_os("CARGO_FEATURE_STD").is_some() {
+        let mut builder = cc::Build::new();
+        let builder = builder
+            .files(src.iter())
+            .out_dir(out_dir.join("dynamic"))
+            .opt_level_str("s")
+            .define("HEATSHRINK_DYNAMIC_ALLOC", Some("1"));
+        for symbol in DYNAMIC_SYMBOLS.iter() {
+            let renamed = symbol.replacen("heatshrink_", "heatshrink_dyn_", 1);
+            builder.define(symbol, Some(renamed.as_str()));
+        }
+        #[cfg(not(target_os = "windows"))]
+        let builder = builder.flag("-Wno-implicit-fallthrough");
+
+        builder.compile("heatshrink_dynamic");
+
+        // Кодер без индекса для DynamicEncoder с Level::Fast, префикс heatshrink_dyn_compact_
+        let mut builder = cc::Build::new();
+        let builder = builder
+            .file(src[1])
+            .out_dir(out_dir.join("dynamic_compact"))
+            .opt_level_str("s")
+            .define("HEATSHRINK_DYNAMIC_ALLOC", Some("1"))
+            .define("HEATSHRINK_USE_INDEX", Some("0"));
+        for symbol in DYNAMIC_SYMBOLS.iter().filter(|s| s.contains("_encoder_")) {
+            let renamed = symbol.replacen("heatshrink_", "heatshrink_dyn_compact_", 1);
+            builder.define(symbol, Some(renamed.as_str()));
+        }
+        #[cfg(not(target_os = "windows"))]
+        let builder = builder.flag("-Wno-implicit-fallthrough");
+
+        builder.compile("heatshrink_dynamic_compact");
+    }
+}
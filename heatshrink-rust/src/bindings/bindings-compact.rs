@@ -0,0 +1,38 @@
+// Кодер heatshrink без индекса поиска (HEATSHRINK_USE_INDEX=0), префикс heatshrink_compact_
+// (см. build.rs). Параметры те же, что у основной библиотеки
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct _heatshrink_encoder_compact {
+    input_size: u16,
+    match_scan_index: u16,
+    match_length: u16,
+    match_pos: u16,
+    outgoing_bits: u16,
+    outgoing_bits_count: u8,
+    flags: u8,
+    state: u8,
+    current_byte: u8,
+    bit_index: u8,
+    // Входной буфер
+    buffer: [u8; (2 << HEATSHRINK_STATIC_WINDOW_BITS) as usize],
+}
+
+extern "C" {
+    pub(crate) fn heatshrink_compact_encoder_reset(hse: *mut _heatshrink_encoder_compact);
+    pub(crate) fn heatshrink_compact_encoder_sink(
+        hse: *mut _heatshrink_encoder_compact,
+        in_buf: *const u8,
+        size: size_t,
+        input_size: *mut size_t,
+    ) -> HSE_sink_res;
+    pub(crate) fn heatshrink_compact_encoder_poll(
+        hse: *mut _heatshrink_encoder_compact,
+        out_buf: *mut u8,
+        out_buf_size: size_t,
+        output_size: *mut size_t,
+    ) -> HSE_poll_res;
+    pub(crate) fn heatshrink_compact_encoder_finish(
+        hse: *mut _heatshrink_encoder_compact,
+    ) -> HSE_finish_res;
+}
@@ -1,6 +1,6 @@
 
-pub const HEATSHRINK_AUTHOR: &'static [u8; 32usize] = b"Scott Vokes <vokes.s@gmail.com>\0";
-pub const HEATSHRINK_URL: &'static [u8; 43usize] = b"https://github.com/atomicobject/heatshrink\0";
+pub const HEATSHRINK_AUTHOR: &[u8; 32usize] = b"Scott Vokes <vokes.s@gmail.com>\0";
+pub const HEATSHRINK_URL: &[u8; 43usize] = b"https://github.com/atomicobject/heatshrink\0";
 
 pub type size_t = usize;
 
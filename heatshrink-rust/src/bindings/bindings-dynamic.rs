@@ -0,0 +1,125 @@
+// heatshrink собранный с HEATSHRINK_DYNAMIC_ALLOC=1 и префиксом heatshrink_dyn_ (см. build.rs)
+
+#[repr(C)]
+#[derive(Debug)]
+pub(crate) struct heatshrink_dyn_encoder {
+    pub(crate) input_size: u16,
+    pub(crate) match_scan_index: u16,
+    pub(crate) match_length: u16,
+    pub(crate) match_pos: u16,
+    pub(crate) outgoing_bits: u16,
+    pub(crate) outgoing_bits_count: u8,
+    pub(crate) flags: u8,
+    pub(crate) state: u8,
+    pub(crate) current_byte: u8,
+    pub(crate) bit_index: u8,
+    pub(crate) window_sz2: u8,
+    pub(crate) lookahead_sz2: u8,
+    // HEATSHRINK_USE_INDEX
+    pub(crate) search_index: *mut u16,
+    // Входной буфер, размер 2 << window_sz2
+    pub(crate) buffer: [u8; 0],
+}
+
+// Тот же кодер без индекса поиска (HEATSHRINK_USE_INDEX=0), префикс heatshrink_dyn_compact_
+#[repr(C)]
+#[derive(Debug)]
+pub(crate) struct heatshrink_dyn_compact_encoder {
+    pub(crate) input_size: u16,
+    pub(crate) match_scan_index: u16,
+    pub(crate) match_length: u16,
+    pub(crate) match_pos: u16,
+    pub(crate) outgoing_bits: u16,
+    pub(crate) outgoing_bits_count: u8,
+    pub(crate) flags: u8,
+    pub(crate) state: u8,
+    pub(crate) current_byte: u8,
+    pub(crate) bit_index: u8,
+    pub(crate) window_sz2: u8,
+    pub(crate) lookahead_sz2: u8,
+    // Входной буфер, размер 2 << window_sz2
+    pub(crate) buffer: [u8; 0],
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub(crate) struct heatshrink_dyn_decoder {
+    pub(crate) input_size: u16,
+    pub(crate) input_index: u16,
+    pub(crate) output_count: u16,
+    pub(crate) output_index: u16,
+    pub(crate) head_index: u16,
+    pub(crate) state: u8,
+    pub(crate) current_byte: u8,
+    pub(crate) bit_index: u8,
+    pub(crate) input_buffer_size: u16,
+    pub(crate) window_sz2: u8,
+    pub(crate) lookahead_sz2: u8,
+    // input_buffer_size байт входного буфера + (1 << window_sz2) байт окна
+    pub(crate) buffers: [u8; 0],
+}
+
+extern "C" {
+    pub(crate) fn heatshrink_dyn_encoder_alloc(
+        window_sz2: u8,
+        lookahead_sz2: u8,
+    ) -> *mut heatshrink_dyn_encoder;
+    pub(crate) fn heatshrink_dyn_encoder_free(hse: *mut heatshrink_dyn_encoder);
+    pub(crate) fn heatshrink_dyn_encoder_reset(hse: *mut heatshrink_dyn_encoder);
+    pub(crate) fn heatshrink_dyn_encoder_sink(
+        hse: *mut heatshrink_dyn_encoder,
+        in_buf: *const u8,
+        size: usize,
+        input_size: *mut usize,
+    ) -> i32;
+    pub(crate) fn heatshrink_dyn_encoder_poll(
+        hse: *mut heatshrink_dyn_encoder,
+        out_buf: *mut u8,
+        out_buf_size: usize,
+        output_size: *mut usize,
+    ) -> i32;
+    pub(crate) fn heatshrink_dyn_encoder_finish(hse: *mut heatshrink_dyn_encoder) -> i32;
+
+    pub(crate) fn heatshrink_dyn_compact_encoder_alloc(
+        window_sz2: u8,
+        lookahead_sz2: u8,
+    ) -> *mut heatshrink_dyn_compact_encoder;
+    pub(crate) fn heatshrink_dyn_compact_encoder_free(hse: *mut heatshrink_dyn_compact_encoder);
+    pub(crate) fn heatshrink_dyn_compact_encoder_reset(hse: *mut heatshrink_dyn_compact_encoder);
+    pub(crate) fn heatshrink_dyn_compact_encoder_sink(
+        hse: *mut heatshrink_dyn_compact_encoder,
+        in_buf: *const u8,
+        size: usize,
+        input_size: *mut usize,
+    ) -> i32;
+    pub(crate) fn heatshrink_dyn_compact_encoder_poll(
+        hse: *mut heatshrink_dyn_compact_encoder,
+        out_buf: *mut u8,
+        out_buf_size: usize,
+        output_size: *mut usize,
+    ) -> i32;
+    pub(crate) fn heatshrink_dyn_compact_encoder_finish(
+        hse: *mut heatshrink_dyn_compact_encoder,
+    ) -> i32;
+
+    pub(crate) fn heatshrink_dyn_decoder_alloc(
+        input_buffer_size: u16,
+        window_sz2: u8,
+        lookahead_sz2: u8,
+    ) -> *mut heatshrink_dyn_decoder;
+    pub(crate) fn heatshrink_dyn_decoder_free(hsd: *mut heatshrink_dyn_decoder);
+    pub(crate) fn heatshrink_dyn_decoder_reset(hsd: *mut heatshrink_dyn_decoder);
+    pub(crate) fn heatshrink_dyn_decoder_sink(
+        hsd: *mut heatshrink_dyn_decoder,
+        in_buf: *const u8,
+        size: usize,
+        input_size: *mut usize,
+    ) -> i32;
+    pub(crate) fn heatshrink_dyn_decoder_poll(
+        hsd: *mut heatshrink_dyn_decoder,
+        out_buf: *mut u8,
+        out_buf_size: usize,
+        output_size: *mut usize,
+    ) -> i32;
+    pub(crate) fn heatshrink_dyn_decoder_finish(hsd: *mut heatshrink_dyn_decoder) -> i32;
+}
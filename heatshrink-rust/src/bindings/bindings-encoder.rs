@@ -1,6 +1,6 @@
 /* automatically generated by rust-bindgen 0.59.1 */
-pub(crate) const HEATSHRINK_AUTHOR: &'static [u8; 32usize] = b"Scott Vokes <vokes.s@gmail.com>\0";
-pub(crate) const HEATSHRINK_URL: &'static [u8; 43usize] =
+pub(crate) const HEATSHRINK_AUTHOR: &[u8; 32usize] = b"Scott Vokes <vokes.s@gmail.com>\0";
+pub(crate) const HEATSHRINK_URL: &[u8; 43usize] =
     b"https://github.com/atomicobject/heatshrink\0";
 pub(crate) const HEATSHRINK_VERSION_MAJOR: u32 = 0;
 pub(crate) const HEATSHRINK_VERSION_MINOR: u32 = 4;
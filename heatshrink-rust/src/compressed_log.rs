@@ -0,0 +1,511 @@
+//! Кольцевой сжатый лог во flash: записи сжимаются в страницы [`Storage`], при заполнении
+//! стирается самая старая страница.
+//!
+//! ```text
+//! страница: seq: u32 LE | len: u32 LE | сжатые данные: len байт | стерто (0xff)
+//! распакованная страница: (varint(длина записи) | запись)*, как в crate::record_stream
+//! ```
+//! Каждая страница - отдельный поток heatshrink с чистым окном, читается без соседних.
+//! Текущая страница копится в RAM и пишется целиком, когда следующая запись в нее
+//! не влезет или по [`CompressedLog::flush`]. Сначала пишутся данные, потом заголовок:
+//! страница, недописанная из-за сброса питания, выглядит стертой.
+//! `seq` растет на 1 с каждой страницей, по нему [`CompressedLog::new`] находит,
+//! куда писать дальше, а итератор - порядок страниц.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::decoder::HeatshrinkDecoder;
+use crate::encoder_common::Context;
+use crate::record_stream::{read_varint, write_varint};
+use crate::{compress_bound, Level, STATIC_LOOKAHEAD_BITS, STATIC_WINDOW_BITS};
+
+pub const HEADER_SIZE: usize = 8;
+// seq стертой страницы
+const ERASED: u32 = u32::MAX;
+
+/// Память со страничным стиранием, как NOR flash: после [`Storage::erase`] страница
+/// заполнена 0xff, каждый байт пишется один раз
+pub trait Storage {
+    type Error;
+
+    fn page_size(&self) -> usize;
+    fn page_count(&self) -> usize;
+    fn read(&self, page: usize, offset: usize, buf: &mut [u8]) -> Result<(), Self::Error>;
+    fn write(&mut self, page: usize, offset: usize, data: &[u8]) -> Result<(), Self::Error>;
+    fn erase(&mut self, page: usize) -> Result<(), Self::Error>;
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Error<E> {
+    Storage(E),
+    /// Запись не помещается даже в пустую страницу
+    RecordTooLarge,
+    /// Страница не распаковывается в целые записи
+    Corrupt {
+        page: usize,
+    },
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Storage(e) => write!(f, "storage: {}", e),
+            Error::RecordTooLarge => write!(f, "record does not fit in a page"),
+            Error::Corrupt { page } => write!(f, "page {} is corrupt", page),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: core::fmt::Debug + core::fmt::Display> std::error::Error for Error<E> {}
+
+// (seq, len) страницы, None - стерта или заголовок не сходится с размером страницы
+fn read_header<S: Storage>(storage: &S, page: usize) -> Result<Option<(u32, usize)>, S::Error> {
+    let mut header = [0u8; HEADER_SIZE];
+    storage.read(page, 0, &mut header)?;
+    let seq = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    if seq == ERASED || len > storage.page_size() - HEADER_SIZE {
+        return Ok(None);
+    }
+    Ok(Some((seq, len)))
+}
+
+// Больше страница с `capacity` байт сжатых данных не распакуется: токен не короче 9 бит
+// и дает не больше 2^lookahead байт. Больше - страница испорчена
+const fn page_limit(capacity: usize) -> usize {
+    // capacity * 8 / 9 токенов, с запасом и без переполнения
+    (capacity / 9 + 1).saturating_mul(8 << STATIC_LOOKAHEAD_BITS)
+}
+
+pub struct CompressedLog<S: Storage> {
+    storage: S,
+    ctx: Context,
+    // сжатые данные текущей страницы
+    page: Vec<u8>,
+    records: usize,
+    // куда и с каким seq запишется текущая страница
+    next: usize,
+    seq: u32,
+}
+
+impl<S: Storage> CompressedLog<S> {
+    /// Продолжить лог, уже записанный в `storage`, или начать в стертой.
+    /// Паника, если страница не больше заголовка
+    pub fn new(storage: S) -> Result<Self, Error<S::Error>> {
+        assert!(storage.page_size() > HEADER_SIZE, "page too small");
+        let mut newest = None;
+        for page in 0..storage.page_count() {
+            if let Some((seq, _)) = read_header(&storage, page).map_err(Error::Storage)? {
+                if !matches!(newest, Some((s, _)) if s >= seq) {
+                    newest = Some((seq, page));
+                }
+            }
+        }
+        let (next, seq) = match newest {
+            Some((seq, page)) => ((page + 1) % storage.page_count(), seq + 1),
+            None => (0, 0),
+        };
+
+        Ok(Self {
+            page: Vec::with_capacity(storage.page_size()),
+            storage,
+            ctx: Context::new(Level::Default),
+            records: 0,
+            next,
+            seq,
+        })
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Записи, не сброшенные [`CompressedLog::flush`], теряются
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    fn capacity(&self) -> usize {
+        self.storage.page_size() - HEADER_SIZE
+    }
+
+    /// Добавить запись. Если она не влезает в текущую страницу, та пишется в storage
+    /// (стирая самую старую), запись идет в следующую
+    pub fn push(&mut self, record: &[u8]) -> Result<(), Error<S::Error>> {
+        let len = u32::try_from(record.len()).map_err(|_| Error::RecordTooLarge)?;
+        let mut prefix = Vec::with_capacity(5);
+        write_varint(&mut prefix, len);
+        if self.try_push(&prefix, record) {
+            return Ok(());
+        }
+        if self.records == 0 {
+            return Err(Error::RecordTooLarge);
+        }
+        self.flush()?;
+        if self.try_push(&prefix, record) {
+            Ok(())
+        } else {
+            Err(Error::RecordTooLarge)
+        }
+    }
+
+    // false - не влезло, страница как была
+    fn try_push(&mut self, prefix: &[u8], record: &[u8]) -> bool {
+        let len = prefix.len() + record.len();
+        // необработанное во входном буфере + запись, даже одними литералами, +1 байт
+        // недописанных бит - точно влезет, иначе проверяем точно на копии кодера
+        let bound = self.page.len()
+            + compress_bound(
+                self.ctx.input_size() as usize + len,
+                STATIC_WINDOW_BITS,
+                STATIC_LOOKAHEAD_BITS,
+            )
+            + 1;
+        let snapshot = if bound <= self.capacity() {
+            None
+        } else {
+            Some((self.ctx.clone(), self.page.len()))
+        };
+
+        self.ctx.sink_all(prefix, &mut self.page);
+        self.ctx.sink_all(record, &mut self.page);
+
+        if let Some((ctx, page_len)) = snapshot {
+            let mut trial = self.ctx.clone();
+            let mut tail = Vec::new();
+            trial.finish_all(&mut tail);
+            if self.page.len() + tail.len() > self.capacity() {
+                self.ctx = ctx;
+                self.page.truncate(page_len);
+                return false;
+            }
+        }
+        self.records += 1;
+        true
+    }
+
+    /// Записать текущую страницу, даже неполную: после этого записи переживут сброс питания.
+    /// Остаток страницы пропадает, часто вызывать не стоит
+    pub fn flush(&mut self) -> Result<(), Error<S::Error>> {
+        if self.records == 0 {
+            return Ok(());
+        }
+        self.ctx.finish_all(&mut self.page);
+
+        let mut header = [0u8; HEADER_SIZE];
+        header[..4].copy_from_slice(&self.seq.to_le_bytes());
+        header[4..].copy_from_slice(&(self.page.len() as u32).to_le_bytes());
+        self.storage.erase(self.next).map_err(Error::Storage)?;
+        self.storage
+            .write(self.next, HEADER_SIZE, &self.page)
+            .map_err(Error::Storage)?;
+        self.storage
+            .write(self.next, 0, &header)
+            .map_err(Error::Storage)?;
+
+        self.next = (self.next + 1) % self.storage.page_count();
+        self.seq += 1;
+        self.page.clear();
+        self.records = 0;
+        self.ctx.reset();
+        Ok(())
+    }
+
+    /// Все сохранившиеся записи от старых к новым, включая еще не сброшенные
+    pub fn iter(&self) -> Result<Records<'_, S>, Error<S::Error>> {
+        let mut pages = Vec::new();
+        for page in 0..self.storage.page_count() {
+            if let Some((seq, _)) = read_header(&self.storage, page).map_err(Error::Storage)? {
+                pages.push((seq, page));
+            }
+        }
+        // от новых к старым, следующая - pop()
+        pages.sort_unstable_by(|a, b| b.cmp(a));
+
+        let pending = if self.records > 0 {
+            let mut ctx = self.ctx.clone();
+            let mut page = self.page.clone();
+            ctx.finish_all(&mut page);
+            Some(page)
+        } else {
+            None
+        };
+        Ok(Records {
+            storage: &self.storage,
+            pages: pages.into_iter().map(|(_, page)| page).collect(),
+            pending,
+            block: Vec::new(),
+            rp: 0,
+            page: 0,
+            failed: false,
+        })
+    }
+}
+
+/// Итератор [`CompressedLog::iter`], после ошибки записей больше нет
+pub struct Records<'a, S: Storage> {
+    storage: &'a S,
+    pages: Vec<usize>,
+    pending: Option<Vec<u8>>,
+    // распакованная текущая страница
+    block: Vec<u8>,
+    rp: usize,
+    page: usize,
+    failed: bool,
+}
+
+impl<S: Storage> Records<'_, S> {
+    // распаковать следующую страницу, false - страниц больше нет
+    fn load(&mut self) -> Result<bool, Error<S::Error>> {
+        let packed = if let Some(page) = self.pages.pop() {
+            let len = read_header(self.storage, page)
+                .map_err(Error::Storage)?
+                .ok_or(Error::Corrupt { page })?
+                .1;
+            let mut packed = vec![0; len];
+            self.storage
+                .read(page, HEADER_SIZE, &mut packed)
+                .map_err(Error::Storage)?;
+            self.page = page;
+            packed
+        } else if let Some(packed) = self.pending.take() {
+            self.page = self.storage.page_count();
+            packed
+        } else {
+            return Ok(false);
+        };
+        let limit = page_limit(self.storage.page_size() - HEADER_SIZE);
+        let mut decoder = HeatshrinkDecoder::source(packed.into_iter()).with_limit(limit);
+        self.block = decoder.by_ref().collect();
+        if decoder.error().is_some() {
+            return Err(Error::Corrupt { page: self.page });
+        }
+        self.rp = 0;
+        Ok(true)
+    }
+
+    fn advance(&mut self) -> Result<Option<Vec<u8>>, Error<S::Error>> {
+        while self.rp == self.block.len() {
+            if !self.load()? {
+                return Ok(None);
+            }
+        }
+        let page = self.page;
+        let (len, start) =
+            read_varint(&self.block, self.rp).map_err(|_| Error::Corrupt { page })?;
+        if len > self.block.len() - start {
+            return Err(Error::Corrupt { page });
+        }
+        self.rp = start + len;
+        Ok(Some(self.block[start..self.rp].to_vec()))
+    }
+}
+
+impl<S: Storage> Iterator for Records<'_, S> {
+    type Item = Result<Vec<u8>, Error<S::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let res = self.advance().transpose();
+        self.failed = matches!(res, Some(Err(_)));
+        res
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MemError {
+    OutOfRange,
+    /// Запись в нестертый байт
+    NotErased,
+}
+
+impl core::fmt::Display for MemError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MemError::OutOfRange => write!(f, "out of range"),
+            MemError::NotErased => write!(f, "write to non-erased byte"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MemError {}
+
+/// [`Storage`] в RAM для тестов, с правилами flash: писать только в стертое
+pub struct MemStorage {
+    data: Vec<u8>,
+    page_size: usize,
+    erases: usize,
+}
+
+impl MemStorage {
+    /// Все страницы стерты
+    pub fn new(page_size: usize, page_count: usize) -> Self {
+        Self {
+            data: vec![0xff; page_size * page_count],
+            page_size,
+            erases: 0,
+        }
+    }
+
+    /// Сколько раз стирались страницы, для оценки износа
+    pub fn erases(&self) -> usize {
+        self.erases
+    }
+
+    fn range(&self, page: usize, offset: usize, len: usize) -> Result<usize, MemError> {
+        if page >= self.page_count() || offset + len > self.page_size {
+            return Err(MemError::OutOfRange);
+        }
+        Ok(page * self.page_size + offset)
+    }
+}
+
+impl Storage for MemStorage {
+    type Error = MemError;
+
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn page_count(&self) -> usize {
+        self.data.len() / self.page_size
+    }
+
+    fn read(&self, page: usize, offset: usize, buf: &mut [u8]) -> Result<(), MemError> {
+        let start = self.range(page, offset, buf.len())?;
+        buf.copy_from_slice(&self.data[start..start + buf.len()]);
+        Ok(())
+    }
+
+    fn write(&mut self, page: usize, offset: usize, data: &[u8]) -> Result<(), MemError> {
+        let start = self.range(page, offset, data.len())?;
+        let dest = &mut self.data[start..start + data.len()];
+        if dest.iter().any(|b| *b != 0xff) {
+            return Err(MemError::NotErased);
+        }
+        dest.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn erase(&mut self, page: usize) -> Result<(), MemError> {
+        let start = self.range(page, 0, self.page_size)?;
+        self.data[start..start + self.page_size].fill(0xff);
+        self.erases += 1;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    use std::format;
+    use std::vec::Vec;
+
+    use crate::compressed_log::{
+        read_header, CompressedLog, Error, MemStorage, Storage, HEADER_SIZE,
+    };
+
+    fn record(n: usize) -> Vec<u8> {
+        format!("{} temperature={} state=ok", n, 200 + n % 37).into_bytes()
+    }
+
+    fn collect(log: &CompressedLog<MemStorage>) -> Vec<Vec<u8>> {
+        log.iter().unwrap().map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn reopen() {
+        let mut log = CompressedLog::new(MemStorage::new(256, 8)).unwrap();
+        let src = (0..100).map(record).collect::<Vec<_>>();
+        src.iter().for_each(|r| log.push(r).unwrap());
+        log.push(b"").unwrap();
+        let mut expected = src.clone();
+        expected.push(Vec::new());
+        // последняя страница еще в RAM
+        assert_eq!(collect(&log), expected);
+
+        log.flush().unwrap();
+        let log = CompressedLog::new(log.into_storage()).unwrap();
+        assert_eq!(collect(&log), expected);
+        let pages = (0..8)
+            .filter(|p| read_header(log.storage(), *p).unwrap().is_some())
+            .count();
+        assert!(pages < 8, "{}", pages);
+    }
+
+    #[test]
+    fn eviction() {
+        let mut log = CompressedLog::new(MemStorage::new(256, 4)).unwrap();
+        for n in 0..2000 {
+            log.push(&record(n)).unwrap();
+        }
+        let res = collect(&log);
+        let first = std::str::from_utf8(&res[0]).unwrap();
+        let first = first.split(' ').next().unwrap().parse::<usize>().unwrap();
+        assert!(first > 1000);
+        assert_eq!(res, (first..2000).map(record).collect::<Vec<_>>());
+        assert!(log.storage().erases() > 50);
+
+        // страницы заполнены почти целиком
+        for page in 0..4 {
+            let (_, len) = read_header(log.storage(), page).unwrap().unwrap();
+            assert!(len > 256 - HEADER_SIZE - 16, "{}", len);
+        }
+    }
+
+    #[test]
+    fn independent_pages() {
+        let mut log = CompressedLog::new(MemStorage::new(256, 4)).unwrap();
+        for n in 0..200 {
+            log.push(&record(n)).unwrap();
+        }
+        log.flush().unwrap();
+        let all = collect(&log);
+        let mut storage = log.into_storage();
+        // без страницы из середины остальные читаются
+        let mut pages = (0..4)
+            .map(|p| (read_header(&storage, p).unwrap().unwrap().0, p))
+            .collect::<Vec<_>>();
+        pages.sort_unstable();
+        storage.erase(pages[1].1).unwrap();
+        let log = CompressedLog::new(storage).unwrap();
+        let res = collect(&log);
+        assert!(res.len() < all.len());
+        assert!(res.iter().all(|r| all.contains(r)));
+        assert_eq!(res.first(), all.first());
+        assert_eq!(res.last(), all.last());
+    }
+
+    #[test]
+    fn errors() {
+        let mut log = CompressedLog::new(MemStorage::new(64, 2)).unwrap();
+        let noise = (0..100u32)
+            .map(|n| ((n * n * 37) >> 3) as u8)
+            .collect::<Vec<_>>();
+        assert_eq!(log.push(&noise), Err(Error::RecordTooLarge));
+        log.push(&noise[..40]).unwrap();
+        assert_eq!(collect(&log), [&noise[..40]]);
+
+        let mut storage = MemStorage::new(64, 2);
+        storage.write(0, 0, b"x").unwrap();
+        assert_eq!(
+            storage.write(0, 0, b"y"),
+            Err(crate::compressed_log::MemError::NotErased)
+        );
+        // мусор вместо сжатых данных
+        storage.erase(0).unwrap();
+        storage.write(0, 0, &[0, 0, 0, 0, 4, 0, 0, 0]).unwrap();
+        storage
+            .write(0, HEADER_SIZE, &[0xff, 0x00, 0x12, 0x34])
+            .unwrap();
+        let log = CompressedLog::new(storage).unwrap();
+        let res = log.iter().unwrap().collect::<Vec<_>>();
+        assert_eq!(res.last(), Some(&Err(Error::Corrupt { page: 0 })));
+    }
+}
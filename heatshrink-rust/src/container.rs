@@ -0,0 +1,434 @@
+//! Самоописывающий формат: заголовок с параметрами сжатия, затем поток heatshrink.
+//!
+//! ```text
+//! "HS" | (window_bits << 4) | lookahead_bits | флаги | [id словаря, u32 LE] |
+//! [число фильтров | фильтры, по байту] | сжатые данные
+//! ```
+//! id словаря есть только с флагом [`FLAG_DICTIONARY`], см. [`crate::dictionary::id`].
+//! Фильтры - только с [`FLAG_FILTERS`], после распаковки они обращаются автоматически,
+//! см. [`crate::filter`].
+//! С флагом [`FLAG_STORED`] данные лежат как есть: сжатие их бы только увеличило
+//! (шифрованное, уже сжатое), см. также [`crate::estimate`].
+
+use alloc::vec::Vec;
+
+use crate::decoder::HeatshrinkDecoder;
+use crate::dictionary;
+use crate::encoder::HeatshrinkEncoder;
+use crate::filter::{Filter, FilterChain, FilterKind, MAX_FILTERS};
+use crate::{valid_params, Level, STATIC_LOOKAHEAD_BITS, STATIC_WINDOW_BITS};
+
+pub const MAGIC: [u8; 2] = *b"HS";
+/// Данные сжаты со словарем, за флагами идет его id
+pub const FLAG_DICTIONARY: u8 = 0x01;
+/// Данные не сжаты, словарь для них не нужен
+pub const FLAG_STORED: u8 = 0x02;
+/// Перед сжатием применены фильтры, их список после id словаря
+pub const FLAG_FILTERS: u8 = 0x04;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Error {
+    /// Данные кончились раньше заголовка
+    Truncated,
+    /// Не начинается с [`MAGIC`]
+    BadMagic,
+    /// Неизвестные флаги (данные записаны более новой версией)
+    UnknownFlags(u8),
+    /// Недопустимые параметры или не те, с которыми собрана основная библиотека
+    UnsupportedParams { window_bits: u8, lookahead_bits: u8 },
+    /// Данные сжаты со словарем `id`, а словарь не передан
+    DictionaryRequired { id: u32 },
+    /// Передан другой словарь
+    DictionaryMismatch { expected: u32, found: u32 },
+    /// Словарь передан, а данные сжаты без него
+    UnexpectedDictionary,
+    /// Неизвестный фильтр или их больше [`MAX_FILTERS`]
+    BadFilters,
+    /// Распакованные данные длиннее `limit` байт, см. [`decompress_with_limit`]
+    OutputLimit { limit: usize },
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Truncated => write!(f, "truncated header"),
+            Error::BadMagic => write!(f, "not a heatshrink container"),
+            Error::UnknownFlags(flags) => write!(f, "unknown header flags {:#04x}", flags),
+            Error::UnsupportedParams {
+                window_bits,
+                lookahead_bits,
+            } => write!(
+                f,
+                "unsupported parameters: -w {} -l {}",
+                window_bits, lookahead_bits
+            ),
+            Error::DictionaryRequired { id } => {
+                write!(f, "dictionary {:08x} required", id)
+            }
+            Error::DictionaryMismatch { expected, found } => write!(
+                f,
+                "wrong dictionary: expected {:08x}, found {:08x}",
+                expected, found
+            ),
+            Error::UnexpectedDictionary => write!(f, "data was compressed without dictionary"),
+            Error::BadFilters => write!(f, "unknown or too many filters"),
+            Error::OutputLimit { limit } => {
+                write!(f, "decompressed data exceeds {} bytes", limit)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Header {
+    pub window_bits: u8,
+    pub lookahead_bits: u8,
+    pub dictionary_id: Option<u32>,
+    pub stored: bool,
+    pub filters: FilterChain,
+}
+
+impl Header {
+    /// Параметры основной (no_std) библиотеки, без словаря
+    pub const fn new() -> Self {
+        Self {
+            window_bits: STATIC_WINDOW_BITS,
+            lookahead_bits: STATIC_LOOKAHEAD_BITS,
+            dictionary_id: None,
+            stored: false,
+            filters: FilterChain::empty(),
+        }
+    }
+
+    /// Размер заголовка в байтах
+    pub fn encoded_len(&self) -> usize {
+        let filters = if self.filters.is_empty() {
+            0
+        } else {
+            1 + self.filters.len()
+        };
+        4 + if self.dictionary_id.is_some() { 4 } else { 0 } + filters
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&MAGIC);
+        out.push(self.window_bits << 4 | self.lookahead_bits);
+        let mut flags = 0;
+        if self.dictionary_id.is_some() {
+            flags |= FLAG_DICTIONARY;
+        }
+        if self.stored {
+            flags |= FLAG_STORED;
+        }
+        if !self.filters.is_empty() {
+            flags |= FLAG_FILTERS;
+        }
+        out.push(flags);
+        if let Some(id) = self.dictionary_id {
+            out.extend_from_slice(&id.to_le_bytes());
+        }
+        if !self.filters.is_empty() {
+            out.push(self.filters.len() as u8);
+            out.extend(self.filters.iter().map(|f| f.to_byte()));
+        }
+    }
+
+    /// Разобрать заголовок, вернуть его и остаток - сжатые данные
+    pub fn parse(src: &[u8]) -> Result<(Self, &[u8]), Error> {
+        if src.len() < 4 {
+            return Err(Error::Truncated);
+        }
+        if src[..2] != MAGIC {
+            return Err(Error::BadMagic);
+        }
+        let (window_bits, lookahead_bits) = (src[2] >> 4, src[2] & 0x0f);
+        if !valid_params(window_bits, lookahead_bits) {
+            return Err(Error::UnsupportedParams {
+                window_bits,
+                lookahead_bits,
+            });
+        }
+        let flags = src[3];
+        if flags & !(FLAG_DICTIONARY | FLAG_STORED | FLAG_FILTERS) != 0 {
+            return Err(Error::UnknownFlags(flags));
+        }
+
+        let mut rest = &src[4..];
+        let dictionary_id = if flags & FLAG_DICTIONARY != 0 {
+            let id = rest.get(..4).ok_or(Error::Truncated)?;
+            rest = &rest[4..];
+            Some(u32::from_le_bytes([id[0], id[1], id[2], id[3]]))
+        } else {
+            None
+        };
+        let filters = if flags & FLAG_FILTERS != 0 {
+            let count = *rest.first().ok_or(Error::Truncated)? as usize;
+            let bytes = rest.get(1..1 + count).ok_or(Error::Truncated)?;
+            rest = &rest[1 + count..];
+            if count > MAX_FILTERS {
+                return Err(Error::BadFilters);
+            }
+            let mut filters = [Filter::new(FilterKind::Delta, 1).unwrap(); MAX_FILTERS];
+            for (f, b) in filters.iter_mut().zip(bytes) {
+                *f = Filter::from_byte(*b).ok_or(Error::BadFilters)?;
+            }
+            FilterChain::new(&filters[..count]).ok_or(Error::BadFilters)?
+        } else {
+            FilterChain::default()
+        };
+
+        Ok((
+            Self {
+                window_bits,
+                lookahead_bits,
+                dictionary_id,
+                stored: flags & FLAG_STORED != 0,
+                filters,
+            },
+            rest,
+        ))
+    }
+
+    /// Тот ли словарь передан для распаковки; несжатым данным подходит любой
+    pub fn check_dictionary(&self, dict: Option<&[u8]>) -> Result<(), Error> {
+        if self.stored {
+            return Ok(());
+        }
+        match (self.dictionary_id, dict) {
+            (None, None) => Ok(()),
+            (None, Some(_)) => Err(Error::UnexpectedDictionary),
+            (Some(id), None) => Err(Error::DictionaryRequired { id }),
+            (Some(expected), Some(dict)) => match dictionary::id(dict) {
+                found if found == expected => Ok(()),
+                found => Err(Error::DictionaryMismatch { expected, found }),
+            },
+        }
+    }
+}
+
+impl Default for Header {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Сжать `src` основной библиотекой, со словарем или без.
+/// Если сжатое не меньше исходного - записать как есть с [`FLAG_STORED`]
+pub fn compress(src: &[u8], dict: Option<&[u8]>) -> Vec<u8> {
+    compress_filtered(src, dict, &FilterChain::default())
+}
+
+/// То же, что [`compress`], перед сжатием - фильтры `filters`, они записываются в заголовок
+pub fn compress_filtered(src: &[u8], dict: Option<&[u8]>, filters: &FilterChain) -> Vec<u8> {
+    let header = Header {
+        dictionary_id: dict.map(dictionary::id),
+        filters: *filters,
+        ..Header::new()
+    };
+    let mut res = Vec::new();
+    header.write(&mut res);
+    let filtered = filters.apply(src);
+    match dict {
+        Some(dict) => res.extend(HeatshrinkEncoder::with_dictionary(
+            filtered.iter().cloned(),
+            dict,
+            Level::Default,
+        )),
+        None => res.extend(HeatshrinkEncoder::source(filtered.iter().cloned())),
+    }
+
+    if res.len() - header.encoded_len() >= src.len() {
+        let header = Header {
+            stored: true,
+            ..Header::new()
+        };
+        res.clear();
+        header.write(&mut res);
+        res.extend_from_slice(src);
+    }
+    res
+}
+
+/// Распаковать то, что записано [`compress`] / [`compress_filtered`];
+/// словарь должен совпасть с указанным в заголовке
+pub fn decompress(src: &[u8], dict: Option<&[u8]>) -> Result<Vec<u8>, Error> {
+    decompress_with_limit(src, dict, usize::MAX)
+}
+
+/// То же, что [`decompress`], но не больше `max_output` байт: дальше - [`Error::OutputLimit`].
+/// Для недоверенных данных (защита от "zip-бомб")
+pub fn decompress_with_limit(
+    src: &[u8],
+    dict: Option<&[u8]>,
+    max_output: usize,
+) -> Result<Vec<u8>, Error> {
+    let (header, body) = Header::parse(src)?;
+    if (header.window_bits, header.lookahead_bits) != (STATIC_WINDOW_BITS, STATIC_LOOKAHEAD_BITS) {
+        return Err(Error::UnsupportedParams {
+            window_bits: header.window_bits,
+            lookahead_bits: header.lookahead_bits,
+        });
+    }
+    header.check_dictionary(dict)?;
+
+    let limit = Error::OutputLimit { limit: max_output };
+    let mut res = if header.stored {
+        if body.len() > max_output {
+            return Err(limit);
+        }
+        body.to_vec()
+    } else {
+        let decoder = match dict {
+            Some(dict) => HeatshrinkDecoder::with_dictionary(body.iter().cloned(), dict),
+            None => HeatshrinkDecoder::source(body.iter().cloned()),
+        };
+        let mut decoder = decoder.with_limit(max_output);
+        let res = decoder.by_ref().collect();
+        if decoder.error().is_some() {
+            return Err(limit);
+        }
+        res
+    };
+    header.filters.inverse(&mut res);
+    Ok(res)
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::container::{
+        compress, compress_filtered, decompress, decompress_with_limit, Error, Header, FLAG_FILTERS,
+    };
+    use crate::dictionary;
+    use crate::filter::{Filter, FilterChain, FilterKind};
+
+    #[test]
+    fn header() {
+        let header = Header {
+            window_bits: 11,
+            lookahead_bits: 4,
+            dictionary_id: Some(0x1234_5678),
+            stored: false,
+            filters: FilterChain::default(),
+        };
+        let mut buf = Vec::new();
+        header.write(&mut buf);
+        assert_eq!(buf, [b'H', b'S', 0xb4, 0x01, 0x78, 0x56, 0x34, 0x12]);
+        assert_eq!(header.encoded_len(), buf.len());
+        buf.push(0xaa);
+        assert_eq!(Header::parse(&buf), Ok((header, &[0xaa][..])));
+
+        assert_eq!(Header::parse(&buf[..6]), Err(Error::Truncated));
+        assert_eq!(Header::parse(b"HZ\x84\x00"), Err(Error::BadMagic));
+        assert_eq!(Header::parse(b"HS\x84\x08"), Err(Error::UnknownFlags(8)));
+        assert_eq!(
+            Header::parse(b"HS\x88\x00"),
+            Err(Error::UnsupportedParams {
+                window_bits: 8,
+                lookahead_bits: 8
+            })
+        );
+    }
+
+    #[test]
+    fn dictionary_check() {
+        let dict = b"\"status\":\"ok\",\"status\":\"error\"";
+        let src = br#"{"status":"ok","code":0}"#;
+
+        let packed = compress(src, Some(dict));
+        assert_eq!(decompress(&packed, Some(dict)), Ok(src.to_vec()));
+        assert_eq!(
+            decompress(&packed, None),
+            Err(Error::DictionaryRequired {
+                id: dictionary::id(dict)
+            })
+        );
+        assert_eq!(
+            decompress(&packed, Some(b"other")),
+            Err(Error::DictionaryMismatch {
+                expected: dictionary::id(dict),
+                found: dictionary::id(b"other")
+            })
+        );
+
+        // без словаря такой короткий не сжимается, повторы - сжимаются
+        let src = src.repeat(3);
+        let packed = compress(&src, None);
+        assert_eq!(decompress(&packed, None), Ok(src));
+        assert_eq!(
+            decompress(&packed, Some(dict)),
+            Err(Error::UnexpectedDictionary)
+        );
+    }
+
+    #[test]
+    fn stored() {
+        // не сжимается: каждый байт новый
+        let src = (0..=255u8).collect::<Vec<_>>();
+        let packed = compress(&src, None);
+        assert_eq!(packed.len(), 4 + src.len());
+        let (header, body) = Header::parse(&packed).unwrap();
+        assert!(header.stored);
+        assert_eq!(body, &src[..]);
+        assert_eq!(decompress(&packed, None), Ok(src.clone()));
+        // словарь несжатым данным не нужен
+        let packed = compress(&src, Some(b"dictionary"));
+        assert_eq!(Header::parse(&packed).unwrap().0.dictionary_id, None);
+        assert_eq!(decompress(&packed, Some(b"other")), Ok(src));
+
+        let packed = compress(b"", None);
+        assert_eq!(decompress(&packed, None), Ok(Vec::new()));
+        let packed = compress(&[0u8; 100], None);
+        assert!(!Header::parse(&packed).unwrap().0.stored);
+    }
+
+    #[test]
+    fn output_limit() {
+        let src = [0u8; 10_000];
+        let packed = compress(&src, None);
+        assert_eq!(
+            decompress_with_limit(&packed, None, 10_000),
+            Ok(src.to_vec())
+        );
+        assert_eq!(
+            decompress_with_limit(&packed, None, 9_999),
+            Err(Error::OutputLimit { limit: 9_999 })
+        );
+
+        let src = (0..=255u8).collect::<Vec<_>>();
+        let packed = compress(&src, None);
+        assert!(Header::parse(&packed).unwrap().0.stored);
+        assert_eq!(
+            decompress_with_limit(&packed, None, 255),
+            Err(Error::OutputLimit { limit: 255 })
+        );
+    }
+
+    #[test]
+    fn filters() {
+        let src = (0..1000i32)
+            .flat_map(|n| (100_000 + n * 40 + n % 7).to_le_bytes())
+            .collect::<Vec<_>>();
+        let chain = FilterChain::new(&[
+            Filter::new(FilterKind::Delta, 4).unwrap(),
+            Filter::new(FilterKind::Shuffle, 4).unwrap(),
+        ])
+        .unwrap();
+        let packed = compress_filtered(&src, None, &chain);
+        assert!(packed.len() < compress(&src, None).len() / 2);
+
+        let (header, _) = Header::parse(&packed).unwrap();
+        assert_eq!(header.filters, chain);
+        assert_eq!(header.encoded_len(), 4 + 1 + 2);
+        assert_eq!(&packed[3..7], [FLAG_FILTERS, 2, 0x14, 0x34]);
+        assert_eq!(decompress(&packed, None), Ok(src));
+
+        assert_eq!(Header::parse(b"HS\x84\x04\x01\x55"), Err(Error::BadFilters));
+        assert_eq!(Header::parse(b"HS\x84\x04\x02\x14"), Err(Error::Truncated));
+    }
+}
@@ -6,9 +6,132 @@
 
 include!("bindings/bindings-decoder.rs");
 
+use crate::framing::Segments;
+use crate::inspect::{StreamParser, TokenKind};
+use crate::{STATIC_LOOKAHEAD_BITS, STATIC_WINDOW_BITS};
+
 impl Default for _heatshrink_decoder {
     fn default() -> _heatshrink_decoder {
-        unsafe { core::mem::uninitialized() }
+        unsafe { core::mem::zeroed() }
+    }
+}
+
+// начальное состояние heatshrink_decoder.c - ждем бит-тег
+pub(crate) const HSDS_TAG_BIT: u8 = 0;
+
+impl _heatshrink_decoder {
+    // словарь в окно, только сразу после reset; сколько байт словаря в окне
+    pub(crate) fn prime(&mut self, dict: &[u8]) -> usize {
+        let window = &mut self.buffers[crate::STATIC_INPUT_BUFFER_SIZE..];
+        crate::dictionary::prime_window(window, &mut self.head_index, dict)
+    }
+
+    // точка sync flush: выбросить дополнение байта, окно сохраняется.
+    // false - еще есть необработанные входные данные
+    pub(crate) fn sync(&mut self) -> bool {
+        if self.input_size != 0 {
+            return false;
+        }
+        self.state = HSDS_TAG_BIT;
+        self.current_byte = 0;
+        self.bit_index = 0;
+        self.output_count = 0;
+        self.output_index = 0;
+        true
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Error {
+    /// Распакованные данные длиннее `limit` байт
+    OutputLimit { limit: usize },
+    /// Строгий режим: ссылка на `offset` байт назад, а распаковано только `out_pos`
+    BeforeStart { offset: usize, out_pos: usize },
+    /// Строгий режим: после последнего токена не дополнение нулями до байта
+    Padding { trailing_bits: u8 },
+    /// Поток с точками flush ([`crate::framing`]) оборван посреди сегмента или заголовка
+    Truncated,
+    /// Поток с точками flush: заголовок сегмента длиннее 5 байт или больше u32
+    BadSegment,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::OutputLimit { limit } => {
+                write!(f, "decompressed data exceeds {} bytes", limit)
+            }
+            Error::BeforeStart { offset, out_pos } => write!(
+                f,
+                "back-reference {} bytes back at output position {}",
+                offset, out_pos
+            ),
+            Error::Padding { trailing_bits } => write!(
+                f,
+                "invalid padding: {} bits after the last token",
+                trailing_bits
+            ),
+            Error::Truncated => write!(f, "truncated flush segment"),
+            Error::BadSegment => write!(f, "bad flush segment header"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// Проверки строгого режима по сжатому потоку, параллельно с декодером
+pub(crate) struct StrictCheck {
+    parser: StreamParser,
+    out_pos: usize,
+}
+
+impl StrictCheck {
+    pub(crate) const fn new(window_bits: u8, lookahead_bits: u8) -> Self {
+        Self {
+            parser: StreamParser::new(window_bits, lookahead_bits),
+            out_pos: 0,
+        }
+    }
+
+    /// В окне уже `len` байт словаря, ссылки на них допустимы
+    pub(crate) const fn with_history(mut self, len: usize) -> Self {
+        self.out_pos = len;
+        self
+    }
+
+    /// Очередной байт сжатого потока, проверять до того, как он попадет в декодер
+    pub(crate) fn push(&mut self, byte: u8) -> Result<(), Error> {
+        let mut res = Ok(());
+        let out_pos = &mut self.out_pos;
+        self.parser.push(byte, |token| match token {
+            TokenKind::Literal(_) => *out_pos += 1,
+            TokenKind::BackRef { offset, length } => {
+                if offset > *out_pos && res.is_ok() {
+                    res = Err(Error::BeforeStart {
+                        offset,
+                        out_pos: *out_pos,
+                    });
+                }
+                *out_pos += length;
+            }
+        });
+        res
+    }
+
+    /// Конец потока: остаток меньше байта и нулевой
+    pub(crate) fn finish(&self) -> Result<(), Error> {
+        match self.parser.trailing() {
+            (bits, 0) if bits < 8 => Ok(()),
+            (trailing_bits, _) => Err(Error::Padding { trailing_bits }),
+        }
+    }
+
+    /// Точка sync flush: дополнение проверяется как в конце потока
+    pub(crate) fn sync(&mut self) -> Result<(), Error> {
+        self.finish()?;
+        self.parser.sync();
+        Ok(())
     }
 }
 
@@ -18,6 +141,15 @@ where
 {
     ctx: _heatshrink_decoder,
     finished: bool,
+    // выдано байт и сколько можно, см. with_limit()
+    produced: usize,
+    limit: usize,
+    error: Option<Error>,
+    strict: Option<StrictCheck>,
+    // байт словаря в окне
+    history: usize,
+    // поток с точками flush, см. framed()
+    segments: Option<Segments>,
     src: T,
 }
 
@@ -29,6 +161,12 @@ where
         let mut res = Self {
             ctx: _heatshrink_decoder::default(),
             finished: false,
+            produced: 0,
+            limit: usize::MAX,
+            error: None,
+            strict: None,
+            history: 0,
+            segments: None,
             src,
         };
         unsafe {
@@ -36,6 +174,46 @@ where
         }
         res
     }
+
+    /// Окно заранее заполнено словарем, тем же, что у кодера ([`crate::dictionary`])
+    pub fn with_dictionary(src: T, dict: &[u8]) -> Self {
+        let mut res = Self::source(src);
+        res.history = res.ctx.prime(dict);
+        res
+    }
+
+    /// Не больше `max_output` байт: дальше итератор заканчивается, а [`HeatshrinkDecoder::error`]
+    /// возвращает [`Error::OutputLimit`]. Для недоверенных данных (защита от "zip-бомб")
+    pub fn with_limit(mut self, max_output: usize) -> Self {
+        self.limit = max_output;
+        self
+    }
+
+    /// Строгий режим: ссылки раньше начала данных и ненулевое дополнение последнего байта -
+    /// ошибка ([`Error::BeforeStart`], [`Error::Padding`]), итератор заканчивается.
+    ///
+    /// Кодер heatshrink сам ссылается раньше начала, если данные начинаются с нулей
+    /// (окно заполнено нулями) - такие потоки строгий режим не пропустит.
+    pub fn strict(mut self) -> Self {
+        self.strict = Some(
+            StrictCheck::new(STATIC_WINDOW_BITS, STATIC_LOOKAHEAD_BITS).with_history(self.history),
+        );
+        self
+    }
+
+    /// Поток с точками flush ([`crate::framing`]), например от
+    /// [`crate::encoder_to_vec::HeatshrinkEncoderToVec::framed`]: заголовки сегментов
+    /// разбираются, дополнение в конце каждого отбрасывается. Поток, оборванный посреди
+    /// сегмента, - [`Error::Truncated`]
+    pub fn framed(mut self) -> Self {
+        self.segments = Some(Segments::default());
+        self
+    }
+
+    /// Почему итератор закончился раньше конца потока
+    pub fn error(&self) -> Option<Error> {
+        self.error
+    }
 }
 
 impl<T> Iterator for HeatshrinkDecoder<T>
@@ -45,6 +223,24 @@ where
     type Item = u8;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+        let res = self.next_byte()?;
+        if self.produced == self.limit {
+            self.error = Some(Error::OutputLimit { limit: self.limit });
+            return None;
+        }
+        self.produced += 1;
+        Some(res)
+    }
+}
+
+impl<T> HeatshrinkDecoder<T>
+where
+    T: Iterator<Item = u8>,
+{
+    fn next_byte(&mut self) -> Option<u8> {
         loop {
             let mut outbuf: u8 = 0;
             let mut actualy_read: usize = 0;
@@ -76,7 +272,18 @@ where
             }
 
             // need more data
-            if let Some(mut b) = self.src.next() {
+            let next = match self.next_input() {
+                Ok(next) => next,
+                Err(e) => {
+                    self.error = Some(e);
+                    return None;
+                }
+            };
+            if let Some(mut b) = next {
+                if let Some(Err(e)) = self.strict.as_mut().map(|s| s.push(b)) {
+                    self.error = Some(e);
+                    return None;
+                }
                 let mut actualy_read: usize = 0;
                 let mut res =
                     unsafe { heatshrink_decoder_sink(&mut self.ctx, &mut b, 1, &mut actualy_read) };
@@ -85,6 +292,14 @@ where
                     _ => panic!(),
                 }
             } else {
+                if matches!(&self.segments, Some(s) if !s.at_boundary()) {
+                    self.error = Some(Error::Truncated);
+                    return None;
+                }
+                if let Some(Err(e)) = self.strict.as_ref().map(|s| s.finish()) {
+                    self.error = Some(e);
+                    return None;
+                }
                 // try finalise
                 self.finished = true;
                 let res = unsafe { heatshrink_decoder_finish(&mut self.ctx) };
@@ -96,12 +311,92 @@ where
             }
         }
     }
+
+    // следующий байт сжатых данных, заголовки сегментов пропускаются
+    fn next_input(&mut self) -> Result<Option<u8>, Error> {
+        let segments = match &mut self.segments {
+            Some(segments) => segments,
+            None => return Ok(self.src.next()),
+        };
+        while segments.remaining() == 0 {
+            let b = match self.src.next() {
+                Some(b) => b,
+                None => return Ok(None),
+            };
+            if segments.header_byte(b)? {
+                // все до точки flush уже распаковано, остаток байта - дополнение
+                let synced = self.ctx.sync();
+                debug_assert!(synced);
+                if let Some(strict) = &mut self.strict {
+                    strict.sync()?;
+                }
+            }
+        }
+        let b = self.src.next();
+        if b.is_some() {
+            segments.consume(1);
+        }
+        Ok(b)
+    }
 }
 
-#[cfg(all(unix))]
+/// Распаковать `src` в `dest`, вернуть размер распакованных данных.
+/// Не влезло - [`Error::OutputLimit`] с `limit` = `dest.len()`
+pub fn decode_into(mut src: &[u8], dest: &mut [u8]) -> Result<usize, Error> {
+    let mut ctx = _heatshrink_decoder::default();
+    let mut wp = 0;
+    unsafe {
+        heatshrink_decoder_reset(&mut ctx);
+    }
+
+    let mut drain = |ctx: &mut _heatshrink_decoder, wp: &mut usize| -> Result<(), Error> {
+        let out_buf = &mut dest[*wp..];
+        let mut out_writen = 0;
+        let res = unsafe {
+            heatshrink_decoder_poll(ctx, out_buf.as_mut_ptr(), out_buf.len(), &mut out_writen)
+        };
+        *wp += out_writen;
+        match res {
+            HSD_poll_res_HSDR_POLL_EMPTY => Ok(()),
+            // MORE бывает и при буфере, заполненном ровно: проверяем, есть ли еще байт
+            HSD_poll_res_HSDR_POLL_MORE => {
+                let mut probe = 0u8;
+                unsafe { heatshrink_decoder_poll(ctx, &mut probe, 1, &mut out_writen) };
+                if out_writen == 0 {
+                    Ok(())
+                } else {
+                    Err(Error::OutputLimit { limit: dest.len() })
+                }
+            }
+            _ => panic!(),
+        }
+    };
+
+    while !src.is_empty() {
+        let mut writen = 0;
+        // в биндингах *mut, но вход декодер не меняет
+        let ptr = src.as_ptr() as *mut u8;
+        match unsafe { heatshrink_decoder_sink(&mut ctx, ptr, src.len(), &mut writen) } {
+            HSD_sink_res_HSDR_SINK_OK | HSD_sink_res_HSDR_SINK_FULL => {}
+            _ => panic!(),
+        }
+        src = &src[writen..];
+        drain(&mut ctx, &mut wp)?;
+    }
+    loop {
+        match unsafe { heatshrink_decoder_finish(&mut ctx) } {
+            HSD_finish_res_HSDR_FINISH_DONE => return Ok(wp),
+            HSD_finish_res_HSDR_FINISH_MORE => drain(&mut ctx, &mut wp)?,
+            _ => panic!(),
+        }
+    }
+}
+
+#[cfg(unix)]
 #[cfg(test)]
 mod tests {
-    use crate::decoder::HeatshrinkDecoder;
+    use crate::decoder::{decode_into, Error, HeatshrinkDecoder};
+    use crate::encoder::HeatshrinkEncoder;
 
     use std::vec::Vec;
 
@@ -115,4 +410,121 @@ mod tests {
         }
         assert_eq!(None, dec.next());
     }
+
+    #[test]
+    fn decode_limit() {
+        let src = b"limit limit limit limit limit".repeat(20);
+        let packed = HeatshrinkEncoder::source(src.iter().cloned()).collect::<Vec<_>>();
+
+        let mut dec = HeatshrinkDecoder::source(packed.iter().cloned()).with_limit(src.len());
+        assert_eq!(dec.by_ref().collect::<Vec<_>>(), src);
+        assert_eq!(dec.error(), None);
+
+        let mut dec = HeatshrinkDecoder::source(packed.iter().cloned()).with_limit(100);
+        assert_eq!(dec.by_ref().count(), 100);
+        assert_eq!(dec.error(), Some(Error::OutputLimit { limit: 100 }));
+        assert_eq!(dec.next(), None);
+
+        // 2 байта -> 8 нулей
+        let mut dec = HeatshrinkDecoder::source([0u8, 0x38].iter().cloned()).with_limit(0);
+        assert_eq!(dec.next(), None);
+        assert_eq!(dec.error(), Some(Error::OutputLimit { limit: 0 }));
+    }
+
+    #[test]
+    fn decode_into_slice() {
+        let src = b"slice slice slice slice slice".repeat(20);
+        let packed = HeatshrinkEncoder::source(src.iter().cloned()).collect::<Vec<_>>();
+
+        let mut dest = vec![0u8; src.len()];
+        assert_eq!(decode_into(&packed, &mut dest), Ok(src.len()));
+        assert_eq!(dest, src);
+
+        let mut dest = vec![0u8; src.len() + 10];
+        assert_eq!(decode_into(&packed, &mut dest), Ok(src.len()));
+
+        let mut dest = vec![0u8; src.len() - 1];
+        assert_eq!(
+            decode_into(&packed, &mut dest),
+            Err(Error::OutputLimit {
+                limit: src.len() - 1
+            })
+        );
+        assert_eq!(decode_into(&[], &mut []), Ok(0));
+    }
+
+    #[test]
+    fn decode_strict() {
+        let src = b"strict strict strict strict".repeat(20);
+        let packed = HeatshrinkEncoder::source(src.iter().cloned()).collect::<Vec<_>>();
+
+        let mut dec = HeatshrinkDecoder::source(packed.iter().cloned()).strict();
+        assert_eq!(dec.by_ref().collect::<Vec<_>>(), src);
+        assert_eq!(dec.error(), None);
+
+        // нули в начале кодер сжимает ссылкой раньше начала
+        let mut dec = HeatshrinkDecoder::source([0u8, 0x38].iter().cloned()).strict();
+        assert_eq!(dec.next(), None);
+        assert_eq!(
+            dec.error(),
+            Some(Error::BeforeStart {
+                offset: 1,
+                out_pos: 0
+            })
+        );
+
+        // мусор в дополнении последнего байта: литерал 'a' - 9 бит + 7 бит дополнения
+        let packed = HeatshrinkEncoder::source(b"a".iter().cloned()).collect::<Vec<_>>();
+        assert_eq!(packed, [0xb0, 0x80]);
+        let mut dec = HeatshrinkDecoder::source([0xb0, 0x81].iter().cloned()).strict();
+        assert_eq!(dec.by_ref().collect::<Vec<_>>(), b"a");
+        assert_eq!(dec.error(), Some(Error::Padding { trailing_bits: 7 }));
+        // без строгого режима - молча
+        let mut dec = HeatshrinkDecoder::source([0xb0, 0x81].iter().cloned());
+        assert_eq!(dec.by_ref().collect::<Vec<_>>(), b"a");
+        assert_eq!(dec.error(), None);
+
+        // лишний байт в конце
+        let mut dec = HeatshrinkDecoder::source([0xb0, 0x80, 0xff].iter().cloned()).strict();
+        dec.by_ref().for_each(drop);
+        assert!(dec.error().is_some());
+    }
+
+    #[test]
+    fn decode_framed() {
+        use crate::encoder_to_vec::{HeatshrinkEncoderToVec, Result};
+
+        let messages = [&b"flush flush flush"[..], b"flush point", b"", b"a"];
+        let mut encoder = HeatshrinkEncoderToVec::dest(Vec::with_capacity(1024), 0).framed();
+        for message in messages {
+            assert!(matches!(encoder.push_bytes(message), Result::Ok));
+            assert!(matches!(encoder.flush(), Result::Ok));
+        }
+        assert!(matches!(encoder.finish(), Result::Done));
+        let packed = encoder.result();
+        let src = messages.concat();
+
+        let mut dec = HeatshrinkDecoder::source(packed.iter().cloned())
+            .framed()
+            .strict();
+        assert_eq!(dec.by_ref().collect::<Vec<_>>(), src);
+        assert_eq!(dec.error(), None);
+        // обычный декодер принял бы заголовки за данные
+        let plain = HeatshrinkDecoder::source(packed.iter().cloned()).collect::<Vec<_>>();
+        assert_ne!(plain, src);
+
+        // пустые сегменты пропускаются
+        let mut dec = HeatshrinkDecoder::source([0, 0].iter().cloned()).framed();
+        assert_eq!(dec.next(), None);
+        assert_eq!(dec.error(), None);
+
+        let mut dec =
+            HeatshrinkDecoder::source(packed[..packed.len() - 1].iter().cloned()).framed();
+        dec.by_ref().for_each(drop);
+        assert_eq!(dec.error(), Some(Error::Truncated));
+
+        let mut dec = HeatshrinkDecoder::source([0x80; 5].iter().cloned()).framed();
+        assert_eq!(dec.next(), None);
+        assert_eq!(dec.error(), Some(Error::BadSegment));
+    }
 }
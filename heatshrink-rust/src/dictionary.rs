@@ -0,0 +1,118 @@
+//! Предустановленный словарь: окна кодера и декодера заранее заполняются типичными данными,
+//! так что уже первые байты короткого сообщения (JSON, CBOR) сжимаются ссылками назад.
+//!
+//! В окно помещаются последние `1 << window_bits` байт словаря, самое частое лучше
+//! класть в конец. У обеих сторон словарь должен быть одинаковым, в [`crate::container`]
+//! записывается его [`id`].
+
+use crate::validate::crc32;
+
+/// Идентификатор словаря для заголовка контейнера: CRC-32 всего словаря
+pub fn id(dict: &[u8]) -> u32 {
+    crc32(dict)
+}
+
+// то, что реально попадет в окно
+pub(crate) fn tail(dict: &[u8], window_bits: u8) -> &[u8] {
+    &dict[dict.len().saturating_sub(1 << window_bits)..]
+}
+
+// Входной буфер кодера: [окно (1 << window_bits)][новые данные], словарь - в конец окна.
+// Только сразу после reset
+pub(crate) fn prime_backlog(buffer: &mut [u8], window_bits: u8, dict: &[u8]) {
+    let dict = tail(dict, window_bits);
+    let window_size = 1 << window_bits;
+    buffer[window_size - dict.len()..window_size].copy_from_slice(dict);
+}
+
+// Окно декодера - кольцевой буфер, словарь пишется как уже распакованные данные.
+// Возвращает сколько байт словаря в окне. Только сразу после reset
+pub(crate) fn prime_window(window: &mut [u8], head_index: &mut u16, dict: &[u8]) -> usize {
+    let window_bits = window.len().trailing_zeros() as u8;
+    let dict = tail(dict, window_bits);
+    let mask = window.len() - 1;
+    for b in dict {
+        window[*head_index as usize & mask] = *b;
+        *head_index = head_index.wrapping_add(1);
+    }
+    dict.len()
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::decoder::{Error, HeatshrinkDecoder};
+    use crate::encoder::HeatshrinkEncoder;
+    use crate::Level;
+
+    const DICT: &[u8] = br#"{"id":,"temperature":,"humidity":,"status":"ok"}"#;
+
+    #[test]
+    fn with_dictionary() {
+        let src = br#"{"id":17,"temperature":21.5,"humidity":40,"status":"ok"}"#;
+
+        let plain = HeatshrinkEncoder::source(src.iter().cloned()).collect::<Vec<_>>();
+        let packed = HeatshrinkEncoder::with_dictionary(src.iter().cloned(), DICT, Level::Default)
+            .collect::<Vec<_>>();
+        assert!(
+            packed.len() < plain.len() * 2 / 3,
+            "{} {}",
+            packed.len(),
+            plain.len()
+        );
+
+        let decoded =
+            HeatshrinkDecoder::with_dictionary(packed.iter().cloned(), DICT).collect::<Vec<_>>();
+        assert_eq!(decoded, src);
+
+        // без словаря - мусор
+        let decoded = HeatshrinkDecoder::source(packed.iter().cloned()).collect::<Vec<_>>();
+        assert_ne!(decoded, src);
+
+        // ссылки в словарь строгий режим пропускает
+        let mut decoder = HeatshrinkDecoder::with_dictionary(packed.iter().cloned(), DICT).strict();
+        assert_eq!(decoder.by_ref().collect::<Vec<_>>(), src);
+        assert_eq!(decoder.error(), None);
+        let mut decoder = HeatshrinkDecoder::source(packed.iter().cloned()).strict();
+        decoder.by_ref().count();
+        assert!(matches!(decoder.error(), Some(Error::BeforeStart { .. })));
+
+        // уровни со словарем: Fast - тот же поток, Best - не длиннее и тоже со ссылками в словарь
+        let encode = |level| {
+            HeatshrinkEncoder::with_dictionary(src.iter().cloned(), DICT, level).collect::<Vec<_>>()
+        };
+        assert_eq!(encode(Level::Fast), packed);
+        let best = encode(Level::Best);
+        assert!(
+            best.len() <= packed.len(),
+            "{} {}",
+            best.len(),
+            packed.len()
+        );
+        let decoded =
+            HeatshrinkDecoder::with_dictionary(best.iter().cloned(), DICT).collect::<Vec<_>>();
+        assert_eq!(decoded, src);
+    }
+
+    #[test]
+    fn long_dictionary() {
+        // в окно попадают последние 256 байт
+        let mut dict = (0..1000u32)
+            .map(|n| (n * 7 % 251) as u8)
+            .collect::<Vec<_>>();
+        dict.extend_from_slice(b"the quick brown fox jumps over the lazy dog");
+        let src = b"the lazy dog and the quick brown fox".repeat(3);
+
+        let packed = HeatshrinkEncoder::with_dictionary(src.iter().cloned(), &dict, Level::Default)
+            .collect::<Vec<_>>();
+        let decoded =
+            HeatshrinkDecoder::with_dictionary(packed.iter().cloned(), &dict).collect::<Vec<_>>();
+        assert_eq!(decoded, src);
+        let decoded =
+            HeatshrinkDecoder::with_dictionary(packed.iter().cloned(), &dict[dict.len() - 256..])
+                .collect::<Vec<_>>();
+        assert_eq!(decoded, src);
+    }
+}
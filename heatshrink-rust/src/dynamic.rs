@@ -0,0 +1,467 @@
+#![allow(non_upper_case_globals)]
+#![allow(non_camel_case_types)]
+
+//! heatshrink с размером окна и lookahead, задаваемыми в рантайме.
+//!
+//! Основной (no_std) вариант собран с фиксированными
+//! `HEATSHRINK_STATIC_WINDOW_BITS` / `HEATSHRINK_STATIC_LOOKAHEAD_BITS`,
+//! здесь - вторая копия библиотеки с `HEATSHRINK_DYNAMIC_ALLOC=1`, память через malloc().
+
+use core::ptr::NonNull;
+
+include!("bindings/bindings-dynamic.rs");
+
+use crate::decoder::{
+    HSD_finish_res_HSDR_FINISH_DONE, HSD_poll_res_HSDR_POLL_EMPTY, HSD_poll_res_HSDR_POLL_MORE,
+    HSD_sink_res_HSDR_SINK_FULL, HSD_sink_res_HSDR_SINK_OK, HSDS_TAG_BIT,
+};
+use crate::encoder_common::resume_after_flush;
+use crate::encoder_common::{
+    HSE_finish_res_HSER_FINISH_DONE, HSE_poll_res_HSER_POLL_EMPTY, HSE_poll_res_HSER_POLL_MORE,
+    HSE_sink_res_HSER_SINK_ERROR_MISUSE, HSE_sink_res_HSER_SINK_OK,
+};
+use crate::{dictionary, valid_params, Level};
+
+/// Входной буфер декодера по умолчанию, как у утилиты heatshrink
+pub const DEFAULT_DECODER_INPUT_BUFFER_SIZE: u16 = 256;
+
+// Контекст кодера выбранного уровня: с индексом поиска (отдельный malloc()) или без
+enum EncoderContext {
+    Indexed(NonNull<heatshrink_dyn_encoder>),
+    Compact(NonNull<heatshrink_dyn_compact_encoder>),
+}
+
+// $indexed / $compact - одна и та же функция C для контекста с индексом и без
+macro_rules! call {
+    ($self:expr, $indexed:ident, $compact:ident $(, $arg:expr)*) => {
+        match $self.ctx {
+            EncoderContext::Indexed(ctx) => unsafe { $indexed(ctx.as_ptr() $(, $arg)*) },
+            EncoderContext::Compact(ctx) => unsafe { $compact(ctx.as_ptr() $(, $arg)*) },
+        }
+    };
+}
+
+// $body с $ctx - указателем на контекст любого варианта, поля у них общие
+macro_rules! with_ctx {
+    ($self:expr, $ctx:ident => $body:expr) => {
+        match $self.ctx {
+            EncoderContext::Indexed(ptr) => {
+                let $ctx = ptr.as_ptr();
+                $body
+            }
+            EncoderContext::Compact(ptr) => {
+                let $ctx = ptr.as_ptr();
+                $body
+            }
+        }
+    };
+}
+
+// буфер кодера - `len` байт сразу за структурой, поле buffer
+macro_rules! buffer {
+    ($ptr:expr, $len:expr) => {
+        core::slice::from_raw_parts_mut(core::ptr::addr_of_mut!((*$ptr).buffer) as *mut u8, $len)
+    };
+}
+
+pub struct DynamicEncoder {
+    ctx: EncoderContext,
+}
+
+// контекст принадлежит только этому объекту
+unsafe impl Send for DynamicEncoder {}
+
+impl DynamicEncoder {
+    /// None если параметры недопустимы (см. [`crate::valid_params`]) или malloc() не смог
+    pub fn new(window_bits: u8, lookahead_bits: u8) -> Option<Self> {
+        Self::with_level(window_bits, lookahead_bits, Level::Default)
+    }
+
+    /// Сжатие с уровнем `level`, см. [`Level`]: Fast - без индекса поиска, памяти меньше
+    /// на `2 * (2 << window_bits)` байт; при окне 2^15 поток может немного отличаться
+    /// от Default, но читается тем же декодером. Best здесь - как Default
+    pub fn with_level(window_bits: u8, lookahead_bits: u8, level: Level) -> Option<Self> {
+        if !valid_params(window_bits, lookahead_bits) {
+            return None;
+        }
+        let ctx = match level {
+            Level::Fast => NonNull::new(unsafe {
+                heatshrink_dyn_compact_encoder_alloc(window_bits, lookahead_bits)
+            })
+            .map(EncoderContext::Compact),
+            _ => NonNull::new(unsafe { heatshrink_dyn_encoder_alloc(window_bits, lookahead_bits) })
+                .map(EncoderContext::Indexed),
+        };
+        ctx.map(|ctx| Self { ctx })
+    }
+
+    pub fn window_bits(&self) -> u8 {
+        with_ctx!(self, ctx => unsafe { (*ctx).window_sz2 })
+    }
+
+    pub fn lookahead_bits(&self) -> u8 {
+        with_ctx!(self, ctx => unsafe { (*ctx).lookahead_sz2 })
+    }
+
+    pub fn reset(&mut self) {
+        call!(
+            self,
+            heatshrink_dyn_encoder_reset,
+            heatshrink_dyn_compact_encoder_reset
+        )
+    }
+
+    /// Заполнить окно словарем ([`crate::dictionary`]), только сразу после new() / reset()
+    pub fn set_dictionary(&mut self, dict: &[u8]) {
+        let window_bits = self.window_bits();
+        let buffer = with_ctx!(self, ptr => unsafe { buffer!(ptr, 2 << window_bits) });
+        dictionary::prime_backlog(buffer, window_bits, dict);
+    }
+
+    /// Сколько байт из `data` принято. 0 - входной буфер полон (нужен [`DynamicEncoder::poll`])
+    /// или уже вызван [`DynamicEncoder::finish`]
+    pub fn sink(&mut self, data: &[u8]) -> usize {
+        let mut writen = 0;
+        match call!(
+            self,
+            heatshrink_dyn_encoder_sink,
+            heatshrink_dyn_compact_encoder_sink,
+            data.as_ptr(),
+            data.len(),
+            &mut writen
+        ) {
+            HSE_sink_res_HSER_SINK_OK => writen,
+            HSE_sink_res_HSER_SINK_ERROR_MISUSE => 0,
+            _ => panic!(),
+        }
+    }
+
+    /// Выдать сжатые данные в `out`: (записано байт, есть ли еще данные)
+    pub fn poll(&mut self, out: &mut [u8]) -> (usize, bool) {
+        let mut out_writen = 0;
+        match call!(
+            self,
+            heatshrink_dyn_encoder_poll,
+            heatshrink_dyn_compact_encoder_poll,
+            out.as_mut_ptr(),
+            out.len(),
+            &mut out_writen
+        ) {
+            HSE_poll_res_HSER_POLL_EMPTY => (out_writen, false),
+            HSE_poll_res_HSER_POLL_MORE => (out_writen, true),
+            _ => panic!(),
+        }
+    }
+
+    /// Признак конца данных. true - все выдано, иначе нужно poll()-ить еще
+    pub fn finish(&mut self) -> bool {
+        call!(
+            self,
+            heatshrink_dyn_encoder_finish,
+            heatshrink_dyn_compact_encoder_finish
+        ) == HSE_finish_res_HSER_FINISH_DONE
+    }
+
+    /// Продолжить поток после того, как [`DynamicEncoder::finish`] вернул true (sync flush):
+    /// все принятое выдано и выровнено до байта, а окно сохраняется и следующие данные
+    /// могут ссылаться на предыдущие. Декодер в этой точке должен вызвать [`DynamicDecoder::sync`].
+    /// false - finish() еще не завершен
+    pub fn resume(&mut self) -> bool {
+        let input_buf_size = 1 << self.window_bits();
+        with_ctx!(self, ptr => {
+            let buffer = unsafe { buffer!(ptr, 2 * input_buf_size) };
+            let ctx = unsafe { &mut *ptr };
+            resume_after_flush!(ctx, buffer, input_buf_size)
+        })
+    }
+}
+
+impl Drop for DynamicEncoder {
+    fn drop(&mut self) {
+        call!(
+            self,
+            heatshrink_dyn_encoder_free,
+            heatshrink_dyn_compact_encoder_free
+        )
+    }
+}
+
+pub struct DynamicDecoder {
+    ctx: NonNull<heatshrink_dyn_decoder>,
+}
+
+unsafe impl Send for DynamicDecoder {}
+
+impl DynamicDecoder {
+    pub fn new(window_bits: u8, lookahead_bits: u8) -> Option<Self> {
+        Self::with_input_buffer(
+            DEFAULT_DECODER_INPUT_BUFFER_SIZE,
+            window_bits,
+            lookahead_bits,
+        )
+    }
+
+    pub fn with_input_buffer(
+        input_buffer_size: u16,
+        window_bits: u8,
+        lookahead_bits: u8,
+    ) -> Option<Self> {
+        if !valid_params(window_bits, lookahead_bits) || input_buffer_size == 0 {
+            return None;
+        }
+        NonNull::new(unsafe {
+            heatshrink_dyn_decoder_alloc(input_buffer_size, window_bits, lookahead_bits)
+        })
+        .map(|ctx| Self { ctx })
+    }
+
+    pub fn window_bits(&self) -> u8 {
+        unsafe { self.ctx.as_ref() }.window_sz2
+    }
+
+    pub fn lookahead_bits(&self) -> u8 {
+        unsafe { self.ctx.as_ref() }.lookahead_sz2
+    }
+
+    pub fn reset(&mut self) {
+        unsafe { heatshrink_dyn_decoder_reset(self.ctx.as_ptr()) }
+    }
+
+    /// Заполнить окно тем же словарем, что у кодера, только сразу после new() / reset()
+    pub fn set_dictionary(&mut self, dict: &[u8]) {
+        let ptr = self.ctx.as_ptr();
+        let (input_buffer_size, window_bits) =
+            unsafe { ((*ptr).input_buffer_size as usize, (*ptr).window_sz2) };
+        // буферы сразу за структурой: входной, потом окно
+        let window = unsafe {
+            core::slice::from_raw_parts_mut(
+                (core::ptr::addr_of_mut!((*ptr).buffers) as *mut u8).add(input_buffer_size),
+                1 << window_bits,
+            )
+        };
+        dictionary::prime_window(window, unsafe { &mut (*ptr).head_index }, dict);
+    }
+
+    /// Сколько байт из `data` принято. 0 - входной буфер полон, нужен [`DynamicDecoder::poll`]
+    pub fn sink(&mut self, data: &[u8]) -> usize {
+        let mut writen = 0;
+        match unsafe {
+            heatshrink_dyn_decoder_sink(self.ctx.as_ptr(), data.as_ptr(), data.len(), &mut writen)
+        } {
+            HSD_sink_res_HSDR_SINK_OK => writen,
+            HSD_sink_res_HSDR_SINK_FULL => 0,
+            _ => panic!(),
+        }
+    }
+
+    /// Выдать распакованные данные в `out`: (записано байт, есть ли еще данные)
+    pub fn poll(&mut self, out: &mut [u8]) -> (usize, bool) {
+        let mut out_writen = 0;
+        match unsafe {
+            heatshrink_dyn_decoder_poll(
+                self.ctx.as_ptr(),
+                out.as_mut_ptr(),
+                out.len(),
+                &mut out_writen,
+            )
+        } {
+            HSD_poll_res_HSDR_POLL_EMPTY => (out_writen, false),
+            HSD_poll_res_HSDR_POLL_MORE => (out_writen, true),
+            _ => panic!(),
+        }
+    }
+
+    /// true - входные данные полностью обработаны
+    pub fn finish(&mut self) -> bool {
+        unsafe {
+            heatshrink_dyn_decoder_finish(self.ctx.as_ptr()) == HSD_finish_res_HSDR_FINISH_DONE
+        }
+    }
+
+    /// Точка sync flush ([`DynamicEncoder::resume`]): отбросить дополнение последнего байта
+    /// и ждать следующий токен, окно сохраняется.
+    /// false - еще есть необработанные входные данные, нужен poll()
+    pub fn sync(&mut self) -> bool {
+        let ctx = unsafe { self.ctx.as_mut() };
+        if ctx.input_size != 0 {
+            return false;
+        }
+        // недочитанные биты дополнения выбрасываются
+        ctx.state = HSDS_TAG_BIT;
+        ctx.current_byte = 0;
+        ctx.bit_index = 0;
+        ctx.output_count = 0;
+        ctx.output_index = 0;
+        true
+    }
+}
+
+impl Drop for DynamicDecoder {
+    fn drop(&mut self) {
+        unsafe { heatshrink_dyn_decoder_free(self.ctx.as_ptr()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::decoder::HeatshrinkDecoder;
+    use crate::dynamic::{DynamicDecoder, DynamicEncoder};
+    use crate::encoder::HeatshrinkEncoder;
+    use crate::encoder_common::{HEATSHRINK_STATIC_LOOKAHEAD_BITS, HEATSHRINK_STATIC_WINDOW_BITS};
+    use crate::{valid_params, Level};
+
+    fn encode(src: &[u8], window_bits: u8, lookahead_bits: u8) -> Vec<u8> {
+        run_encoder(
+            src,
+            DynamicEncoder::new(window_bits, lookahead_bits).unwrap(),
+        )
+    }
+
+    fn run_encoder(src: &[u8], mut encoder: DynamicEncoder) -> Vec<u8> {
+        let mut res = Vec::new();
+        let mut buf = [0u8; 64];
+        let mut src = src;
+
+        loop {
+            let writen = encoder.sink(src);
+            src = &src[writen..];
+            if src.is_empty() && encoder.finish() {
+                return res;
+            }
+            loop {
+                let (n, more) = encoder.poll(&mut buf);
+                res.extend_from_slice(&buf[..n]);
+                if !more {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn decode(src: &[u8], window_bits: u8, lookahead_bits: u8) -> Vec<u8> {
+        run_decoder(
+            src,
+            DynamicDecoder::with_input_buffer(32, window_bits, lookahead_bits).unwrap(),
+        )
+    }
+
+    fn run_decoder(src: &[u8], mut decoder: DynamicDecoder) -> Vec<u8> {
+        let mut res = Vec::new();
+        let mut buf = [0u8; 100];
+        let mut src = src;
+
+        loop {
+            let writen = decoder.sink(src);
+            src = &src[writen..];
+            loop {
+                let (n, more) = decoder.poll(&mut buf);
+                res.extend_from_slice(&buf[..n]);
+                if !more {
+                    break;
+                }
+            }
+            if src.is_empty() && decoder.finish() {
+                return res;
+            }
+        }
+    }
+
+    #[test]
+    fn params() {
+        assert!(valid_params(8, 4));
+        assert!(valid_params(4, 3));
+        assert!(valid_params(15, 14));
+        assert!(!valid_params(3, 2));
+        assert!(!valid_params(16, 4));
+        assert!(!valid_params(8, 8));
+        assert!(!valid_params(8, 2));
+
+        assert!(DynamicEncoder::new(16, 4).is_none());
+        assert!(DynamicDecoder::new(8, 9).is_none());
+
+        let encoder = DynamicEncoder::new(11, 5).unwrap();
+        assert_eq!((encoder.window_bits(), encoder.lookahead_bits()), (11, 5));
+    }
+
+    #[test]
+    fn same_as_static() {
+        let src = (0..3000).map(|n| (n * n % 251) as u8).collect::<Vec<_>>();
+
+        let dynamic = encode(
+            &src,
+            HEATSHRINK_STATIC_WINDOW_BITS as u8,
+            HEATSHRINK_STATIC_LOOKAHEAD_BITS as u8,
+        );
+        let normal = HeatshrinkEncoder::source(src.iter().cloned()).collect::<Vec<_>>();
+        assert_eq!(dynamic, normal);
+
+        let decoded = HeatshrinkDecoder::source(dynamic.into_iter()).collect::<Vec<_>>();
+        assert_eq!(decoded, src);
+    }
+
+    #[test]
+    fn levels() {
+        let src = include_bytes!("dynamic.rs");
+        let dict = &src[src.len() - 300..];
+        for (window_bits, lookahead_bits) in [(4, 3), (8, 4), (11, 5), (15, 14)] {
+            let encode = |level, dict: Option<&[u8]>| {
+                let mut encoder =
+                    DynamicEncoder::with_level(window_bits, lookahead_bits, level).unwrap();
+                assert_eq!(
+                    (encoder.window_bits(), encoder.lookahead_bits()),
+                    (window_bits, lookahead_bits)
+                );
+                if let Some(dict) = dict {
+                    encoder.set_dictionary(dict);
+                }
+                run_encoder(src, encoder)
+            };
+            // без индекса поиска поток читается тем же декодером, Best здесь - как Default
+            for dict in [None, Some(dict)] {
+                let default = encode(Level::Default, dict);
+                let fast = encode(Level::Fast, dict);
+                let mut decoder = DynamicDecoder::new(window_bits, lookahead_bits).unwrap();
+                if let Some(dict) = dict {
+                    decoder.set_dictionary(dict);
+                }
+                assert_eq!(run_decoder(&fast, decoder), src);
+                // с окном 2^15 поиск перебором может выбрать другие ссылки
+                if window_bits < 15 {
+                    assert_eq!(fast, default);
+                }
+                assert_eq!(encode(Level::Best, dict), default);
+            }
+        }
+    }
+
+    #[test]
+    fn enc_dec_all_params() {
+        use rand::Rng;
+
+        let mut rng = rand::thread_rng();
+        let src = (0..5000)
+            .map(|n| {
+                if n % 7 == 0 {
+                    rng.gen()
+                } else {
+                    (n % 13) as u8
+                }
+            })
+            .collect::<Vec<u8>>();
+
+        for window_bits in 4..=15 {
+            for lookahead_bits in 3..window_bits {
+                let encoded = encode(&src, window_bits, lookahead_bits);
+                assert_eq!(
+                    decode(&encoded, window_bits, lookahead_bits),
+                    src,
+                    "-w {} -l {}",
+                    window_bits,
+                    lookahead_bits
+                );
+            }
+        }
+    }
+}
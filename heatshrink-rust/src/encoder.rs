@@ -1,43 +1,88 @@
 #![allow(non_upper_case_globals)]
 
-use crate::encoder_common::_heatshrink_encoder;
-use crate::encoder_common::{
-    heatshrink_encoder_finish, heatshrink_encoder_poll, heatshrink_encoder_reset,
-    heatshrink_encoder_sink,
-};
+use alloc::vec::{IntoIter, Vec};
+
+use crate::encoder_common::Context;
 use crate::encoder_common::{
     HSE_finish_res_HSER_FINISH_DONE, HSE_finish_res_HSER_FINISH_MORE, HSE_poll_res_HSER_POLL_EMPTY,
     HSE_poll_res_HSER_POLL_MORE, HSE_sink_res_HSER_SINK_ERROR_MISUSE, HSE_sink_res_HSER_SINK_OK,
 };
+#[cfg(feature = "stats")]
+use crate::stats::{Collector, Stats};
+use crate::{
+    compress_bound, dictionary, optimal, Level, STATIC_LOOKAHEAD_BITS, STATIC_WINDOW_BITS,
+};
 
 pub struct HeatshrinkEncoder<T>
 where
     T: Iterator<Item = u8>,
 {
-    ctx: _heatshrink_encoder,
+    ctx: Context,
+    // Level::Best: весь вход сжимается сразу при первом next(), здесь - хвост словаря
+    best: Option<Vec<u8>>,
+    optimal: Option<IntoIter<u8>>,
     delayed_byte: Option<u8>,
     finished: bool,
+    // принято кодером / выдано итератором, для size_hint()
+    sunk: usize,
+    emitted: usize,
+    #[cfg(feature = "stats")]
+    stats: Collector,
+    // итератор выдал None
+    drained: bool,
 
     // Поскольку это трейт а не объект нужно чтобы ссылка жила не меньше чем сама структура
     src: T,
 }
 
-impl<'a, T> HeatshrinkEncoder<T>
+impl<T> HeatshrinkEncoder<T>
 where
     T: Iterator<Item = u8>,
 {
     pub fn source(src: T) -> Self {
-        let mut res = Self {
-            ctx: _heatshrink_encoder::default(),
+        Self::with_level(src, Level::Default)
+    }
+
+    /// Сжатие с уровнем `level`, см. [`Level`]
+    pub fn with_level(src: T, level: Level) -> Self {
+        Self {
+            ctx: Context::new(level),
+            best: if level == Level::Best {
+                Some(Vec::new())
+            } else {
+                None
+            },
+            optimal: None,
             delayed_byte: None,
             finished: false,
+            sunk: 0,
+            emitted: 0,
+            #[cfg(feature = "stats")]
+            stats: Collector::default(),
+            drained: false,
             src, // то же что src: src
-        };
-        unsafe {
-            heatshrink_encoder_reset(&mut res.ctx);
+        }
+    }
+
+    /// Окно заранее заполнено словарем, см. [`crate::dictionary`], сжатие с уровнем `level`
+    pub fn with_dictionary(src: T, dict: &[u8], level: Level) -> Self {
+        let mut res = Self::with_level(src, level);
+        match &mut res.best {
+            Some(best) => best.extend_from_slice(dictionary::tail(dict, STATIC_WINDOW_BITS)),
+            None => res.ctx.prime(dict),
         }
         res
     }
+
+    /// Статистика сжатия, None пока итератор не выдал последний байт
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Option<&Stats> {
+        if self.drained {
+            Some(self.stats.stats())
+        } else {
+            None
+        }
+    }
 }
 
 impl<T> Iterator for HeatshrinkEncoder<T>
@@ -47,12 +92,62 @@ where
     type Item = u8;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let res = self.next_byte();
+        self.emitted += res.is_some() as usize;
+        #[cfg(feature = "stats")]
+        if let Some(b) = res {
+            self.stats.push(b);
+        }
+        self.drained |= res.is_none();
+        res
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.drained {
+            return (0, Some(0));
+        }
+        if let Some(packed) = &self.optimal {
+            return packed.size_hint();
+        }
+        let (lower, upper) = if self.finished {
+            (0, Some(0))
+        } else {
+            self.src.size_hint()
+        };
+        let known = self.sunk + self.delayed_byte.is_some() as usize;
+
+        let lower = compress_min(known.saturating_add(lower)).saturating_sub(self.emitted);
+        let upper = upper
+            .and_then(|n| n.checked_add(known))
+            .filter(|n| *n <= usize::MAX / 9 * 8)
+            .map(|n| compress_bound(n, STATIC_WINDOW_BITS, STATIC_LOOKAHEAD_BITS) - self.emitted);
+        (lower, upper)
+    }
+}
+
+// Минимальный размер сжатых данных: каждый токен покрывает не больше 2^lookahead байт
+// и занимает не меньше min(9, 1 + window + lookahead) бит
+const fn compress_min(input_len: usize) -> usize {
+    let max_len = 1 << STATIC_LOOKAHEAD_BITS;
+    let backref_bits = 1 + STATIC_WINDOW_BITS as usize + STATIC_LOOKAHEAD_BITS as usize;
+    let token_bits = if backref_bits < 9 { backref_bits } else { 9 };
+    let tokens = input_len.div_ceil(max_len);
+    // tokens * token_bits / 8 с округлением вверх, без переполнения
+    tokens / 8 * token_bits + (tokens % 8 * token_bits).div_ceil(8)
+}
+
+impl<T> HeatshrinkEncoder<T>
+where
+    T: Iterator<Item = u8>,
+{
+    fn next_byte(&mut self) -> Option<u8> {
+        if self.best.is_some() {
+            return self.next_optimal();
+        }
         loop {
-            let mut outbuf: u8 = 0;
-            let mut actualy_read: usize = 0;
-            let res = unsafe {
-                heatshrink_encoder_poll(&mut self.ctx, &mut outbuf, 1, &mut actualy_read)
-            };
+            let mut outbuf = [0u8];
+            let (res, actualy_read) = self.ctx.poll(&mut outbuf);
+            let outbuf = outbuf[0];
             match res {
                 HSE_poll_res_HSER_POLL_EMPTY => {
                     if actualy_read == 0 {
@@ -87,13 +182,9 @@ where
                     } else {
                         self.src.next()
                     };
-                    if let Some(mut b) = v {
-                        let mut actualy_read = 0;
-                        let res = unsafe {
-                            heatshrink_encoder_sink(&mut self.ctx, &mut b, 1, &mut actualy_read)
-                        };
-                        match res {
-                            HSE_sink_res_HSER_SINK_OK => { /* ok */ }
+                    if let Some(b) = v {
+                        match self.ctx.sink(&[b]).0 {
+                            HSE_sink_res_HSER_SINK_OK => self.sunk += 1,
                             HSE_sink_res_HSER_SINK_ERROR_MISUSE => {
                                 self.delayed_byte = Some(b);
                                 break;
@@ -103,8 +194,7 @@ where
                     } else {
                         // try finalise
                         self.finished = true;
-                        let res = unsafe { heatshrink_encoder_finish(&mut self.ctx) };
-                        match res {
+                        match self.ctx.finish() {
                             HSE_finish_res_HSER_FINISH_DONE => return None, // ok
                             HSE_finish_res_HSER_FINISH_MORE => break, // there is data in encoder buff
                             _ => panic!(),
@@ -116,21 +206,75 @@ where
     }
 }
 
-#[cfg(all(unix))]
+impl<T> HeatshrinkEncoder<T>
+where
+    T: Iterator<Item = u8>,
+{
+    fn next_optimal(&mut self) -> Option<u8> {
+        if self.optimal.is_none() {
+            let src = self.src.by_ref().collect::<Vec<_>>();
+            self.sunk = src.len();
+            self.finished = true;
+            let dict = self.best.as_deref().unwrap_or_default();
+            let packed = optimal::compress_with_dictionary(
+                &src,
+                dict,
+                STATIC_WINDOW_BITS,
+                STATIC_LOOKAHEAD_BITS,
+            );
+            self.optimal = packed.map(Vec::into_iter);
+        }
+        self.optimal.as_mut()?.next()
+    }
+}
+
+#[cfg(unix)]
 #[cfg(test)]
 mod tests {
     extern crate alloc;
 
     use alloc::vec::Vec;
 
+    use crate::decoder::HeatshrinkDecoder;
     use crate::encoder::HeatshrinkEncoder;
+    use crate::Level;
 
     #[test]
     fn encode_static_data() {
-        static DATA: &[u8; 19] = b"s;djfdlsdj\x00\0128sdfs";
+        static DATA: &[u8; 19] = b"s;djfdlsdj\x00\x00128sdfs";
         let _ = HeatshrinkEncoder::source(DATA.iter().cloned()).collect::<Vec<_>>();
     }
 
+    #[test]
+    fn size_hint() {
+        use rand::Rng;
+
+        let mut rng = rand::thread_rng();
+        for len in [0usize, 1, 8, 100, 1000] {
+            let src = (0..len).map(|_| rng.gen_range(0..8u8)).collect::<Vec<_>>();
+            let mut enc = HeatshrinkEncoder::source(src.iter().cloned());
+            let mut remaining = enc.by_ref().collect::<Vec<_>>().len();
+
+            let mut enc = HeatshrinkEncoder::source(src.iter().cloned());
+            loop {
+                let (lower, upper) = enc.size_hint();
+                assert!(lower <= remaining, "{} <= {}", lower, remaining);
+                assert!(remaining <= upper.unwrap());
+                if enc.next().is_none() {
+                    break;
+                }
+                remaining -= 1;
+            }
+            assert_eq!(enc.size_hint(), (0, Some(0)));
+        }
+
+        // вход неизвестной длины
+        let enc = HeatshrinkEncoder::source(core::iter::repeat(0u8));
+        let (lower, upper) = enc.size_hint();
+        assert!(lower > usize::MAX / 16);
+        assert_eq!(upper, None);
+    }
+
     #[test]
     fn encode_zeros() {
         let zeros = [0u8; 8];
@@ -141,4 +285,37 @@ mod tests {
         assert_eq!(Some(0x38), enc.next());
         assert_eq!(None, enc.next());
     }
+
+    #[test]
+    fn levels() {
+        use rand::Rng;
+
+        let mut rng = rand::thread_rng();
+        let text = include_bytes!("encoder.rs");
+        let noise = (0..3000).map(|_| rng.gen_range(0..4u8)).collect::<Vec<_>>();
+        for src in [&text[..], &noise[..], &[0u8; 100][..], b""] {
+            let encode = |level| {
+                HeatshrinkEncoder::with_level(src.iter().cloned(), level).collect::<Vec<_>>()
+            };
+            let default = encode(Level::Default);
+            assert_eq!(encode(Level::Fast), default);
+            let best = encode(Level::Best);
+            assert!(
+                best.len() <= default.len(),
+                "{} {}",
+                best.len(),
+                default.len()
+            );
+            let decoded = HeatshrinkDecoder::source(best.iter().cloned())
+                .with_limit(src.len())
+                .collect::<Vec<_>>();
+            assert_eq!(decoded, src);
+        }
+
+        let mut enc = HeatshrinkEncoder::with_level(text.iter().cloned(), Level::Best);
+        assert!(enc.size_hint().1.unwrap() >= text.len());
+        enc.next();
+        let remaining = enc.size_hint();
+        assert_eq!(remaining.0, enc.count());
+    }
 }
@@ -5,13 +5,54 @@
 #![allow(deprecated)]
 
 include!("bindings/bindings-encoder.rs");
+include!("bindings/bindings-compact.rs");
 
 impl Default for _heatshrink_encoder {
     fn default() -> Self {
-        unsafe { core::mem::uninitialized() }
+        unsafe { core::mem::zeroed() }
     }
 }
 
+impl Default for _heatshrink_encoder_compact {
+    fn default() -> Self {
+        unsafe { core::mem::zeroed() }
+    }
+}
+
+// Состояния heatshrink_encoder.c, нужные для sync flush
+pub(crate) const HSES_NOT_FULL: u8 = 0;
+pub(crate) const HSES_DONE: u8 = 9;
+pub(crate) const FLAG_IS_FINISHING: u8 = 0x01;
+
+// Продолжить поток после finish() и poll() до HSES_DONE, не сбрасывая окно:
+// все принятое уже выдано и выровнено до байта, дальше кодер работает как после save_backlog().
+// false - кодер еще не дошел до HSES_DONE
+macro_rules! resume_after_flush {
+    ($ctx:expr, $buffer:expr, $input_buf_size:expr) => {{
+        if $ctx.state != $crate::encoder_common::HSES_DONE {
+            false
+        } else {
+            let input_buf_size: usize = $input_buf_size;
+            let msi = $ctx.match_scan_index as usize;
+            let buffer: &mut [u8] = $buffer;
+            // save_backlog(): последние input_buf_size байт становятся окном
+            buffer.copy_within(msi..2 * input_buf_size, 0);
+            $ctx.match_scan_index = 0;
+            $ctx.input_size -= msi as u16;
+
+            $ctx.state = $crate::encoder_common::HSES_NOT_FULL;
+            $ctx.flags &= !$crate::encoder_common::FLAG_IS_FINISHING;
+            $ctx.match_length = 0;
+            $ctx.outgoing_bits_count = 0;
+            // st_flush_bit_buffer() не сбрасывает битовый буфер
+            $ctx.current_byte = 0;
+            $ctx.bit_index = 0x80;
+            true
+        }
+    }};
+}
+pub(crate) use resume_after_flush;
+
 impl _heatshrink_encoder {
     pub(crate) const fn input_buffer_size() -> usize {
         1 << HEATSHRINK_STATIC_WINDOW_BITS
@@ -20,9 +61,183 @@ impl _heatshrink_encoder {
     pub(crate) fn input_size(&self) -> u16 {
         self.input_size
     }
+
+    // словарь в окно, только сразу после reset
+    pub(crate) fn prime(&mut self, dict: &[u8]) {
+        crate::dictionary::prime_backlog(
+            &mut self.buffer,
+            HEATSHRINK_STATIC_WINDOW_BITS as u8,
+            dict,
+        );
+    }
+
+    // продолжить поток после sync flush, см. resume_after_flush!
+    pub(crate) fn resume(&mut self) -> bool {
+        resume_after_flush!(self, &mut self.buffer[..], Self::input_buffer_size())
+    }
+}
+
+impl _heatshrink_encoder_compact {
+    fn prime(&mut self, dict: &[u8]) {
+        crate::dictionary::prime_backlog(
+            &mut self.buffer,
+            HEATSHRINK_STATIC_WINDOW_BITS as u8,
+            dict,
+        );
+    }
+
+    fn resume(&mut self) -> bool {
+        resume_after_flush!(
+            self,
+            &mut self.buffer[..],
+            _heatshrink_encoder::input_buffer_size()
+        )
+    }
+}
+
+/// Куда [`Context`] выдает сжатые данные
+pub(crate) trait Output {
+    /// false - не влезло: часть сжатого потеряна, поток испорчен
+    fn put(&mut self, data: &[u8]) -> bool;
+}
+
+impl Output for alloc::vec::Vec<u8> {
+    fn put(&mut self, data: &[u8]) -> bool {
+        self.extend_from_slice(data);
+        true
+    }
+}
+
+/// Контекст кодера C выбранного уровня: с индексом поиска или без.
+/// Хранится на месте, без кучи: размер - как у варианта с индексом, см. [`crate::Level::Fast`]
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+pub(crate) enum Context {
+    Indexed(_heatshrink_encoder),
+    Compact(_heatshrink_encoder_compact),
+}
+
+impl Context {
+    /// Уже после reset. [`crate::Level::Best`] кодер C не использует, для него - как Default
+    pub(crate) fn new(level: crate::Level) -> Self {
+        let mut res = match level {
+            crate::Level::Fast => Context::Compact(Default::default()),
+            _ => Context::Indexed(Default::default()),
+        };
+        res.reset();
+        res
+    }
+
+    pub(crate) fn reset(&mut self) {
+        match self {
+            Context::Indexed(ctx) => unsafe { heatshrink_encoder_reset(ctx) },
+            Context::Compact(ctx) => unsafe { heatshrink_compact_encoder_reset(ctx) },
+        }
+    }
+
+    /// (результат, сколько принято)
+    pub(crate) fn sink(&mut self, data: &[u8]) -> (HSE_sink_res, usize) {
+        let mut written = 0;
+        let res = match self {
+            Context::Indexed(ctx) => unsafe {
+                heatshrink_encoder_sink(ctx, data.as_ptr(), data.len(), &mut written)
+            },
+            Context::Compact(ctx) => unsafe {
+                heatshrink_compact_encoder_sink(ctx, data.as_ptr(), data.len(), &mut written)
+            },
+        };
+        (res, written)
+    }
+
+    /// (результат, сколько выдано)
+    pub(crate) fn poll(&mut self, out: &mut [u8]) -> (HSE_poll_res, usize) {
+        let mut read = 0;
+        let res = match self {
+            Context::Indexed(ctx) => unsafe {
+                heatshrink_encoder_poll(ctx, out.as_mut_ptr(), out.len(), &mut read)
+            },
+            Context::Compact(ctx) => unsafe {
+                heatshrink_compact_encoder_poll(ctx, out.as_mut_ptr(), out.len(), &mut read)
+            },
+        };
+        (res, read)
+    }
+
+    pub(crate) fn finish(&mut self) -> HSE_finish_res {
+        match self {
+            Context::Indexed(ctx) => unsafe { heatshrink_encoder_finish(ctx) },
+            Context::Compact(ctx) => unsafe { heatshrink_compact_encoder_finish(ctx) },
+        }
+    }
+
+    pub(crate) fn prime(&mut self, dict: &[u8]) {
+        match self {
+            Context::Indexed(ctx) => ctx.prime(dict),
+            Context::Compact(ctx) => ctx.prime(dict),
+        }
+    }
+
+    /// Продолжить поток после sync flush, см. resume_after_flush!
+    pub(crate) fn resume(&mut self) -> bool {
+        match self {
+            Context::Indexed(ctx) => ctx.resume(),
+            Context::Compact(ctx) => ctx.resume(),
+        }
+    }
+
+    /// Байт во входном буфере кодера, еще не сжатых
+    pub(crate) fn input_size(&self) -> u16 {
+        match self {
+            Context::Indexed(ctx) => ctx.input_size,
+            Context::Compact(ctx) => ctx.input_size,
+        }
+    }
+
+    /// Все `data` в кодер, сжатое по ходу - в `out`. false - `out` переполнен
+    pub(crate) fn sink_all(&mut self, mut data: &[u8], out: &mut impl Output) -> bool {
+        while !data.is_empty() {
+            match self.sink(data) {
+                // MISUSE: входной буфер полон, сначала poll()
+                (HSE_sink_res_HSER_SINK_OK | HSE_sink_res_HSER_SINK_ERROR_MISUSE, n) => {
+                    data = &data[n..]
+                }
+                _ => panic!(),
+            }
+            if !self.poll_all(out) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Все, что кодер готов отдать, в `out`
+    pub(crate) fn poll_all(&mut self, out: &mut impl Output) -> bool {
+        let mut buf = [0u8; 32];
+        loop {
+            let (res, n) = self.poll(&mut buf);
+            if !out.put(&buf[..n]) {
+                return false;
+            }
+            match res {
+                HSE_poll_res_HSER_POLL_EMPTY => return true,
+                HSE_poll_res_HSER_POLL_MORE => {}
+                _ => panic!(),
+            }
+        }
+    }
+
+    /// Конец данных: остаток с выравниванием до байта в `out`
+    pub(crate) fn finish_all(&mut self, out: &mut impl Output) -> bool {
+        while self.finish() == HSE_finish_res_HSER_FINISH_MORE {
+            if !self.poll_all(out) {
+                return false;
+            }
+        }
+        true
+    }
 }
 
-#[cfg(all(unix))]
+#[cfg(unix)]
 #[cfg(test)]
 mod tests {
     extern crate alloc;
@@ -359,7 +574,7 @@ mod tests {
                 assert_eq!(result, HSE_sink_res_HSER_SINK_OK);
 
                 src_slice = &src_slice[writen..];
-                let finish = src_slice.len() == 0;
+                let finish = src_slice.is_empty();
 
                 let input_size = if finish {
                     // записан последний блок
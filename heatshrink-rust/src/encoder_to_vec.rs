@@ -2,16 +2,16 @@
 
 use alloc::vec::Vec;
 
-use crate::encoder_common::_heatshrink_encoder;
+use crate::encoder_common::{Context, Output};
 use crate::encoder_common::{
-    heatshrink_encoder_finish, heatshrink_encoder_poll, heatshrink_encoder_reset,
-    heatshrink_encoder_sink,
-};
-use crate::encoder_common::{
-    HSE_finish_res_HSER_FINISH_DONE, HSE_finish_res_HSER_FINISH_MORE, HSE_poll_res_HSER_POLL_EMPTY,
-    HSE_poll_res_HSER_POLL_ERROR_MISUSE, HSE_poll_res_HSER_POLL_MORE,
+    HSE_poll_res_HSER_POLL_EMPTY, HSE_poll_res_HSER_POLL_ERROR_MISUSE, HSE_poll_res_HSER_POLL_MORE,
     HSE_sink_res_HSER_SINK_ERROR_MISUSE, HSE_sink_res_HSER_SINK_OK, HEATSHRINK_STATIC_WINDOW_BITS,
 };
+use crate::framing::segment_header;
+use crate::record_stream::VARINT_MAX;
+#[cfg(feature = "stats")]
+use crate::stats::{Collector, Stats};
+use crate::{compress_bound, Level, STATIC_LOOKAHEAD_BITS, STATIC_WINDOW_BITS};
 
 pub enum Result {
     // данные успешно обработаны
@@ -22,6 +22,9 @@ pub enum Result {
 
     // ошибка: выходной буфер кончился, финализация неуспешна
     Overflow,
+
+    // ошибка: flush() в потоке без framed(), ничего не сделано
+    NotFramed,
 }
 
 const MINIMAL_BUFF_SIZE: usize = 1 << HEATSHRINK_STATIC_WINDOW_BITS;
@@ -29,37 +32,128 @@ const MINIMAL_BUFF_SIZE: usize = 1 << HEATSHRINK_STATIC_WINDOW_BITS;
 /// во входном буфере после успешного poll()
 const MAX_SADIMENT: usize = 15;
 
+// свободная часть буфера с позиции wp
+struct Tail<'a> {
+    dest: &'a mut [u8],
+    wp: &'a mut usize,
+}
+
+impl Output for Tail<'_> {
+    fn put(&mut self, data: &[u8]) -> bool {
+        match self.dest.get_mut(*self.wp..*self.wp + data.len()) {
+            Some(out) => {
+                out.copy_from_slice(data);
+                *self.wp += data.len();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
 pub struct HeatshrinkEncoderToVec {
-    ctx: _heatshrink_encoder,
+    ctx: Context,
     dest: Vec<u8>,
     wp: usize,
     reserved_start_pos: usize,
+    finished: bool,
+    // сколько байт принято и сколько гарантированно влезет, см. with_input_len()
+    total_in: usize,
+    input_len: Option<usize>,
+    // framed(): начало текущего сегмента
+    segment_start: Option<usize>,
+    // разобрано для статистики до этой позиции
+    #[cfg(feature = "stats")]
+    parsed: usize,
+    #[cfg(feature = "stats")]
+    collector: Collector,
+    #[cfg(feature = "stats")]
+    stats: Option<Stats>,
 }
 
 impl HeatshrinkEncoderToVec {
     /// 1. Cлайс для записи должен быть капасити не меньше чем MINIMAL_BWFF_SIZE
+    /// 2. Когда буфер почти заполнен, push_bytes() сам финализирует поток и вернет Done
+    /// 3. Граница [`compress_bound`] здесь не действует: последние MINIMAL_BUFF_SIZE байт
+    ///    держатся в резерве, и Overflow или Done возможны при любой капасити.
+    ///    Гарантия по [`compress_bound`] - только у [`HeatshrinkEncoderToVec::with_input_len`]
     pub fn dest(mut dest: Vec<u8>, offset: usize) -> Self {
         assert!(dest.capacity() >= MINIMAL_BUFF_SIZE);
 
         // tamporary change vector size to it's max capasity
         unsafe { dest.set_len(dest.capacity()) };
-        let mut res = Self {
-            ctx: _heatshrink_encoder::default(),
+        Self {
+            ctx: Context::new(Level::Default),
             reserved_start_pos: dest.len() - MINIMAL_BUFF_SIZE,
             dest,
             wp: offset,
-        };
-        unsafe {
-            heatshrink_encoder_reset(&mut res.ctx);
+            finished: false,
+            total_in: 0,
+            input_len: None,
+            segment_start: None,
+            #[cfg(feature = "stats")]
+            parsed: offset,
+            #[cfg(feature = "stats")]
+            collector: Collector::default(),
+            #[cfg(feature = "stats")]
+            stats: None,
         }
+    }
+
+    /// Буфер под `input_len` байт входа (капасити увеличивается до [`compress_bound`]):
+    /// пока принято не больше `input_len` байт и не было точек flush (запаса на них нет),
+    /// push_bytes() не вернет Overflow и не финализирует поток сам, только через finish().
+    /// Сверх `input_len` - всегда Overflow, принятое до этого можно финализировать.
+    pub fn with_input_len(mut dest: Vec<u8>, offset: usize, input_len: usize) -> Self {
+        let bound = compress_bound(input_len, STATIC_WINDOW_BITS, STATIC_LOOKAHEAD_BITS);
+        let capacity = offset + bound.max(MINIMAL_BUFF_SIZE);
+        dest.reserve_exact(capacity.saturating_sub(dest.len()));
+
+        let mut res = Self::dest(dest, offset);
+        res.input_len = Some(input_len);
         res
     }
 
+    /// Сжатие с уровнем `level`, см. [`Level`]: Best здесь - как Default.
+    /// Вызывать сразу после конструктора
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.ctx = Context::new(level);
+        self
+    }
+
+    /// Поток с точками flush ([`crate::framing`]): сжатые данные идут сегментами с заголовком
+    /// длины, точку [`HeatshrinkEncoderToVec::flush`] декодер находит сам. Читается только
+    /// декодером в режиме framed, например [`crate::decoder::HeatshrinkDecoder::framed`].
+    /// Вызывать сразу после конструктора
+    pub fn framed(mut self) -> Self {
+        self.segment_start = Some(self.wp);
+        if self.input_len.is_some() {
+            // заголовок последнего сегмента сверх compress_bound()
+            self.dest.reserve_exact(VARINT_MAX);
+            unsafe { self.dest.set_len(self.dest.capacity()) };
+        } else {
+            self.reserved_start_pos = self.reserved_start_pos.saturating_sub(VARINT_MAX);
+        }
+        self
+    }
+
     pub fn push_bytes(&mut self, mut data: &[u8]) -> Result {
-        let mut writen = 0;
-        match unsafe {
-            heatshrink_encoder_sink(&mut self.ctx, data.as_ptr(), data.len(), &mut writen)
-        } {
+        // после finish() писать уже некуда
+        if self.finished {
+            return Result::Overflow;
+        }
+
+        // в пределах with_input_len() худший случай влезает - пишем до конца буфера без резерва
+        if let Some(input_len) = self.input_len {
+            if self.total_in + data.len() > input_len {
+                return Result::Overflow;
+            }
+            self.total_in += data.len();
+            return self.push_all(data);
+        }
+
+        let (res, writen) = self.ctx.sink(data);
+        match res {
             HSE_sink_res_HSER_SINK_OK => {
                 if writen == data.len() {
                     // все влезло, выход
@@ -79,67 +173,40 @@ impl HeatshrinkEncoderToVec {
             return Result::Overflow;
         }
 
-        let normal_out_buf = &mut self.dest[self.wp..self.reserved_start_pos];
-        let mut out_writen = 0;
-        match unsafe {
-            heatshrink_encoder_poll(
-                &mut self.ctx,
-                normal_out_buf.as_mut_ptr(),
-                normal_out_buf.len(),
-                &mut out_writen,
-            )
-        } {
+        let (res, out_writen) = self
+            .ctx
+            .poll(&mut self.dest[self.wp..self.reserved_start_pos]);
+        match res {
             HSE_poll_res_HSER_POLL_EMPTY => {
                 self.wp += out_writen; /* ok */
 
                 // запись остатков
-                let sink_res = unsafe {
-                    heatshrink_encoder_sink(&mut self.ctx, data.as_ptr(), data.len(), &mut writen)
-                };
-
-                if sink_res != HSE_sink_res_HSER_SINK_OK {
+                if self.ctx.sink(data).0 != HSE_sink_res_HSER_SINK_OK {
                     return Result::Overflow;
                 }
 
-                return if self.wp == self.reserved_start_pos {
+                if self.wp == self.reserved_start_pos {
                     self.finish()
                 } else {
                     Result::Ok
-                };
+                }
             }
             HSE_poll_res_HSER_POLL_MORE | HSE_poll_res_HSER_POLL_ERROR_MISUSE => {
                 // Есть данные, которые не влезли в основной буфер, пишем их в резервную область
                 self.wp += out_writen;
-                let reserved_out_buf = &mut self.dest[self.wp..];
-                let poll_res = unsafe {
-                    heatshrink_encoder_poll(
-                        &mut self.ctx,
-                        reserved_out_buf.as_mut_ptr(),
-                        reserved_out_buf.len(),
-                        &mut out_writen,
-                    )
-                };
+                let (poll_res, out_writen) = self.ctx.poll(&mut self.dest[self.wp..]);
                 match poll_res {
                     HSE_poll_res_HSER_POLL_EMPTY => {
                         // Обновляем позицию для следующей зписи
                         self.wp += out_writen;
 
                         // запись остатков
-                        let sink_res = unsafe {
-                            heatshrink_encoder_sink(
-                                &mut self.ctx,
-                                data.as_ptr(),
-                                data.len(),
-                                &mut writen,
-                            )
-                        };
-
-                        if sink_res != HSE_sink_res_HSER_SINK_OK {
+                        if self.ctx.sink(data).0 != HSE_sink_res_HSER_SINK_OK {
                             return Result::Overflow;
                         }
-                        return self.finish();
+                        self.finish()
                     }
-                    HSE_poll_res_HSER_POLL_MORE => return Result::Overflow,
+                    HSE_poll_res_HSER_POLL_MORE => Result::Overflow,
                     _ => panic!(),
                 }
             }
@@ -147,59 +214,166 @@ impl HeatshrinkEncoderToVec {
         }
     }
 
-    /// Любые данные, просто побайтно скармливаются упаковщику
-    pub fn push<T: Copy>(&mut self, data: T) -> Result {
-        let data = unsafe {
-            core::slice::from_raw_parts(
-                &data as *const _ as *const u8,
-                core::mem::size_of_val(&data),
-            )
+    fn push_all(&mut self, data: &[u8]) -> Result {
+        let mut out = Tail {
+            dest: &mut self.dest,
+            wp: &mut self.wp,
         };
+        if self.ctx.sink_all(data, &mut out) {
+            Result::Ok
+        } else {
+            // по compress_bound() так не бывает
+            Result::Overflow
+        }
+    }
 
-        self.push_bytes(data)
+    /// Запись фиксированного размера побайтно, как лежит в памяти (без padding - иначе
+    /// не NoUninit). Читается обратно [`crate::record::RecordReader`]
+    pub fn push_record<T: bytemuck::NoUninit>(&mut self, record: &T) -> Result {
+        self.push_bytes(bytemuck::bytes_of(record))
     }
 
     pub fn finish(&mut self) -> Result {
-        let result = unsafe { heatshrink_encoder_finish(&mut self.ctx) };
-        match result {
-            HSE_finish_res_HSER_FINISH_MORE => {
-                let out_buf = &mut self.dest[self.wp..];
-                let mut out_writen = 0;
-                match unsafe {
-                    heatshrink_encoder_poll(
-                        &mut self.ctx,
-                        out_buf.as_mut_ptr(),
-                        out_buf.len(),
-                        &mut out_writen,
-                    )
-                } {
-                    // Все успешно обработано, все влезло в выходной буффер
-                    HSE_poll_res_HSER_POLL_EMPTY => {
-                        self.wp += out_writen;
-                        unsafe { self.dest.set_len(self.wp) };
-                        Result::Done
-                    }
-                    // Финализировано неудачно, остаток данных не влез в указанный буфер
-                    // Записанные данные неконсистентны, остается только выбросить все в мусор
-                    HSE_poll_res_HSER_POLL_MORE => Result::Overflow,
-                    // ошибка
-                    _ => panic!(),
-                }
-            }
-            HSE_finish_res_HSER_FINISH_DONE => {
-                unsafe { self.dest.set_len(self.wp) };
-                Result::Done
-            }
-            _ => panic!(),
+        self.finished = true;
+        let mut out = Tail {
+            dest: &mut self.dest,
+            wp: &mut self.wp,
+        };
+        if self.ctx.finish_all(&mut out) {
+            self.done()
+        } else {
+            // остаток не влез, записанное неконсистентно
+            Result::Overflow
+        }
+    }
+
+    /// Точка flush, только в потоке [`HeatshrinkEncoderToVec::framed`]: все принятое
+    /// сжимается и выравнивается до байта, и декодер framed распаковывает
+    /// [`HeatshrinkEncoderToVec::written`] целиком. Окно сохраняется, следующие данные
+    /// по-прежнему ссылаются на предыдущие.
+    /// В обычном потоке хвост входа остается во входном буфере кодера до finish(), а точку
+    /// выравнивания посередине обычный декодер не прочтет: там flush() возвращает NotFramed
+    /// и ничего не делает.
+    /// Каждый вызов стоит до байта дополнения и заголовок сегмента, в with_input_len() запас
+    /// на них не заложен. Done - буфер почти заполнен и поток финализирован, как после finish()
+    pub fn flush(&mut self) -> Result {
+        if self.segment_start.is_none() {
+            return Result::NotFramed;
+        }
+        if self.finished {
+            return Result::Overflow;
+        }
+
+        let mut out = Tail {
+            dest: &mut self.dest,
+            wp: &mut self.wp,
+        };
+        if !self.ctx.finish_all(&mut out) {
+            // не влезло, записанное неконсистентно
+            self.finished = true;
+            return Result::Overflow;
+        }
+        self.ctx.resume();
+
+        #[cfg(feature = "stats")]
+        {
+            self.collect();
+            self.collector.sync();
+        }
+        if !self.close_segment() {
+            self.finished = true;
+            return Result::Overflow;
+        }
+
+        if self.input_len.is_none() && self.wp >= self.reserved_start_pos {
+            // выровненный поток уже корректно завершен
+            self.finished = true;
+            self.done()
+        } else {
+            Result::Ok
         }
     }
 
+    fn done(&mut self) -> Result {
+        #[cfg(feature = "stats")]
+        {
+            self.collect();
+            self.stats = Some(*self.collector.stats());
+        }
+        if !self.close_segment() {
+            return Result::Overflow;
+        }
+        unsafe { self.dest.set_len(self.wp) };
+        Result::Done
+    }
+
+    // заголовок перед сжатыми данными с прошлой точки flush; false - не влез
+    fn close_segment(&mut self) -> bool {
+        let start = match self.segment_start {
+            Some(start) if start < self.wp => start,
+            _ => return true,
+        };
+        let header = match segment_header(self.wp - start) {
+            Some(header) if header.len() <= self.dest.len() - self.wp => header,
+            _ => return false,
+        };
+        self.dest.copy_within(start..self.wp, start + header.len());
+        self.dest[start..start + header.len()].copy_from_slice(&header);
+        self.wp += header.len();
+        self.segment_start = Some(self.wp);
+        #[cfg(feature = "stats")]
+        {
+            self.parsed = self.wp;
+        }
+        true
+    }
+
+    #[cfg(feature = "stats")]
+    fn collect(&mut self) {
+        let collector = &mut self.collector;
+        self.dest[self.parsed..self.wp]
+            .iter()
+            .for_each(|b| collector.push(*b));
+        self.parsed = self.wp;
+    }
+
+    /// Статистика сжатия, None пока finish() не вернул Done
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Option<&Stats> {
+        self.stats.as_ref()
+    }
+
+    /// Уже записанная часть буфера, включая `offset`: после flush() ее можно отправлять
+    pub fn written(&self) -> &[u8] {
+        &self.dest[..self.wp]
+    }
+
     pub fn result(self) -> Vec<u8> {
         self.dest
     }
 }
 
-#[cfg(all(unix))]
+/// Форматированный вывод прямо в упаковщик: `write!(encoder, "t = {}", t)`
+impl core::fmt::Write for HeatshrinkEncoderToVec {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        // push_bytes() гарантированно принимает не больше MINIMAL_BUFF_SIZE - MAX_SADIMENT за раз
+        let mut chunks = s
+            .as_bytes()
+            .chunks(MINIMAL_BUFF_SIZE - MAX_SADIMENT)
+            .peekable();
+        while let Some(chunk) = chunks.next() {
+            match self.push_bytes(chunk) {
+                Result::Ok => {}
+                // буфер заполнен и финализирован, но строка влезла целиком
+                Result::Done if chunks.peek().is_none() => {}
+                _ => return Err(core::fmt::Error),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
 #[cfg(test)]
 mod tests {
     extern crate alloc;
@@ -227,7 +401,7 @@ mod tests {
             let v = rng.gen_range(0..u32::MAX);
             src.push(v);
 
-            match encoder.push(v) {
+            match encoder.push_record(&v) {
                 crate::encoder_to_vec::Result::Ok => {
                     in_count += mem::size_of::<u32>();
                 }
@@ -241,12 +415,13 @@ mod tests {
                     );
                     break result;
                 }
-                crate::encoder_to_vec::Result::Overflow => panic!("overrun"),
+                crate::encoder_to_vec::Result::Overflow
+                | crate::encoder_to_vec::Result::NotFramed => panic!("overrun"),
             }
         };
 
         let normaly_encoded =
-            HeatshrinkEncoder::source(src.iter().map(|i| u32::to_le_bytes(*i)).flatten())
+            HeatshrinkEncoder::source(src.iter().flat_map(|i| u32::to_le_bytes(*i)))
                 .collect::<Vec<_>>();
         let decoder = HeatshrinkDecoder::source(res.iter().cloned());
 
@@ -285,7 +460,7 @@ mod tests {
             let v = rng.gen_range(0..u32::MAX);
             src.push(v);
 
-            match encoder.push(v) {
+            match encoder.push_record(&v) {
                 crate::encoder_to_vec::Result::Ok => {
                     in_count += mem::size_of::<u32>();
                 }
@@ -299,7 +474,8 @@ mod tests {
                     );
                     break result;
                 }
-                crate::encoder_to_vec::Result::Overflow => panic!("overrun"),
+                crate::encoder_to_vec::Result::Overflow
+                | crate::encoder_to_vec::Result::NotFramed => panic!("overrun"),
             }
         };
 
@@ -309,6 +485,140 @@ mod tests {
         );
     }
 
+    #[test]
+    fn encode_fmt_write() {
+        use core::fmt::Write;
+
+        let mut encoder = HeatshrinkEncoderToVec::dest(Vec::with_capacity(4096), 0);
+        let mut expected = std::string::String::new();
+
+        for i in 0..100 {
+            writeln!(
+                encoder,
+                "[{:>5}] sensor {}: {:.3}",
+                i * 10,
+                i % 4,
+                i as f32 / 7.0
+            )
+            .unwrap();
+            writeln!(
+                expected,
+                "[{:>5}] sensor {}: {:.3}",
+                i * 10,
+                i % 4,
+                i as f32 / 7.0
+            )
+            .unwrap();
+        }
+        // длинная строка за один вызов write_str()
+        let long = "x".repeat(1000);
+        encoder.write_str(&long).unwrap();
+        expected.push_str(&long);
+
+        match encoder.finish() {
+            crate::encoder_to_vec::Result::Done => {}
+            _ => panic!(),
+        }
+        let res = encoder.result();
+
+        let decoded = HeatshrinkDecoder::source(res.into_iter()).collect::<Vec<_>>();
+        assert_eq!(decoded, expected.as_bytes());
+    }
+
+    #[test]
+    fn encode_fmt_write_overflow() {
+        use core::fmt::Write;
+        use rand::Rng;
+
+        let mut rng = rand::thread_rng();
+        let mut encoder = HeatshrinkEncoderToVec::dest(Vec::with_capacity(512), 0);
+
+        let res = (0..1000).try_for_each(|_| write!(encoder, "{:08x}", rng.gen::<u32>()));
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn encode_with_input_len() {
+        use crate::{compress_bound, STATIC_LOOKAHEAD_BITS, STATIC_WINDOW_BITS};
+        use rand::Rng;
+
+        let mut rng = rand::thread_rng();
+        for len in [0usize, 1, 100, 1000, 5000] {
+            // несжимаемые данные - худший случай
+            let src = (0..len).map(|_| rng.gen::<u8>()).collect::<Vec<_>>();
+            let mut encoder = HeatshrinkEncoderToVec::with_input_len(Vec::new(), 0, len);
+
+            let mut rest = src.as_slice();
+            while !rest.is_empty() {
+                let n = rng.gen_range(1..=rest.len().min(600));
+                match encoder.push_bytes(&rest[..n]) {
+                    crate::encoder_to_vec::Result::Ok => {}
+                    _ => panic!("len {}: {} bytes left", len, rest.len()),
+                }
+                rest = &rest[n..];
+            }
+            match encoder.finish() {
+                crate::encoder_to_vec::Result::Done => {}
+                _ => panic!("len {}: finish", len),
+            }
+            let res = encoder.result();
+            assert!(res.len() <= compress_bound(len, STATIC_WINDOW_BITS, STATIC_LOOKAHEAD_BITS));
+
+            let decoded = HeatshrinkDecoder::source(res.into_iter()).collect::<Vec<_>>();
+            assert_eq!(decoded, src);
+        }
+    }
+
+    #[test]
+    fn encode_with_input_len_exceeded() {
+        let mut encoder = HeatshrinkEncoderToVec::with_input_len(vec![1, 2], 2, 10);
+        assert!(matches!(
+            encoder.push_bytes(b"0123456789"),
+            crate::encoder_to_vec::Result::Ok
+        ));
+        assert!(matches!(
+            encoder.push_bytes(b"a"),
+            crate::encoder_to_vec::Result::Overflow
+        ));
+        assert!(matches!(
+            encoder.finish(),
+            crate::encoder_to_vec::Result::Done
+        ));
+
+        let res = encoder.result();
+        assert_eq!(res[..2], [1, 2]);
+        let decoded = HeatshrinkDecoder::source(res[2..].iter().cloned()).collect::<Vec<_>>();
+        assert_eq!(decoded, b"0123456789");
+    }
+
+    #[test]
+    fn encode_with_level() {
+        use crate::Level;
+
+        let src = include_bytes!("encoder_to_vec.rs");
+        let encode = |level| {
+            let mut encoder =
+                HeatshrinkEncoderToVec::with_input_len(Vec::new(), 0, src.len()).with_level(level);
+            assert!(matches!(
+                encoder.push_bytes(src),
+                crate::encoder_to_vec::Result::Ok
+            ));
+            assert!(matches!(
+                encoder.finish(),
+                crate::encoder_to_vec::Result::Done
+            ));
+            encoder.result()
+        };
+        let default = encode(Level::Default);
+        assert_eq!(
+            default,
+            HeatshrinkEncoder::source(src.iter().cloned()).collect::<Vec<_>>()
+        );
+        // без индекса поиска - тот же поток, Best здесь - как Default
+        assert_eq!(encode(Level::Fast), default);
+        assert_eq!(encode(Level::Best), default);
+    }
+
     #[test]
     fn encode_interrupt() {
         use rand::Rng;
@@ -324,7 +634,7 @@ mod tests {
             let v = rng.gen_range(0..u32::MAX);
             src.push(v);
 
-            match encoder.push(v) {
+            match encoder.push_record(&v) {
                 crate::encoder_to_vec::Result::Ok => {
                     in_count += mem::size_of::<u32>();
                 }
@@ -338,7 +648,8 @@ mod tests {
                     );
                     break result;
                 }
-                crate::encoder_to_vec::Result::Overflow => panic!("overrun"),
+                crate::encoder_to_vec::Result::Overflow
+                | crate::encoder_to_vec::Result::NotFramed => panic!("overrun"),
             }
 
             if src.len() > 1500 / 4 {
@@ -371,4 +682,28 @@ mod tests {
             .collect::<Vec<_>>();
         assert_eq!(r, src);
     }
+
+    #[test]
+    fn flush_needs_framed() {
+        let mut encoder = HeatshrinkEncoderToVec::dest(Vec::with_capacity(1024), 0);
+        assert!(matches!(
+            encoder.push_bytes(b"abcabcabc"),
+            crate::encoder_to_vec::Result::Ok
+        ));
+        let written = encoder.written().len();
+        assert!(matches!(
+            encoder.flush(),
+            crate::encoder_to_vec::Result::NotFramed
+        ));
+        assert_eq!(encoder.written().len(), written);
+        assert!(matches!(
+            encoder.finish(),
+            crate::encoder_to_vec::Result::Done
+        ));
+        let res = encoder.result();
+        assert_eq!(
+            HeatshrinkDecoder::source(res.iter().cloned()).collect::<Vec<_>>(),
+            b"abcabcabc"
+        );
+    }
 }
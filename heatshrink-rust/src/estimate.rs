@@ -0,0 +1,149 @@
+//! Оценка сжимаемости по выборке: стоит ли тратить CPU на сжатие (шифрованное и уже сжатое
+//! heatshrink только увеличивает, см. [`crate::container::FLAG_STORED`]).
+//!
+//! Из входа берется [`SAMPLES`] кусков по [`SAMPLE_SIZE`] байт, равномерно по всей длине,
+//! каждый разбирается жадно, как кодером C, с окном только внутри куска. Ни кодера,
+//! ни памяти под буферы не нужно. История короче настоящей, так что оценка скорее
+//! пессимистична.
+
+use crate::{STATIC_LOOKAHEAD_BITS, STATIC_WINDOW_BITS};
+
+pub const SAMPLE_SIZE: usize = 256;
+pub const SAMPLES: usize = 4;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Estimate {
+    /// Сколько байт просмотрено
+    pub sampled: usize,
+    /// Оценка их сжатого размера в байтах
+    pub compressed: usize,
+}
+
+impl Estimate {
+    /// Сжатие уменьшит данные
+    pub fn worthwhile(&self) -> bool {
+        self.compressed < self.sampled
+    }
+
+    /// Сжатый размер в процентах от исходного, без плавающей точки
+    pub fn percent(&self) -> usize {
+        if self.sampled == 0 {
+            return 100;
+        }
+        self.compressed * 100 / self.sampled
+    }
+}
+
+// Размер куска в битах при жадном разборе с параметрами основной библиотеки
+fn greedy_bits(chunk: &[u8]) -> usize {
+    let window = 1usize << STATIC_WINDOW_BITS;
+    let max_len = 1usize << STATIC_LOOKAHEAD_BITS;
+    let backref_bits = 1 + STATIC_WINDOW_BITS as usize + STATIC_LOOKAHEAD_BITS as usize;
+    // как find_longest_match(): ссылка короче break_even / 8 + 1 не выгодна
+    let min_len = backref_bits / 8 + 1;
+
+    let mut bits = 0;
+    let mut p = 0;
+    while p < chunk.len() {
+        let limit = max_len.min(chunk.len() - p);
+        let best = (p.saturating_sub(window)..p)
+            .map(|c| {
+                (0..limit)
+                    .take_while(|&k| chunk[c + k] == chunk[p + k])
+                    .count()
+            })
+            .max()
+            .unwrap_or(0);
+        if best >= min_len {
+            bits += backref_bits;
+            p += best;
+        } else {
+            bits += 9;
+            p += 1;
+        }
+    }
+    bits
+}
+
+/// Оценить сжатие `src` по выборке
+pub fn estimate(src: &[u8]) -> Estimate {
+    let mut res = Estimate {
+        sampled: 0,
+        compressed: 0,
+    };
+    let mut sample = |chunk: &[u8]| {
+        res.sampled += chunk.len();
+        res.compressed += greedy_bits(chunk).div_ceil(8);
+    };
+
+    if src.len() <= SAMPLES * SAMPLE_SIZE {
+        src.chunks(SAMPLE_SIZE).for_each(&mut sample);
+    } else {
+        let step = (src.len() - SAMPLE_SIZE) / (SAMPLES - 1);
+        for i in 0..SAMPLES {
+            sample(&src[i * step..i * step + SAMPLE_SIZE]);
+        }
+    }
+    res
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::encoder::HeatshrinkEncoder;
+    use crate::estimate::{estimate, SAMPLES, SAMPLE_SIZE};
+
+    fn noise(len: usize) -> Vec<u8> {
+        let mut x = 0x1234_5678u32;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn incompressible() {
+        let src = noise(10_000);
+        let est = estimate(&src);
+        assert_eq!(est.sampled, SAMPLES * SAMPLE_SIZE);
+        assert!(!est.worthwhile());
+        assert!(est.percent() >= 110, "{:?}", est);
+    }
+
+    #[test]
+    fn compressible() {
+        let src = include_bytes!("encoder_to_vec.rs");
+        let est = estimate(src);
+        assert!(est.worthwhile());
+
+        // не дальше 15 процентных пунктов от настоящего сжатия
+        let actual = HeatshrinkEncoder::source(src.iter().cloned()).count() * 100 / src.len();
+        assert!(
+            est.percent().abs_diff(actual) <= 15,
+            "{} {}",
+            est.percent(),
+            actual
+        );
+    }
+
+    #[test]
+    fn short() {
+        assert_eq!(estimate(b"").percent(), 100);
+        let est = estimate(&[7u8; 300]);
+        assert_eq!(est.sampled, 300);
+        assert!(est.worthwhile());
+        // вход короче выборки - точно как кодер, без ссылок до начала
+        let src = noise(100);
+        let est = estimate(&src);
+        assert_eq!(
+            est.compressed,
+            HeatshrinkEncoder::source(src.iter().cloned()).count()
+        );
+    }
+}
@@ -0,0 +1,283 @@
+//! Обратимые фильтры перед сжатием для массивов отсчетов (i16, i32 и т.п.): LZSS плохо
+//! находит повторы в медленно меняющихся числах, а в их разностях и в разложенных по
+//! байтам плоскостях - хорошо.
+//!
+//! Данные - элементы по `width` байт (1..=8, little endian), хвост короче элемента
+//! не меняется. Цепочка применяется по порядку перед сжатием ([`FilterChain::forward`])
+//! и в обратном порядке после распаковки ([`FilterChain::inverse`]).
+//! В [`crate::container`] цепочка записывается в заголовок, по байту на фильтр:
+//! вид в старшем полубайте, `width` в младшем.
+
+use alloc::vec::Vec;
+
+/// Сколько фильтров помещается в цепочку
+pub const MAX_FILTERS: usize = 4;
+
+/// Вид фильтра
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FilterKind {
+    /// Разность с предыдущим элементом (по модулю 2^(8 * width))
+    Delta,
+    /// XOR с предыдущим элементом
+    Xor,
+    /// Байтовые плоскости: сначала все младшие байты элементов, потом следующие и т.д.
+    Shuffle,
+}
+
+/// Фильтр над элементами по `width` байт. Ширина проверяется в [`Filter::new`],
+/// так что forward() / inverse() принимают любые данные
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Filter {
+    kind: FilterKind,
+    width: u8,
+}
+
+impl Filter {
+    /// None - ширина не 1..=8
+    pub const fn new(kind: FilterKind, width: u8) -> Option<Self> {
+        if width >= 1 && width <= 8 {
+            Some(Self { kind, width })
+        } else {
+            None
+        }
+    }
+
+    pub fn kind(&self) -> FilterKind {
+        self.kind
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    /// Байт для заголовка контейнера
+    pub fn to_byte(&self) -> u8 {
+        let kind = match self.kind {
+            FilterKind::Delta => 1,
+            FilterKind::Xor => 2,
+            FilterKind::Shuffle => 3,
+        };
+        kind << 4 | self.width
+    }
+
+    /// None - неизвестный вид или недопустимая ширина
+    pub fn from_byte(b: u8) -> Option<Self> {
+        let kind = match b >> 4 {
+            1 => FilterKind::Delta,
+            2 => FilterKind::Xor,
+            3 => FilterKind::Shuffle,
+            _ => return None,
+        };
+        Self::new(kind, b & 0x0f)
+    }
+
+    /// Применить перед сжатием
+    pub fn forward(&self, data: &mut [u8]) {
+        let width = self.width as usize;
+        match self.kind {
+            FilterKind::Delta => {
+                // с конца, чтобы предыдущий элемент был еще исходным
+                for i in (1..data.len() / width).rev() {
+                    let d = read(data, i, width).wrapping_sub(read(data, i - 1, width));
+                    write(data, i, width, d);
+                }
+            }
+            FilterKind::Xor => {
+                for i in (1..data.len() / width).rev() {
+                    let d = read(data, i, width) ^ read(data, i - 1, width);
+                    write(data, i, width, d);
+                }
+            }
+            FilterKind::Shuffle => {
+                let count = data.len() / width;
+                let src = data[..count * width].to_vec();
+                for (i, element) in src.chunks_exact(width).enumerate() {
+                    for (plane, b) in element.iter().enumerate() {
+                        data[plane * count + i] = *b;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Обратить после распаковки
+    pub fn inverse(&self, data: &mut [u8]) {
+        let width = self.width as usize;
+        match self.kind {
+            FilterKind::Delta => {
+                for i in 1..data.len() / width {
+                    let v = read(data, i, width).wrapping_add(read(data, i - 1, width));
+                    write(data, i, width, v);
+                }
+            }
+            FilterKind::Xor => {
+                for i in 1..data.len() / width {
+                    let v = read(data, i, width) ^ read(data, i - 1, width);
+                    write(data, i, width, v);
+                }
+            }
+            FilterKind::Shuffle => {
+                let count = data.len() / width;
+                let src = data[..count * width].to_vec();
+                for (i, element) in data.chunks_exact_mut(width).enumerate() {
+                    for (plane, b) in element.iter_mut().enumerate() {
+                        *b = src[plane * count + i];
+                    }
+                }
+            }
+        }
+    }
+}
+
+// элемент i как little endian число
+fn read(data: &[u8], i: usize, width: usize) -> u64 {
+    data[i * width..(i + 1) * width]
+        .iter()
+        .rev()
+        .fold(0, |acc, b| acc << 8 | *b as u64)
+}
+
+// младшие width байт v, лишнее отбрасывается - арифметика по модулю 2^(8 * width)
+fn write(data: &mut [u8], i: usize, width: usize, v: u64) {
+    data[i * width..(i + 1) * width].copy_from_slice(&v.to_le_bytes()[..width]);
+}
+
+/// Цепочка до [`MAX_FILTERS`] фильтров, без выделения памяти
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct FilterChain {
+    filters: [Option<Filter>; MAX_FILTERS],
+    len: u8,
+}
+
+impl FilterChain {
+    /// Без фильтров
+    pub const fn empty() -> Self {
+        Self {
+            filters: [None; MAX_FILTERS],
+            len: 0,
+        }
+    }
+
+    /// None - больше [`MAX_FILTERS`]
+    pub fn new(filters: &[Filter]) -> Option<Self> {
+        if filters.len() > MAX_FILTERS {
+            return None;
+        }
+        let mut res = Self::default();
+        for f in filters {
+            res.filters[res.len as usize] = Some(*f);
+            res.len += 1;
+        }
+        Some(res)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Filter> + '_ {
+        self.filters[..self.len()].iter().flatten().cloned()
+    }
+
+    /// Все фильтры по порядку, перед сжатием
+    pub fn forward(&self, data: &mut [u8]) {
+        self.iter().for_each(|f| f.forward(data));
+    }
+
+    /// В обратном порядке, после распаковки
+    pub fn inverse(&self, data: &mut [u8]) {
+        self.iter().rev().for_each(|f| f.inverse(data));
+    }
+
+    /// `src` с фильтрами, копией
+    pub fn apply(&self, src: &[u8]) -> Vec<u8> {
+        let mut res = src.to_vec();
+        self.forward(&mut res);
+        res
+    }
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::encoder::HeatshrinkEncoder;
+    use crate::filter::{Filter, FilterChain, FilterKind};
+
+    fn filter(kind: FilterKind, width: u8) -> Filter {
+        Filter::new(kind, width).unwrap()
+    }
+
+    // медленно меняющийся сигнал, как с АЦП
+    fn samples() -> Vec<u8> {
+        (0..2000i32)
+            .map(|n| (1000 + n * 3 + (n % 17) * 5 - (n % 5) * 7) as i16)
+            .flat_map(i16::to_le_bytes)
+            .collect()
+    }
+
+    fn compressed_len(src: &[u8]) -> usize {
+        HeatshrinkEncoder::source(src.iter().cloned()).count()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let src = (0..103u32).map(|n| (n * n * 37) as u8).collect::<Vec<_>>();
+        for width in 1..=8 {
+            for kind in [FilterKind::Delta, FilterKind::Xor, FilterKind::Shuffle] {
+                let f = filter(kind, width);
+                assert_eq!(Filter::from_byte(f.to_byte()), Some(f));
+                let tail = src.len() / width as usize * width as usize;
+                let mut data = src.clone();
+                f.forward(&mut data);
+                if width > 1 || kind != FilterKind::Shuffle {
+                    assert_ne!(data, src, "{:?}", f);
+                }
+                // хвост короче элемента не трогается
+                assert_eq!(data[tail..], src[tail..]);
+                f.inverse(&mut data);
+                assert_eq!(data, src, "{:?}", f);
+            }
+        }
+        assert_eq!(Filter::from_byte(0x10), None);
+        assert_eq!(Filter::from_byte(0x19), None);
+        assert_eq!(Filter::from_byte(0x42), None);
+        assert_eq!(Filter::new(FilterKind::Delta, 0), None);
+        assert_eq!(Filter::new(FilterKind::Shuffle, 9), None);
+    }
+
+    #[test]
+    fn delta() {
+        let mut data = [10, 0, 12, 0, 11, 0, 0xff, 0xff];
+        filter(FilterKind::Delta, 2).forward(&mut data);
+        // 10, +2, -1, -12 (0xffff - 11)
+        assert_eq!(data, [10, 0, 2, 0, 0xff, 0xff, 0xf4, 0xff]);
+    }
+
+    #[test]
+    fn chain() {
+        let src = samples();
+        let chain =
+            FilterChain::new(&[filter(FilterKind::Delta, 2), filter(FilterKind::Shuffle, 2)])
+                .unwrap();
+        assert_eq!(chain.len(), 2);
+        let filtered = chain.apply(&src);
+        assert!(
+            compressed_len(&filtered) < compressed_len(&src) / 2,
+            "{} {}",
+            compressed_len(&filtered),
+            compressed_len(&src)
+        );
+        let mut data = filtered;
+        chain.inverse(&mut data);
+        assert_eq!(data, src);
+
+        assert!(FilterChain::new(&[filter(FilterKind::Xor, 1); 5]).is_none());
+        assert!(FilterChain::default().is_empty());
+    }
+}
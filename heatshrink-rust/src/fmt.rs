@@ -0,0 +1,193 @@
+use core::fmt::{self, Write};
+
+use crate::decoder::HeatshrinkDecoder;
+use crate::CompressedData;
+
+// сколько байт распаковывается за раз на стеке
+const CHUNK_SIZE: usize = 64;
+// максимальный хвост незаконченного utf-8 символа, переносимый в следующий кусок
+const MAX_UTF8_TAIL: usize = 3;
+
+/// Сжатая строка, которая выводится через `Display` без аллокаций:
+/// распаковывается кусками по `CHUNK_SIZE` байт, utf-8 проверяется на лету,
+/// символ, разрезанный границей куска, переносится в следующий кусок.
+/// Некорректные последовательности заменяются на U+FFFD, как в `String::from_utf8_lossy`.
+/// Ширина, заполнение и точность (`{:>20}`, `{:-^n}`, `{:.5}`) работают как у `str`,
+/// но строка тогда распаковывается дважды: первый проход считает символы.
+///
+/// ```ignore
+/// static HELLO: CompressedStr = CompressedStr::new(packed_string!("Привет!"));
+/// writeln!(uart, "{}", HELLO)?;
+/// ```
+pub struct CompressedStr<'a>(CompressedData<'a>);
+
+impl<'a> CompressedStr<'a> {
+    pub const fn new(data: CompressedData<'a>) -> Self {
+        Self(data)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.original_size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.original_size == 0
+    }
+}
+
+impl<'a> From<CompressedData<'a>> for CompressedStr<'a> {
+    fn from(data: CompressedData<'a>) -> Self {
+        Self(data)
+    }
+}
+
+impl<'a> CompressedStr<'a> {
+    // распакованная строка кусками в out
+    fn chunks(&self, mut out: impl FnMut(&str) -> fmt::Result) -> fmt::Result {
+        let mut decoder = HeatshrinkDecoder::source(self.0.data.iter().cloned());
+        let mut buf = [0u8; CHUNK_SIZE + MAX_UTF8_TAIL];
+        let mut tail = 0;
+
+        loop {
+            let mut len = tail;
+            for b in decoder.by_ref().take(CHUNK_SIZE) {
+                buf[len] = b;
+                len += 1;
+            }
+            let finished = len - tail < CHUNK_SIZE;
+
+            let mut chunk = &buf[..len];
+            tail = 0;
+            while !chunk.is_empty() {
+                match core::str::from_utf8(chunk) {
+                    Ok(s) => {
+                        out(s)?;
+                        break;
+                    }
+                    Err(e) => {
+                        let (valid, rest) = chunk.split_at(e.valid_up_to());
+                        out(unsafe { core::str::from_utf8_unchecked(valid) })?;
+                        match e.error_len() {
+                            Some(invalid) => {
+                                out("\u{FFFD}")?;
+                                chunk = &rest[invalid..];
+                            }
+                            // символ не закончен, но данные еще будут
+                            None if !finished => {
+                                tail = rest.len();
+                                break;
+                            }
+                            None => {
+                                out("\u{FFFD}")?;
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+
+            if finished {
+                return Ok(());
+            }
+            buf.copy_within(len - tail..len, 0);
+        }
+    }
+}
+
+impl<'a> fmt::Display for CompressedStr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.width().is_none() && f.precision().is_none() {
+            return self.chunks(|s| f.write_str(s));
+        }
+        let mut chars = 0;
+        self.chunks(|s| {
+            chars += s.chars().count();
+            Ok(())
+        })?;
+        let shown = f.precision().map_or(chars, |p| p.min(chars));
+        let pad = f.width().map_or(0, |w| w.saturating_sub(shown));
+        let (before, after) = match f.align() {
+            Some(fmt::Alignment::Right) => (pad, 0),
+            Some(fmt::Alignment::Center) => (pad / 2, pad - pad / 2),
+            Some(fmt::Alignment::Left) | None => (0, pad),
+        };
+        let fill = f.fill();
+        for _ in 0..before {
+            f.write_char(fill)?;
+        }
+        let mut left = shown;
+        self.chunks(|s| {
+            let end = s.char_indices().nth(left).map_or(s.len(), |(i, _)| i);
+            left -= s[..end].chars().count();
+            f.write_str(&s[..end])
+        })?;
+        for _ in 0..after {
+            f.write_char(fill)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    use crate::encoder::HeatshrinkEncoder;
+    use crate::fmt::CompressedStr;
+    use crate::CompressedData;
+
+    fn compress(src: &[u8]) -> Vec<u8> {
+        HeatshrinkEncoder::source(src.iter().cloned()).collect()
+    }
+
+    #[test]
+    fn display_multibyte() {
+        // 2, 3 и 4-байтные символы обязательно попадут на границы кусков
+        let src = "Ёжик 🦔 в тумане — ёжик в тумане. ".repeat(20);
+        let packed = compress(src.as_bytes());
+
+        let s = CompressedStr::new(CompressedData {
+            data: &packed,
+            original_size: src.len(),
+        });
+        assert_eq!(s.len(), src.len());
+        assert_eq!(s.to_string(), src);
+        assert_eq!(format!("[{}]", s), format!("[{}]", src));
+    }
+
+    #[test]
+    fn display_invalid_utf8() {
+        let mut src = "ok ".repeat(30).into_bytes();
+        src.extend_from_slice(&[0xff, b'!', 0xd0]);
+        let packed = compress(&src);
+
+        let s = CompressedStr::new(CompressedData {
+            data: &packed,
+            original_size: src.len(),
+        });
+        assert_eq!(s.to_string(), std::string::String::from_utf8_lossy(&src));
+    }
+
+    #[test]
+    fn display_padding() {
+        let src = "Ёжик 🦔";
+        let packed = compress(src.as_bytes());
+
+        let s = CompressedStr::new(CompressedData {
+            data: &packed,
+            original_size: src.len(),
+        });
+        for (res, expected) in [
+            (format!("{:>10}", s), format!("{:>10}", src)),
+            (format!("{:-^11}", s), format!("{:-^11}", src)),
+            (format!("{:<9}|", s), format!("{:<9}|", src)),
+            (format!("{:.3}", s), format!("{:.3}", src)),
+            (format!("{:*>8.5}", s), format!("{:*>8.5}", src)),
+            (format!("{:3}", s), format!("{:3}", src)),
+        ] {
+            assert_eq!(res, expected);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Поток с точками flush: сжатые данные режутся на сегменты по точкам flush, и приемник
+//! находит их в самом потоке, без договоренностей на уровне транспорта.
+//!
+//! ```text
+//! поток: сегмент*
+//! сегмент: varint(n) | n байт сжатых данных, выровненных до байта
+//! ```
+//! varint - как в [`crate::record_stream`], не больше 5 байт и не больше u32. Окно общее
+//! на весь поток: сегмент продолжает предыдущий, ссылки назад через границу допустимы.
+//! Биты дополнения в последнем байте сегмента декодер выбрасывает, следующий токен - с нового
+//! байта. Последний сегмент закрывает finish() кодера, без точек flush поток - один сегмент.
+//! Пустых сегментов кодер не пишет, декодер их пропускает.
+//!
+//! Кодеры: [`crate::encoder_to_vec::HeatshrinkEncoderToVec::framed`], `io::EncoderWriter::framed`.
+//! Декодеры: [`crate::decoder::HeatshrinkDecoder::framed`], `io::DecoderWriter::framed`,
+//! `heatshrink -d -F`
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::decoder::Error;
+use crate::record_stream::{write_varint, VARINT_MAX};
+
+/// Заголовок сегмента длиной `len`. None - длиннее u32::MAX
+pub(crate) fn segment_header(len: usize) -> Option<Vec<u8>> {
+    let mut header = Vec::with_capacity(VARINT_MAX);
+    write_varint(&mut header, u32::try_from(len).ok()?);
+    Some(header)
+}
+
+/// Разбор заголовков сегментов на стороне декодера
+#[derive(Default)]
+pub(crate) struct Segments {
+    // недочитанный заголовок
+    header: u64,
+    header_len: usize,
+    // осталось байт данных текущего сегмента
+    remaining: usize,
+}
+
+impl Segments {
+    /// Сколько байт сжатых данных текущего сегмента еще впереди, 0 - дальше заголовок
+    pub(crate) fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// `n` байт данных сегмента переданы декодеру
+    pub(crate) fn consume(&mut self, n: usize) {
+        self.remaining -= n;
+    }
+
+    /// Очередной байт заголовка. true - заголовок закончен, начался новый сегмент:
+    /// декодер должен выбросить дополнение предыдущего
+    pub(crate) fn header_byte(&mut self, b: u8) -> Result<bool, Error> {
+        self.header |= ((b & 0x7f) as u64) << (7 * self.header_len);
+        self.header_len += 1;
+        if b & 0x80 != 0 {
+            return if self.header_len == VARINT_MAX {
+                Err(Error::BadSegment)
+            } else {
+                Ok(false)
+            };
+        }
+        let len = u32::try_from(self.header).map_err(|_| Error::BadSegment)?;
+        self.remaining = len as usize;
+        self.header = 0;
+        self.header_len = 0;
+        Ok(true)
+    }
+
+    /// Поток можно закончить здесь: между сегментами
+    pub(crate) fn at_boundary(&self) -> bool {
+        self.remaining == 0 && self.header_len == 0
+    }
+}
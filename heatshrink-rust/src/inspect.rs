@@ -0,0 +1,370 @@
+//! Разбор сжатого потока на токены без распаковки - для отладки битых потоков.
+//!
+//! Формат heatshrink: каждый токен начинается с бита-тега,
+//! `1` + 8 бит - литерал, `0` + `window_bits` бит (offset - 1) + `lookahead_bits` бит (length - 1) -
+//! ссылка назад. Биты идут от старшего к младшему, хвост последнего байта дополнен нулями.
+
+use core::fmt;
+
+use crate::valid_params;
+
+/// Чтение потока по битам, от старшего к младшему
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Текущая позиция в битах от начала потока
+    pub(crate) fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+
+    /// `count` <= 16 бит, None если столько не осталось (позиция не сдвигается)
+    pub(crate) fn read(&mut self, count: u8) -> Option<u16> {
+        if self.remaining() < count as usize {
+            return None;
+        }
+        let mut res = 0u16;
+        for _ in 0..count {
+            let bit = (self.data[self.pos / 8] >> (7 - self.pos % 8)) & 1;
+            res = (res << 1) | bit as u16;
+            self.pos += 1;
+        }
+        Some(res)
+    }
+}
+
+/// Побайтный разбор потока: для потоковой обработки, когда данных целиком нет
+pub(crate) struct StreamParser {
+    window_bits: u8,
+    lookahead_bits: u8,
+    // еще не разобранные биты, младшие `bits` бит; до 1 + 15 + 14 - 1 + 8
+    acc: u64,
+    bits: u8,
+}
+
+impl StreamParser {
+    pub(crate) const fn new(window_bits: u8, lookahead_bits: u8) -> Self {
+        Self {
+            window_bits,
+            lookahead_bits,
+            acc: 0,
+            bits: 0,
+        }
+    }
+
+    /// Добавить байт и вызвать `f` для каждого завершенного им токена
+    pub(crate) fn push(&mut self, byte: u8, mut f: impl FnMut(TokenKind)) {
+        self.acc = (self.acc << 8) | byte as u64;
+        self.bits += 8;
+
+        // неполный токен ждет следующего байта
+        while self.bits > 0 {
+            let literal = (self.acc >> (self.bits - 1)) & 1 == 1;
+            let need = if literal {
+                1 + 8
+            } else {
+                1 + self.window_bits + self.lookahead_bits
+            };
+            if self.bits < need {
+                return;
+            }
+            self.take(1);
+            if literal {
+                f(TokenKind::Literal(self.take(8) as u8));
+            } else {
+                let offset = self.take(self.window_bits) as usize + 1;
+                let length = self.take(self.lookahead_bits) as usize + 1;
+                f(TokenKind::BackRef { offset, length });
+            }
+        }
+    }
+
+    fn take(&mut self, count: u8) -> u32 {
+        self.bits -= count;
+        ((self.acc >> self.bits) & ((1 << count) - 1)) as u32
+    }
+
+    /// Биты после последнего целого токена: в конце потока - дополнение последнего байта,
+    /// меньше 8 бит и все нули
+    pub(crate) fn trailing(&self) -> (u8, u32) {
+        (self.bits, (self.acc & ((1 << self.bits) - 1)) as u32)
+    }
+
+    /// Точка sync flush: остаток - дополнение байта, следующий токен с нового байта
+    pub(crate) fn sync(&mut self) {
+        self.acc = 0;
+        self.bits = 0;
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TokenKind {
+    Literal(u8),
+    /// Скопировать `length` байт, начиная с `offset` байт назад от текущей позиции
+    BackRef {
+        offset: usize,
+        length: usize,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Позиция бита-тега в сжатом потоке
+    pub bit_pos: usize,
+    /// Позиция в распакованных данных, куда попадет первый байт токена
+    pub out_pos: usize,
+}
+
+impl Token {
+    /// Сколько байт токен добавит в распакованные данные
+    pub fn len(&self) -> usize {
+        match self.kind {
+            TokenKind::Literal(_) => 1,
+            TokenKind::BackRef { length, .. } => length,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Ссылка указывает раньше начала данных: декодер скопирует содержимое
+    /// не заполненного окна (нули у heatshrink_decoder_reset())
+    pub fn before_start(&self) -> bool {
+        match self.kind {
+            TokenKind::Literal(_) => false,
+            TokenKind::BackRef { offset, .. } => offset > self.out_pos,
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bit {:>8}  out {:>8}  ", self.bit_pos, self.out_pos)?;
+        match self.kind {
+            TokenKind::Literal(b) if b.is_ascii_graphic() || b == b' ' => {
+                write!(f, "literal  0x{:02x} '{}'", b, b as char)
+            }
+            TokenKind::Literal(b) => write!(f, "literal  0x{:02x}", b),
+            TokenKind::BackRef { offset, length } => {
+                write!(f, "backref  offset {:>5} length {:>5}", offset, length)?;
+                if self.before_start() {
+                    f.write_str("  <-- before start of output")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct Summary {
+    pub literals: usize,
+    pub backrefs: usize,
+    /// Ссылки раньше начала данных, см. [`Token::before_start`]
+    pub before_start: usize,
+    /// Размер распакованных данных
+    pub output_size: usize,
+    /// Биты после последнего целого токена (дополнение последнего байта или обрыв потока)
+    pub trailing_bits: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "literals: {}, backrefs: {} ({} before start), output: {} bytes, trailing bits: {}",
+            self.literals, self.backrefs, self.before_start, self.output_size, self.trailing_bits
+        )
+    }
+}
+
+/// Итератор токенов сжатого потока, см. [`tokens`]
+pub struct Tokens<'a> {
+    reader: BitReader<'a>,
+    window_bits: u8,
+    lookahead_bits: u8,
+    summary: Summary,
+}
+
+/// Разобрать поток, сжатый с параметрами `window_bits` / `lookahead_bits`.
+/// None если параметры недопустимы
+pub fn tokens(data: &[u8], window_bits: u8, lookahead_bits: u8) -> Option<Tokens<'_>> {
+    if !valid_params(window_bits, lookahead_bits) {
+        return None;
+    }
+    Some(Tokens {
+        reader: BitReader::new(data),
+        window_bits,
+        lookahead_bits,
+        summary: Summary::default(),
+    })
+}
+
+impl<'a> Tokens<'a> {
+    /// Статистика по уже выданным токенам, `trailing_bits` - после окончания итерации
+    pub fn summary(&self) -> Summary {
+        self.summary
+    }
+
+    fn read_token(&mut self) -> Option<TokenKind> {
+        if self.reader.read(1)? == 1 {
+            Some(TokenKind::Literal(self.reader.read(8)? as u8))
+        } else {
+            let offset = self.reader.read(self.window_bits)? as usize + 1;
+            let length = self.reader.read(self.lookahead_bits)? as usize + 1;
+            Some(TokenKind::BackRef { offset, length })
+        }
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bit_pos = self.reader.pos();
+        let kind = match self.read_token() {
+            Some(kind) => kind,
+            None => {
+                // неполный токен декодер отбрасывает так же
+                self.summary.trailing_bits = self.reader.remaining() + self.reader.pos() - bit_pos;
+                self.reader = BitReader::new(&[]);
+                return None;
+            }
+        };
+
+        let token = Token {
+            kind,
+            bit_pos,
+            out_pos: self.summary.output_size,
+        };
+        match kind {
+            TokenKind::Literal(_) => self.summary.literals += 1,
+            TokenKind::BackRef { .. } => {
+                self.summary.backrefs += 1;
+                if token.before_start() {
+                    self.summary.before_start += 1;
+                }
+            }
+        }
+        self.summary.output_size += token.len();
+        Some(token)
+    }
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::decoder::HeatshrinkDecoder;
+    use crate::encoder::HeatshrinkEncoder;
+    use crate::inspect::{tokens, BitReader, Summary, TokenKind};
+    use crate::{STATIC_LOOKAHEAD_BITS, STATIC_WINDOW_BITS};
+
+    #[test]
+    fn bit_reader() {
+        let mut reader = BitReader::new(&[0b1010_0000, 0xff]);
+        assert_eq!(reader.read(1), Some(1));
+        assert_eq!(reader.read(3), Some(0b010));
+        assert_eq!(reader.read(8), Some(0b0000_1111));
+        assert_eq!(reader.read(5), None);
+        assert_eq!(reader.pos(), 12);
+        assert_eq!(reader.read(4), Some(0b1111));
+        assert_eq!(reader.remaining(), 0);
+    }
+
+    #[test]
+    fn zeros() {
+        // 8 нулей: одна ссылка на 1 байт назад, в еще пустое окно
+        let mut tokens = tokens(&[0x00, 0x38], 8, 4).unwrap();
+
+        let token = tokens.next().unwrap();
+        assert_eq!(
+            token.kind,
+            TokenKind::BackRef {
+                offset: 1,
+                length: 8
+            }
+        );
+        assert!(token.before_start());
+        assert_eq!(tokens.next(), None);
+        assert_eq!(
+            tokens.summary(),
+            Summary {
+                literals: 0,
+                backrefs: 1,
+                before_start: 1,
+                output_size: 8,
+                trailing_bits: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn literals() {
+        let packed = HeatshrinkEncoder::source(b"abc".iter().cloned()).collect::<Vec<_>>();
+        let found = tokens(&packed, 8, 4)
+            .unwrap()
+            .map(|t| (t.kind, t.bit_pos, t.out_pos))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            found,
+            [
+                (TokenKind::Literal(b'a'), 0, 0),
+                (TokenKind::Literal(b'b'), 9, 1),
+                (TokenKind::Literal(b'c'), 18, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn output_size_matches_decoder() {
+        let src = (0..2000)
+            .map(|n| (n % 37) as u8 ^ (n / 100) as u8)
+            .collect::<Vec<_>>();
+        let packed = HeatshrinkEncoder::source(src.iter().cloned()).collect::<Vec<_>>();
+
+        let mut tokens = tokens(&packed, STATIC_WINDOW_BITS, STATIC_LOOKAHEAD_BITS).unwrap();
+        let mut out = Vec::new();
+        for token in tokens.by_ref() {
+            assert_eq!(token.out_pos, out.len());
+            match token.kind {
+                TokenKind::Literal(b) => out.push(b),
+                TokenKind::BackRef { offset, length } => {
+                    assert!(!token.before_start());
+                    for _ in 0..length {
+                        out.push(out[out.len() - offset]);
+                    }
+                }
+            }
+        }
+        let summary = tokens.summary();
+
+        assert_eq!(out, src);
+        assert_eq!(summary.output_size, src.len());
+        assert!(summary.trailing_bits < 8);
+        assert_eq!(
+            HeatshrinkDecoder::source(packed.into_iter()).count(),
+            summary.output_size
+        );
+    }
+
+    #[test]
+    fn invalid_params() {
+        assert!(tokens(&[], 3, 3).is_none());
+        assert!(tokens(&[], 8, 8).is_none());
+    }
+}
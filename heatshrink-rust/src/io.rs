@@ -0,0 +1,776 @@
+//! Адаптеры `std::io::Write` поверх [`crate::dynamic`]: сжатие/распаковка потока
+//! в любой `Write` (файл, stdout, сокет).
+
+use std::io::{self, Write};
+use std::vec;
+use std::vec::Vec;
+
+use crate::decoder::{Error, StrictCheck};
+use crate::dynamic::{DynamicDecoder, DynamicEncoder};
+use crate::framing::{segment_header, Segments};
+use crate::{dictionary, optimal, Level};
+
+const OUT_BUF_SIZE: usize = 4096;
+
+fn invalid_params(window_bits: u8, lookahead_bits: u8) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "invalid heatshrink parameters: -w {} -l {}",
+            window_bits, lookahead_bits
+        ),
+    )
+}
+
+fn invalid_data(e: Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn finished() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::BrokenPipe,
+        "heatshrink stream is already finished",
+    )
+}
+
+/// Все записанное сжимается и пишется в `inner`, в конце обязательно [`EncoderWriter::finish`]
+pub struct EncoderWriter<W: Write> {
+    encoder: DynamicEncoder,
+    inner: W,
+    buf: Vec<u8>,
+    total_in: u64,
+    total_out: u64,
+    // после try_finish поток закрыт, новые данные уже некуда дописать
+    finished: bool,
+    // framed(): сжатые данные с прошлой точки flush, длина станет известна в ней
+    segment: Option<Vec<u8>>,
+    // Level::Best: окно (первые history байт) и вход с прошлой точки flush
+    best: Option<Vec<u8>>,
+    history: usize,
+}
+
+impl<W: Write> EncoderWriter<W> {
+    pub fn new(inner: W, window_bits: u8, lookahead_bits: u8) -> io::Result<Self> {
+        Self::with_level(inner, window_bits, lookahead_bits, Level::Default)
+    }
+
+    /// Сжатие с уровнем `level`, см. [`Level`]. Best копит вход в памяти до точки flush
+    /// ([`EncoderWriter::framed`]) или finish и сжимает его [`crate::optimal`] целиком:
+    /// память - весь этот вход и еще около 9 байт на байт
+    pub fn with_level(
+        inner: W,
+        window_bits: u8,
+        lookahead_bits: u8,
+        level: Level,
+    ) -> io::Result<Self> {
+        // с Best кодер C не работает, нужен только самый маленький контекст
+        let encoder_level = if level == Level::Best {
+            Level::Fast
+        } else {
+            level
+        };
+        let encoder = DynamicEncoder::with_level(window_bits, lookahead_bits, encoder_level)
+            .ok_or_else(|| invalid_params(window_bits, lookahead_bits))?;
+        Ok(Self {
+            encoder,
+            inner,
+            buf: vec![0; OUT_BUF_SIZE],
+            total_in: 0,
+            total_out: 0,
+            finished: false,
+            segment: None,
+            best: if level == Level::Best {
+                Some(Vec::new())
+            } else {
+                None
+            },
+            history: 0,
+        })
+    }
+
+    /// Окно заранее заполнено словарем ([`crate::dictionary`]), только до первой записи
+    pub fn with_dictionary(mut self, dict: &[u8]) -> Self {
+        match &mut self.best {
+            Some(best) => {
+                best.extend_from_slice(dictionary::tail(dict, self.encoder.window_bits()));
+                self.history = best.len();
+            }
+            None => self.encoder.set_dictionary(dict),
+        }
+        self
+    }
+
+    /// Поток с точками flush ([`crate::framing`]): [`Write::flush`] ставит точку flush,
+    /// декодер ([`DecoderWriter::framed`]) находит ее сам. Сжатые данные копятся в памяти
+    /// до точки flush или finish, в `inner` уходят сегментом целиком. Только до первой записи
+    pub fn framed(mut self) -> Self {
+        self.segment = Some(Vec::new());
+        self
+    }
+
+    /// Байт принято на сжатие
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    /// Байт сжатых данных отдано в `inner`
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    // выгрузить все, что упаковщик готов отдать
+    fn drain(&mut self) -> io::Result<()> {
+        loop {
+            let (n, more) = self.encoder.poll(&mut self.buf);
+            match &mut self.segment {
+                Some(segment) => segment.extend_from_slice(&self.buf[..n]),
+                None => {
+                    self.inner.write_all(&self.buf[..n])?;
+                    self.total_out += n as u64;
+                }
+            }
+            if !more {
+                return Ok(());
+            }
+        }
+    }
+
+    // все принятое выдать с выравниванием до байта, framed() - сегментом в inner
+    fn end_segment(&mut self) -> io::Result<()> {
+        match &mut self.best {
+            Some(best) => {
+                let (window_bits, lookahead_bits) =
+                    (self.encoder.window_bits(), self.encoder.lookahead_bits());
+                let (history, input) = best.split_at(self.history);
+                let packed =
+                    optimal::compress_with_dictionary(input, history, window_bits, lookahead_bits)
+                        .unwrap();
+                // окно следующего сегмента - хвост всего записанного
+                let keep = best.len().min(1 << window_bits);
+                best.drain(..best.len() - keep);
+                self.history = keep;
+                match &mut self.segment {
+                    Some(segment) => segment.extend_from_slice(&packed),
+                    None => {
+                        self.inner.write_all(&packed)?;
+                        self.total_out += packed.len() as u64;
+                    }
+                }
+            }
+            None => {
+                while !self.encoder.finish() {
+                    self.drain()?;
+                }
+            }
+        }
+        if let Some(segment) = &mut self.segment {
+            if !segment.is_empty() {
+                let header = segment_header(segment.len()).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "flush segment over 4 GiB")
+                })?;
+                self.inner.write_all(&header)?;
+                self.inner.write_all(segment)?;
+                self.total_out += (header.len() + segment.len()) as u64;
+                segment.clear();
+            }
+        }
+        Ok(())
+    }
+
+    /// Завершить поток: дописать хвост, после этого `write` возвращает ошибку
+    pub fn try_finish(&mut self) -> io::Result<()> {
+        if !self.finished {
+            self.end_segment()?;
+            self.finished = true;
+        }
+        self.inner.flush()
+    }
+
+    /// [`EncoderWriter::try_finish`] и вернуть `inner`
+    pub fn finish(mut self) -> io::Result<W> {
+        self.try_finish()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncoderWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.finished {
+            return Err(finished());
+        }
+        if data.is_empty() {
+            return Ok(0);
+        }
+        if let Some(best) = &mut self.best {
+            best.extend_from_slice(data);
+            self.total_in += data.len() as u64;
+            return Ok(data.len());
+        }
+        loop {
+            let writen = self.encoder.sink(data);
+            self.drain()?;
+            if writen > 0 {
+                self.total_in += writen as u64;
+                return Ok(writen);
+            }
+        }
+    }
+
+    /// Точка flush, только в потоке [`EncoderWriter::framed`]: все записанное сжимается
+    /// и выравнивается до байта, декодер framed распаковывает его целиком. Окно сохраняется,
+    /// следующие данные по-прежнему ссылаются на предыдущие. Каждая точка стоит до байта
+    /// дополнения и заголовок сегмента.
+    /// В обычном потоке хвост входа остается в кодере до finish(), а точку выравнивания
+    /// посередине обычный декодер не прочтет: там flush() возвращает `Unsupported` и ничего
+    /// не делает. После finish - только `inner.flush()`
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.finished {
+            if self.segment.is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "flush needs a framed heatshrink stream",
+                ));
+            }
+            self.end_segment()?;
+            if self.best.is_none() {
+                self.encoder.resume();
+            }
+        }
+        self.inner.flush()
+    }
+}
+
+/// Все записанное распаковывается и пишется в `inner`, в конце обязательно [`DecoderWriter::finish`]
+pub struct DecoderWriter<W: Write> {
+    decoder: DynamicDecoder,
+    inner: W,
+    buf: Vec<u8>,
+    total_in: u64,
+    total_out: u64,
+    limit: usize,
+    strict: Option<StrictCheck>,
+    // байт словаря в окне
+    history: usize,
+    // поток с точками flush, см. framed()
+    segments: Option<Segments>,
+}
+
+impl<W: Write> DecoderWriter<W> {
+    pub fn new(inner: W, window_bits: u8, lookahead_bits: u8) -> io::Result<Self> {
+        let decoder = DynamicDecoder::new(window_bits, lookahead_bits)
+            .ok_or_else(|| invalid_params(window_bits, lookahead_bits))?;
+        Ok(Self {
+            decoder,
+            inner,
+            buf: vec![0; OUT_BUF_SIZE],
+            total_in: 0,
+            total_out: 0,
+            limit: usize::MAX,
+            strict: None,
+            history: 0,
+            segments: None,
+        })
+    }
+
+    /// Не больше `max_output` байт распакованных данных: дальше `write` возвращает
+    /// `InvalidData` с [`Error::OutputLimit`] внутри, в `inner` попадает ровно `max_output` байт
+    pub fn with_limit(mut self, max_output: usize) -> Self {
+        self.limit = max_output;
+        self
+    }
+
+    /// Окно заранее заполнено тем же словарем, что у кодера, только до первой записи
+    pub fn with_dictionary(mut self, dict: &[u8]) -> Self {
+        self.decoder.set_dictionary(dict);
+        self.history = dict.len().min(1 << self.decoder.window_bits());
+        if let Some(strict) = self.strict.take() {
+            self.strict = Some(strict.with_history(self.history));
+        }
+        self
+    }
+
+    /// Строгий режим, см. [`crate::decoder::HeatshrinkDecoder::strict`]: ошибки -
+    /// `InvalidData` с [`Error::BeforeStart`] / [`Error::Padding`] внутри
+    pub fn strict(mut self) -> Self {
+        self.strict = Some(
+            StrictCheck::new(self.decoder.window_bits(), self.decoder.lookahead_bits())
+                .with_history(self.history),
+        );
+        self
+    }
+
+    /// Поток с точками flush ([`crate::framing`]) от [`EncoderWriter::framed`]: в конце
+    /// каждого сегмента все записанное уже распаковано и отдано в `inner`, дополнение
+    /// отбрасывается. Битый заголовок сегмента - `InvalidData` с [`Error::BadSegment`] внутри
+    pub fn framed(mut self) -> Self {
+        self.segments = Some(Segments::default());
+        self
+    }
+
+    /// Байт сжатых данных принято
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    /// Байт распакованных данных отдано в `inner`
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    fn drain(&mut self) -> io::Result<()> {
+        loop {
+            let (n, more) = self.decoder.poll(&mut self.buf);
+            let allowed = (self.limit as u64 - self.total_out).min(n as u64) as usize;
+            self.inner.write_all(&self.buf[..allowed])?;
+            self.total_out += allowed as u64;
+            if allowed < n {
+                return Err(invalid_data(Error::OutputLimit { limit: self.limit }));
+            }
+            if !more {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Проверить, что поток закончился целиком, и сбросить `inner`
+    pub fn try_finish(&mut self) -> io::Result<()> {
+        self.drain()?;
+        let in_segment = matches!(&self.segments, Some(s) if !s.at_boundary());
+        if in_segment || !self.decoder.finish() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated heatshrink stream",
+            ));
+        }
+        if let Some(strict) = &self.strict {
+            strict.finish().map_err(invalid_data)?;
+        }
+        self.inner.flush()
+    }
+
+    // конец сегмента framed(): дополнение отбрасывается, окно сохраняется
+    fn sync(&mut self) -> io::Result<()> {
+        self.drain()?;
+        if !self.decoder.sync() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "heatshrink stream is not at flush point",
+            ));
+        }
+        if let Some(strict) = &mut self.strict {
+            strict.sync().map_err(invalid_data)?;
+        }
+        self.inner.flush()
+    }
+
+    // без разбора сегментов
+    fn sink(&mut self, data: &[u8]) -> io::Result<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let writen = self.decoder.sink(data);
+            // проверить принятое, пока декодер его не распаковал
+            if let Some(strict) = &mut self.strict {
+                for b in &data[..writen] {
+                    strict.push(*b).map_err(invalid_data)?;
+                }
+            }
+            self.drain()?;
+            if writen > 0 {
+                self.total_in += writen as u64;
+                return Ok(writen);
+            }
+        }
+    }
+
+    /// [`DecoderWriter::try_finish`] и вернуть `inner`
+    pub fn finish(mut self) -> io::Result<W> {
+        self.try_finish()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for DecoderWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let segments = match &mut self.segments {
+            Some(segments) => segments,
+            None => return self.sink(data),
+        };
+        let mut header = 0;
+        while segments.remaining() == 0 && header < data.len() {
+            segments.header_byte(data[header]).map_err(invalid_data)?;
+            header += 1;
+        }
+        let len = segments.remaining().min(data.len() - header);
+        self.total_in += header as u64;
+        let writen = self.sink(&data[header..header + len])?;
+        if let Some(segments) = &mut self.segments {
+            segments.consume(writen);
+            if writen > 0 && segments.remaining() == 0 {
+                self.sync()?;
+            }
+        }
+        Ok(header + writen)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.drain()?;
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::vec::Vec;
+
+    use crate::decoder::{Error, HeatshrinkDecoder};
+    use crate::io::{DecoderWriter, EncoderWriter};
+
+    #[test]
+    fn encode_decode() {
+        let src = (0..100_000u32)
+            .flat_map(|n| (n / 10).to_le_bytes())
+            .collect::<Vec<_>>();
+
+        let mut encoder = EncoderWriter::new(Vec::new(), 11, 4).unwrap();
+        for chunk in src.chunks(1000) {
+            encoder.write_all(chunk).unwrap();
+        }
+        assert_eq!(encoder.total_in(), src.len() as u64);
+        encoder.try_finish().unwrap();
+        let total_out = encoder.total_out();
+        let encoded = encoder.finish().unwrap();
+        assert_eq!(encoded.len() as u64, total_out);
+        assert!(encoded.len() < src.len() / 4);
+
+        let mut decoder = DecoderWriter::new(Vec::new(), 11, 4).unwrap();
+        decoder.write_all(&encoded).unwrap();
+        assert_eq!(decoder.total_in(), encoded.len() as u64);
+        assert_eq!(decoder.total_out(), src.len() as u64);
+        assert_eq!(decoder.finish().unwrap(), src);
+    }
+
+    #[test]
+    fn write_after_finish() {
+        let mut encoder = EncoderWriter::new(Vec::new(), 8, 4).unwrap();
+        encoder.write_all(b"abcabcabc").unwrap();
+        encoder.try_finish().unwrap();
+        let len = encoder.get_ref().len();
+
+        let err = encoder.write(b"abc").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+        encoder.flush().unwrap();
+        // повторный finish ничего не дописывает
+        encoder.try_finish().unwrap();
+        assert_eq!(encoder.total_in(), 9);
+        assert_eq!(encoder.finish().unwrap().len(), len);
+    }
+
+    #[test]
+    fn compress_bound_any_params() {
+        use crate::compress_bound;
+        use rand::Rng;
+
+        let mut rng = rand::thread_rng();
+        for (window_bits, lookahead_bits) in [(4, 3), (8, 4), (11, 4), (15, 14)] {
+            for len in [0, 1, 9, 100, 3000] {
+                let src = (0..len).map(|_| rng.gen_range(0..4u8)).collect::<Vec<_>>();
+                let mut encoder =
+                    EncoderWriter::new(Vec::new(), window_bits, lookahead_bits).unwrap();
+                encoder.write_all(&src).unwrap();
+                let encoded = encoder.finish().unwrap();
+                assert!(
+                    encoded.len() <= compress_bound(len, window_bits, lookahead_bits),
+                    "-w {}",
+                    window_bits
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn compatible_with_static() {
+        let src = b"abcabcabcabc - compatible with the static build - abcabcabc".repeat(10);
+
+        let mut encoder = EncoderWriter::new(Vec::new(), 8, 4).unwrap();
+        encoder.write_all(&src).unwrap();
+        let encoded = encoder.finish().unwrap();
+
+        let decoded = HeatshrinkDecoder::source(encoded.into_iter()).collect::<Vec<_>>();
+        assert_eq!(decoded, src);
+    }
+
+    #[test]
+    fn decode_limit() {
+        // ~10 КиБ сжатых нулей -> 100 КиБ
+        let mut encoder = EncoderWriter::new(Vec::new(), 8, 4).unwrap();
+        encoder.write_all(&[0u8; 100_000]).unwrap();
+        let bomb = encoder.finish().unwrap();
+
+        let mut decoder = DecoderWriter::new(Vec::new(), 8, 4)
+            .unwrap()
+            .with_limit(10_000);
+        let err = decoder.write_all(&bomb).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref::<Error>(),
+            Some(&Error::OutputLimit { limit: 10_000 })
+        );
+        assert_eq!(decoder.total_out(), 10_000);
+        assert_eq!(decoder.get_ref().len(), 10_000);
+
+        let mut decoder = DecoderWriter::new(Vec::new(), 8, 4)
+            .unwrap()
+            .with_limit(100_000);
+        decoder.write_all(&bomb).unwrap();
+        assert_eq!(decoder.finish().unwrap().len(), 100_000);
+    }
+
+    #[test]
+    fn decode_strict() {
+        let src = b"strict strict strict strict".repeat(20);
+        let mut encoder = EncoderWriter::new(Vec::new(), 10, 5).unwrap();
+        encoder.write_all(&src).unwrap();
+        let encoded = encoder.finish().unwrap();
+
+        let mut decoder = DecoderWriter::new(Vec::new(), 10, 5).unwrap().strict();
+        decoder.write_all(&encoded).unwrap();
+        assert_eq!(decoder.finish().unwrap(), src);
+
+        let mut decoder = DecoderWriter::new(Vec::new(), 8, 4).unwrap().strict();
+        let err = decoder.write_all(&[0x00, 0x38]).unwrap_err();
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref::<Error>(),
+            Some(&Error::BeforeStart {
+                offset: 1,
+                out_pos: 0
+            })
+        );
+        assert!(decoder.get_ref().is_empty());
+
+        let mut decoder = DecoderWriter::new(Vec::new(), 8, 4).unwrap().strict();
+        decoder.write_all(&[0xb0, 0x81]).unwrap();
+        let err = decoder.finish().unwrap_err();
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref::<Error>(),
+            Some(&Error::Padding { trailing_bits: 7 })
+        );
+    }
+
+    #[test]
+    fn decode_strict_large_params() {
+        // незаконченная ссылка 1 + w + l бит и новый байт не влезают в 32 бита
+        let src = include_bytes!("decoder.rs");
+        for (window_bits, lookahead_bits) in [(14, 13), (15, 14)] {
+            let mut encoder = EncoderWriter::new(Vec::new(), window_bits, lookahead_bits).unwrap();
+            encoder.write_all(src).unwrap();
+            let encoded = encoder.finish().unwrap();
+
+            let mut decoder = DecoderWriter::new(Vec::new(), window_bits, lookahead_bits)
+                .unwrap()
+                .strict();
+            decoder.write_all(&encoded).unwrap();
+            assert_eq!(decoder.finish().unwrap(), &src[..]);
+        }
+    }
+
+    #[test]
+    fn flush_points() {
+        let messages = [
+            &b"temperature=21.5 humidity=40"[..],
+            b"temperature=21.5 humidity=41",
+            b"",
+            b"temperature=21.6 humidity=41",
+        ];
+
+        let mut encoder = EncoderWriter::new(Vec::new(), 11, 4).unwrap().framed();
+        let mut decoder = DecoderWriter::new(Vec::new(), 11, 4)
+            .unwrap()
+            .framed()
+            .strict();
+        let mut expected = Vec::new();
+        let mut sizes = Vec::new();
+        for message in messages {
+            let start = encoder.get_ref().len();
+            encoder.write_all(message).unwrap();
+            // до точки flush в inner ничего не уходит
+            assert_eq!(encoder.get_ref().len(), start);
+            encoder.flush().unwrap();
+            let frame = &encoder.get_ref()[start..];
+            sizes.push(frame.len());
+
+            // все до точки flush распаковывается сразу, границу декодер находит сам
+            decoder.write_all(frame).unwrap();
+            expected.extend_from_slice(message);
+            assert_eq!(decoder.get_ref(), &expected);
+        }
+        assert_eq!(sizes[2], 0);
+        // окно сохраняется, повторы ссылаются на предыдущие сообщения
+        assert!(sizes[1] < sizes[0] / 2, "{:?}", sizes);
+
+        let start = encoder.get_ref().len();
+        encoder.write_all(messages[0]).unwrap();
+        let encoded = encoder.finish().unwrap();
+        // весь поток целиком, по байту
+        let mut whole = DecoderWriter::new(Vec::new(), 11, 4).unwrap().framed();
+        for b in &encoded {
+            whole.write_all(&[*b]).unwrap();
+        }
+        assert_eq!(whole.total_in(), encoded.len() as u64);
+
+        // последний сегмент оборван
+        decoder
+            .write_all(&encoded[start..encoded.len() - 1])
+            .unwrap();
+        let err = decoder.try_finish().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        decoder.write_all(&encoded[encoded.len() - 1..]).unwrap();
+        expected.extend_from_slice(messages[0]);
+        assert_eq!(decoder.finish().unwrap(), expected);
+        assert_eq!(whole.finish().unwrap(), expected);
+
+        let mut decoder = DecoderWriter::new(Vec::new(), 11, 4).unwrap().framed();
+        let err = decoder.write_all(&[0xff; 6]).unwrap_err();
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref::<Error>(),
+            Some(&Error::BadSegment)
+        );
+    }
+
+    #[test]
+    fn flush_points_to_vec() {
+        use crate::encoder_to_vec::{HeatshrinkEncoderToVec, Result};
+
+        let mut encoder = HeatshrinkEncoderToVec::dest(Vec::with_capacity(1024), 0).framed();
+        let mut decoder = DecoderWriter::new(Vec::new(), 8, 4).unwrap().framed();
+        let mut expected = Vec::new();
+        let mut sent = 0;
+        for n in 0..20u32 {
+            let message = format!("event {} ok;", n % 3);
+            assert!(matches!(encoder.push_bytes(message.as_bytes()), Result::Ok));
+            assert!(matches!(encoder.flush(), Result::Ok));
+
+            decoder.write_all(&encoder.written()[sent..]).unwrap();
+            sent = encoder.written().len();
+            expected.extend_from_slice(message.as_bytes());
+            assert_eq!(decoder.get_ref(), &expected);
+        }
+        // после нескольких сообщений каждое - пара обратных ссылок и заголовок
+        assert!(sent < expected.len() / 2, "{} {}", sent, expected.len());
+
+        assert!(matches!(encoder.finish(), Result::Done));
+        // дополнение и заголовки сегментов не считаются токенами
+        #[cfg(feature = "stats")]
+        assert_eq!(encoder.stats().unwrap().input_bytes, expected.len());
+        decoder.write_all(&encoder.written()[sent..]).unwrap();
+        assert_eq!(decoder.finish().unwrap(), expected);
+
+        // тот же поток читает итератор
+        let packed = encoder.result();
+        let mut iter = HeatshrinkDecoder::source(packed.iter().copied()).framed();
+        assert_eq!(iter.by_ref().collect::<Vec<_>>(), expected);
+        assert_eq!(iter.error(), None);
+    }
+
+    #[test]
+    fn with_dictionary() {
+        let dict = (0..3000u32)
+            .flat_map(|n| format!("sensor={} value={};", n % 5, n % 11).into_bytes())
+            .collect::<Vec<_>>();
+        let src = b"sensor=3 value=7;sensor=4 value=10;";
+
+        let mut encoder = EncoderWriter::new(Vec::new(), 12, 5)
+            .unwrap()
+            .with_dictionary(&dict);
+        encoder.write_all(src).unwrap();
+        let encoded = encoder.finish().unwrap();
+        assert!(encoded.len() < src.len() / 3, "{}", encoded.len());
+
+        let mut decoder = DecoderWriter::new(Vec::new(), 12, 5)
+            .unwrap()
+            .strict()
+            .with_dictionary(&dict);
+        decoder.write_all(&encoded).unwrap();
+        assert_eq!(decoder.finish().unwrap(), src);
+    }
+
+    #[test]
+    fn levels() {
+        use crate::{optimal, Level};
+
+        let src = include_bytes!("io.rs");
+        let dict = &src[..2000];
+        let encode = |level, dict: Option<&[u8]>| {
+            let encoder = EncoderWriter::with_level(Vec::new(), 10, 5, level).unwrap();
+            let mut encoder = match dict {
+                Some(dict) => encoder.with_dictionary(dict),
+                None => encoder,
+            };
+            for chunk in src.chunks(1000) {
+                encoder.write_all(chunk).unwrap();
+                // в обычном потоке точек flush нет
+                let err = encoder.flush().unwrap_err();
+                assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+            }
+            assert_eq!(encoder.total_in(), src.len() as u64);
+            encoder.finish().unwrap()
+        };
+        for dict in [None, Some(dict)] {
+            let default = encode(Level::Default, dict);
+            assert_eq!(encode(Level::Fast, dict), default);
+            // Best - тот же optimal, только по частям
+            let best = encode(Level::Best, dict);
+            let expected = match dict {
+                Some(dict) => optimal::compress_with_dictionary(src, dict, 10, 5),
+                None => optimal::compress(src, 10, 5),
+            };
+            assert_eq!(Some(&best), expected.as_ref());
+            assert!(
+                best.len() < default.len(),
+                "{} {}",
+                best.len(),
+                default.len()
+            );
+        }
+
+        // с точками flush Best сжимает вход до каждой, окно общее на весь поток
+        let mut encoder = EncoderWriter::with_level(Vec::new(), 10, 5, Level::Best)
+            .unwrap()
+            .with_dictionary(dict)
+            .framed();
+        let mut decoder = DecoderWriter::new(Vec::new(), 10, 5)
+            .unwrap()
+            .framed()
+            .strict()
+            .with_dictionary(dict);
+        let mut sizes = Vec::new();
+        for chunk in src.chunks(700) {
+            let start = encoder.get_ref().len();
+            encoder.write_all(chunk).unwrap();
+            encoder.flush().unwrap();
+            sizes.push(encoder.get_ref().len() - start);
+            decoder.write_all(&encoder.get_ref()[start..]).unwrap();
+            assert_eq!(decoder.get_ref().len() as u64, encoder.total_in());
+        }
+        let encoded = encoder.finish().unwrap();
+        assert!(sizes.iter().all(|n| *n < 700), "{:?}", sizes);
+        assert_eq!(sizes.iter().sum::<usize>(), encoded.len());
+        assert_eq!(decoder.finish().unwrap(), &src[..]);
+    }
+
+    #[test]
+    fn invalid_params() {
+        assert!(EncoderWriter::new(Vec::new(), 3, 2).is_err());
+        assert!(DecoderWriter::new(Vec::new(), 8, 8).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use core::cell::UnsafeCell;
+use core::ops::Deref;
+
+use alloc::vec::Vec;
+
+use crate::decoder::HeatshrinkDecoder;
+use crate::CompressedData;
+
+#[cfg(feature = "std")]
+type Once<T> = std::sync::OnceLock<T>;
+#[cfg(not(feature = "std"))]
+type Once<T> = spin::Once<T>;
+
+#[cfg(feature = "std")]
+fn call_once<T>(once: &Once<T>, f: impl FnOnce() -> T) -> &T {
+    once.get_or_init(f)
+}
+#[cfg(not(feature = "std"))]
+fn call_once<T>(once: &Once<T>, f: impl FnOnce() -> T) -> &T {
+    once.call_once(f)
+}
+
+fn decompress(data: &CompressedData) -> Vec<u8> {
+    let mut res = Vec::with_capacity(data.original_size);
+    res.extend(HeatshrinkDecoder::source(data.data.iter().cloned()));
+    assert_eq!(res.len(), data.original_size, "corrupted compressed data");
+    res
+}
+
+// распаковка в готовый буфер, данные обрезаны до original_size
+fn decompress_into(data: &CompressedData, buf: &mut [u8]) {
+    let mut size = 0;
+    for b in HeatshrinkDecoder::source(data.data.iter().cloned()) {
+        *buf.get_mut(size).expect("corrupted compressed data") = b;
+        size += 1;
+    }
+    assert_eq!(size, data.original_size, "corrupted compressed data");
+}
+
+/// Сжатые данные, которые распаковываются в кучу при первом обращении и далее
+/// переиспользуются.
+///
+/// ```ignore
+/// static LOGO: LazyDecompressed = packed_file_lazy!("assets/logo.bin");
+/// display.draw(&LOGO);
+/// ```
+pub struct LazyDecompressed<'a> {
+    data: CompressedData<'a>,
+    cache: Once<Vec<u8>>,
+}
+
+impl<'a> LazyDecompressed<'a> {
+    pub const fn new(data: CompressedData<'a>) -> Self {
+        Self {
+            data,
+            cache: Once::new(),
+        }
+    }
+
+    pub fn get(&self) -> &[u8] {
+        call_once(&self.cache, || decompress(&self.data)).as_slice()
+    }
+}
+
+impl<'a> Deref for LazyDecompressed<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.get()
+    }
+}
+
+/// Сжатая строка, распаковывается при первом обращении, см. `packed_str_lazy!`
+pub struct LazyStr<'a>(LazyDecompressed<'a>);
+
+impl<'a> LazyStr<'a> {
+    pub const fn new(data: CompressedData<'a>) -> Self {
+        Self(LazyDecompressed::new(data))
+    }
+
+    pub fn get(&self) -> &str {
+        let data = call_once(&self.0.cache, || {
+            let data = decompress(&self.0.data);
+            // проверяем один раз, при распаковке
+            assert!(core::str::from_utf8(&data).is_ok(), "invalid utf-8");
+            data
+        });
+        unsafe { core::str::from_utf8_unchecked(data) }
+    }
+}
+
+impl<'a> Deref for LazyStr<'a> {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.get()
+    }
+}
+
+/// То же, что [`LazyDecompressed`], но распаковывает в собственный буфер на N байт,
+/// без кучи. В static буфер окажется в .bss.
+///
+/// ```ignore
+/// static TABLE: LazyDecompressedBuf<4096> = LazyDecompressedBuf::new(packed_file!("table.bin"));
+/// ```
+pub struct LazyDecompressedBuf<'a, const N: usize> {
+    data: CompressedData<'a>,
+    ready: Once<()>,
+    buf: UnsafeCell<[u8; N]>,
+}
+
+// В buf пишет только замыкание внутри ready, читать можно только после его завершения
+unsafe impl<'a, const N: usize> Sync for LazyDecompressedBuf<'a, N> {}
+
+impl<'a, const N: usize> LazyDecompressedBuf<'a, N> {
+    pub const fn new(data: CompressedData<'a>) -> Self {
+        assert!(data.original_size <= N, "buffer is too small");
+        Self {
+            data,
+            ready: Once::new(),
+            buf: UnsafeCell::new([0; N]),
+        }
+    }
+
+    pub fn get(&self) -> &[u8] {
+        call_once(&self.ready, || {
+            decompress_into(&self.data, unsafe { &mut *self.buf.get() })
+        });
+        let buf = unsafe { &*self.buf.get() };
+        &buf[..self.data.original_size]
+    }
+}
+
+impl<'a, const N: usize> Deref for LazyDecompressedBuf<'a, N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.get()
+    }
+}
+
+/// То же, что [`LazyDecompressedBuf`], но буфер пользователя: например общий для
+/// нескольких ресурсов или в отдельной секции памяти.
+///
+/// ```ignore
+/// static mut BUF: [u8; 4096] = [0; 4096];
+/// static TABLE: LazyDecompressedSlice = LazyDecompressedSlice::new(
+///     packed_file!("table.bin"),
+///     unsafe { &mut *core::ptr::addr_of_mut!(BUF) },
+/// );
+/// ```
+pub struct LazyDecompressedSlice<'a> {
+    data: CompressedData<'a>,
+    ready: Once<()>,
+    buf: UnsafeCell<&'static mut [u8]>,
+}
+
+// Как у LazyDecompressedBuf: пишет только замыкание внутри ready
+unsafe impl<'a> Sync for LazyDecompressedSlice<'a> {}
+
+impl<'a> LazyDecompressedSlice<'a> {
+    pub const fn new(data: CompressedData<'a>, buf: &'static mut [u8]) -> Self {
+        assert!(data.original_size <= buf.len(), "buffer is too small");
+        Self {
+            data,
+            ready: Once::new(),
+            buf: UnsafeCell::new(buf),
+        }
+    }
+
+    pub fn get(&self) -> &[u8] {
+        call_once(&self.ready, || {
+            decompress_into(&self.data, unsafe { &mut **self.buf.get() })
+        });
+        let buf = unsafe { &**self.buf.get() };
+        &buf[..self.data.original_size]
+    }
+}
+
+impl<'a> Deref for LazyDecompressedSlice<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.get()
+    }
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::encoder::HeatshrinkEncoder;
+    use crate::lazy::{LazyDecompressed, LazyDecompressedBuf, LazyDecompressedSlice, LazyStr};
+    use crate::CompressedData;
+
+    fn compress(src: &[u8]) -> Vec<u8> {
+        HeatshrinkEncoder::source(src.iter().cloned()).collect()
+    }
+
+    #[test]
+    fn lazy_decompressed() {
+        let src = (0..1000).map(|n| (n % 17) as u8).collect::<Vec<_>>();
+        let packed = compress(&src);
+
+        let lazy = LazyDecompressed::new(CompressedData {
+            data: &packed,
+            original_size: src.len(),
+        });
+        assert_eq!(&*lazy, src.as_slice());
+        // второй раз - из кеша
+        assert_eq!(lazy.get().as_ptr(), lazy.get().as_ptr());
+    }
+
+    #[test]
+    fn lazy_str() {
+        let src = "Строка, строка, строка";
+        let packed = compress(src.as_bytes());
+
+        let lazy = LazyStr::new(CompressedData {
+            data: &packed,
+            original_size: src.len(),
+        });
+        assert_eq!(&*lazy, src);
+    }
+
+    #[test]
+    fn lazy_static_buffer() {
+        static PACKED: [u8; 2] = [0x00, 0x38];
+        static ZEROS: LazyDecompressedBuf<16> = LazyDecompressedBuf::new(CompressedData {
+            data: &PACKED,
+            original_size: 8,
+        });
+
+        assert_eq!(&*ZEROS, &[0u8; 8]);
+    }
+
+    #[test]
+    fn lazy_user_buffer() {
+        static mut BUF: [u8; 16] = [0xff; 16];
+        static ZEROS: LazyDecompressedSlice = LazyDecompressedSlice::new(
+            CompressedData {
+                data: &[0x00, 0x38],
+                original_size: 8,
+            },
+            unsafe { &mut *core::ptr::addr_of_mut!(BUF) },
+        );
+
+        assert_eq!(&*ZEROS, &[0u8; 8]);
+        assert_eq!(ZEROS.get().as_ptr(), ZEROS.get().as_ptr());
+    }
+
+    #[test]
+    #[should_panic]
+    fn lazy_corrupted() {
+        let lazy = LazyDecompressed::new(CompressedData {
+            data: &[0x00, 0x38],
+            original_size: 9,
+        });
+        let _ = lazy.get();
+    }
+}
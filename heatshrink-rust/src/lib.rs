@@ -2,22 +2,135 @@
 
 extern crate alloc;
 
+pub mod compressed_log;
+pub mod container;
 pub mod decoder;
+pub mod dictionary;
 
+#[cfg(feature = "std")]
+pub mod dynamic;
 pub mod encoder;
 pub(crate) mod encoder_common;
 pub mod encoder_to_vec;
+pub mod estimate;
+pub mod filter;
+pub mod fmt;
+pub mod framing;
+pub mod inspect;
+#[cfg(feature = "std")]
+pub mod io;
+pub mod lazy;
+#[cfg(feature = "log")]
+pub mod logger;
+pub mod message;
+pub mod optimal;
+
+#[cfg(feature = "packed")]
+pub mod packed;
+pub mod record;
+pub mod record_stream;
+pub mod seekable;
+#[cfg(feature = "stats")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod train;
+#[cfg(feature = "std")]
+pub mod tune;
+pub mod validate;
+
+pub const MIN_WINDOW_BITS: u8 = encoder_common::HEATSHRINK_MIN_WINDOW_BITS as u8;
+pub const MAX_WINDOW_BITS: u8 = encoder_common::HEATSHRINK_MAX_WINDOW_BITS as u8;
+pub const MIN_LOOKAHEAD_BITS: u8 = encoder_common::HEATSHRINK_MIN_LOOKAHEAD_BITS as u8;
+
+/// Параметры, с которыми собрана основная (no_std) библиотека
+pub const STATIC_WINDOW_BITS: u8 = encoder_common::HEATSHRINK_STATIC_WINDOW_BITS as u8;
+pub const STATIC_LOOKAHEAD_BITS: u8 = encoder_common::HEATSHRINK_STATIC_LOOKAHEAD_BITS as u8;
+
+/// Допустимы ли такие параметры: `4 <= window <= 15`, `3 <= lookahead < window`
+pub const fn valid_params(window_bits: u8, lookahead_bits: u8) -> bool {
+    window_bits >= MIN_WINDOW_BITS
+        && window_bits <= MAX_WINDOW_BITS
+        && lookahead_bits >= MIN_LOOKAHEAD_BITS
+        && lookahead_bits < window_bits
+}
+
+/// Входной буфер декодера основной (no_std) библиотеки
+pub const STATIC_INPUT_BUFFER_SIZE: usize =
+    encoder_common::HEATSHRINK_STATIC_INPUT_BUFFER_SIZE as usize;
+
+/// Размер контекста кодера (`heatshrink_encoder` со статическими буферами и индексом поиска)
+pub const fn encoder_ram(window_bits: u8) -> usize {
+    // 16 байт заголовка, индекс: размер + 2 << window_bits u16, входной буфер 2 << window_bits
+    16 + 2 + 3 * (2 << window_bits)
+}
+
+/// Размер контекста декодера (`heatshrink_decoder` со статическими буферами)
+pub const fn decoder_ram(window_bits: u8, input_buffer_size: usize) -> usize {
+    // 13 байт заголовка, входной буфер, окно; выравнивание на u16
+    (13 + input_buffer_size + (1 << window_bits) + 1) & !1
+}
+
+/// Максимальный размер сжатых данных для `input_len` байт входа кодером с параметрами
+/// `window_bits` / `lookahead_bits`, включая дополнение последнего байта. Худший случай -
+/// одни литералы по 9 бит: все кодеры крейта берут ссылку назад, только если она короче
+/// литералов (совпадение длиннее `(1 + window_bits + lookahead_bits) / 8` байт), поэтому
+/// при допустимых параметрах граница от них не зависит. Паника, если параметры не проходят
+/// [`valid_params`].
+/// Точки flush ([`framing`]) в границу не входят: каждая добавляет до байта дополнения
+/// и каждый сегмент - заголовок до 5 байт. Гарантию "не больше" без точек flush дает
+/// [`encoder_to_vec::HeatshrinkEncoderToVec::with_input_len`]
+pub const fn compress_bound(input_len: usize, window_bits: u8, lookahead_bits: u8) -> usize {
+    assert!(
+        valid_params(window_bits, lookahead_bits),
+        "invalid heatshrink parameters"
+    );
+    input_len + input_len.div_ceil(8)
+}
+
+/// Уровень сжатия кодера основной библиотеки. Поток на любом уровне читается тем же
+/// декодером, уровни отличаются скоростью, памятью и размером результата
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Level {
+    /// Кодер C без индекса поиска (`HEATSHRINK_USE_INDEX=0`): индекс не строится при каждом
+    /// заполнении буфера, зато каждое совпадение ищется перебором окна. Результат тот же,
+    /// что у Default. Индекс - `2 + 2 * (2 << window_bits)` байт: его нет в памяти
+    /// `dynamic::DynamicEncoder` (там он отдельный malloc()), а кодеры основной библиотеки
+    /// хранят контекст на месте, размером с [`encoder_ram`] на любом уровне
+    Fast,
+    /// Кодер C с индексом, жадный разбор - как раньше
+    #[default]
+    Default,
+    /// Оптимальный разбор ([`optimal`]): меньше всего, но весь вход собирается в памяти
+    /// и на сжатие нужно еще около 9 байт на байт входа, см. [`optimal`].
+    /// Только у кодеров, которые видят вход целиком: `HeatshrinkEncoder` и `io::EncoderWriter`
+    /// (до точки flush или finish). `HeatshrinkEncoderToVec`, `message::MessageEncoder`
+    /// и `DynamicEncoder` отдают сжатое по мере поступления, у них Best - как Default
+    Best,
+}
+
+impl core::str::FromStr for Level {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fast" => Ok(Level::Fast),
+            "default" => Ok(Level::Default),
+            "best" => Ok(Level::Best),
+            _ => Err(()),
+        }
+    }
+}
 
 pub struct CompressedData<'a> {
     pub data: &'a [u8],
     pub original_size: usize,
 }
 
-#[cfg(all(unix))]
+#[cfg(any(unix, feature = "std"))]
 #[macro_use]
 extern crate std;
 
-#[cfg(all(unix))]
+#[cfg(unix)]
 #[cfg(test)]
 mod tests {
     use crate::decoder::HeatshrinkDecoder;
@@ -25,6 +138,54 @@ mod tests {
 
     use std::vec::Vec;
 
+    #[test]
+    fn ram_footprint() {
+        use crate::decoder::_heatshrink_decoder;
+        use crate::encoder_common::_heatshrink_encoder;
+        use crate::{decoder_ram, encoder_ram, STATIC_INPUT_BUFFER_SIZE, STATIC_WINDOW_BITS};
+
+        assert_eq!(
+            encoder_ram(STATIC_WINDOW_BITS),
+            core::mem::size_of::<_heatshrink_encoder>()
+        );
+        assert_eq!(
+            decoder_ram(STATIC_WINDOW_BITS, STATIC_INPUT_BUFFER_SIZE),
+            core::mem::size_of::<_heatshrink_decoder>()
+        );
+    }
+
+    #[test]
+    fn compress_bound() {
+        use crate::{STATIC_LOOKAHEAD_BITS, STATIC_WINDOW_BITS};
+        use rand::Rng;
+
+        let compress_bound =
+            |len| crate::compress_bound(len, STATIC_WINDOW_BITS, STATIC_LOOKAHEAD_BITS);
+        assert_eq!(compress_bound(0), 0);
+        assert_eq!(compress_bound(1), 2);
+        assert_eq!(compress_bound(8), 9);
+        assert_eq!(compress_bound(9), 11);
+        assert_eq!(crate::compress_bound(9, 15, 14), 11);
+
+        // без повторов - ровно граница
+        let src = (0..=255u8).collect::<Vec<_>>();
+        let packed = HeatshrinkEncoder::source(src.iter().cloned()).collect::<Vec<_>>();
+        assert_eq!(packed.len(), compress_bound(src.len()));
+
+        let mut rng = rand::thread_rng();
+        for len in 0..300 {
+            let src = (0..len).map(|_| rng.gen_range(0..4u8)).collect::<Vec<_>>();
+            let packed = HeatshrinkEncoder::source(src.iter().cloned()).collect::<Vec<_>>();
+            assert!(packed.len() <= compress_bound(len));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn compress_bound_invalid_params() {
+        crate::compress_bound(10, 8, 8);
+    }
+
     #[test]
     fn decode_zeros() {
         let input = [0u8, 0x38];
@@ -0,0 +1,331 @@
+//! Бэкенд `log`: строки лога сжимаются и уходят в [`Sink`] пользователя (кольцевой буфер,
+//! UART, файл), на хосте читаются [`decode`], [`LineDecoder`] или `heatshrink log`.
+//!
+//! Поток - [`crate::record_stream`], одна запись на строку. Блок закрывается и уходит в sink
+//! каждые [`DEFAULT_FLUSH_INTERVAL`] строк ([`HeatshrinkLogger::with_flush_interval`]),
+//! сразу после строки уровня Error и по [`log::Log::flush`] - так хост видит лог почти
+//! сразу, а блоки распаковываются независимо. Незакрытый блок хранится в RAM,
+//! хвост захваченного потока может быть недописанным блоком ([`Error::Truncated`]).
+//!
+//! Логгер не ждет блокировок, поэтому его можно звать из прерывания (если аллокатор это
+//! допускает): строка, пришедшая, пока кодер занят другим контекстом, выбрасывается и
+//! считается в [`HeatshrinkLogger::dropped`]. Sink вызывается без блокировки кодера; если
+//! sink занят, готовые блоки уходят в него с одной из следующих строк или по flush().
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use log::{Level, LevelFilter, Metadata, Record};
+
+use crate::record_stream::{BlockDecoder, Error, RecordDecoder, RecordEncoder};
+
+pub const DEFAULT_FLUSH_INTERVAL: usize = 16;
+
+/// Куда пишется сжатый поток. Ошибки записи логгеру сообщать некуда, их решает sink
+pub trait Sink: Send {
+    fn write(&mut self, data: &[u8]);
+}
+
+impl<F: FnMut(&[u8]) + Send> Sink for F {
+    fn write(&mut self, data: &[u8]) {
+        self(data)
+    }
+}
+
+/// Строка: `[время] LEVEL target: сообщение`, время - с [`HeatshrinkLogger::with_timestamp`]
+pub struct HeatshrinkLogger<S> {
+    encoder: spin::Mutex<RecordEncoder>,
+    sink: spin::Mutex<S>,
+    dropped: AtomicUsize,
+    level: LevelFilter,
+    timestamp: Option<fn() -> u64>,
+}
+
+impl<S: Sink> HeatshrinkLogger<S> {
+    pub fn new(sink: S) -> Self {
+        Self {
+            encoder: spin::Mutex::new(
+                RecordEncoder::new().with_sync_interval(DEFAULT_FLUSH_INTERVAL),
+            ),
+            sink: spin::Mutex::new(sink),
+            dropped: AtomicUsize::new(0),
+            level: LevelFilter::Trace,
+            timestamp: None,
+        }
+    }
+
+    pub fn with_level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Отправлять блок каждые `records` строк, 0 - только по flush() и Error
+    pub fn with_flush_interval(mut self, records: usize) -> Self {
+        *self.encoder.get_mut() = RecordEncoder::new().with_sync_interval(records);
+        self
+    }
+
+    /// Время в начале строки, например миллисекунды от старта
+    pub fn with_timestamp(mut self, now: fn() -> u64) -> Self {
+        self.timestamp = Some(now);
+        self
+    }
+
+    /// Установить глобальным логгером (`log::set_logger`), уровень - из `with_level`
+    pub fn init(self) -> Result<(), log::SetLoggerError>
+    where
+        S: 'static,
+    {
+        let level = self.level;
+        log::set_logger(Box::leak(Box::new(self)))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+
+    /// Сколько строк выброшено: кодер был занят другим контекстом
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    // Готовые блоки - в sink. Вывод забирает только владелец sink, так блоки не
+    // перемешиваются; кодер заблокирован только на время take_output()
+    fn drain(&self) {
+        let mut sink = match self.sink.try_lock() {
+            Some(sink) => sink,
+            None => return,
+        };
+        loop {
+            let out = match self.encoder.try_lock() {
+                Some(mut encoder) => encoder.take_output(),
+                None => return,
+            };
+            if out.is_empty() {
+                return;
+            }
+            sink.write(&out);
+        }
+    }
+}
+
+impl<S: Sink> log::Log for HeatshrinkLogger<S> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut line = String::new();
+        if let Some(now) = self.timestamp {
+            let _ = write!(line, "[{}] ", now());
+        }
+        let _ = write!(
+            line,
+            "{:<5} {}: {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        match self.encoder.try_lock() {
+            Some(mut encoder) => {
+                encoder.push(line.as_bytes());
+                if record.level() == Level::Error {
+                    encoder.sync();
+                }
+            }
+            None => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        self.drain();
+    }
+
+    fn flush(&self) {
+        if let Some(mut encoder) = self.encoder.try_lock() {
+            encoder.sync();
+        }
+        self.drain();
+    }
+}
+
+/// Строки из захваченного потока, по порядку. Не UTF-8 заменяется на U+FFFD
+pub fn decode(src: &[u8]) -> impl Iterator<Item = Result<String, Error>> + '_ {
+    RecordDecoder::new(src).map(|r| r.map(|line| String::from_utf8_lossy(&line).into_owned()))
+}
+
+/// [`decode`] для потока, приходящего по частям: строки отдаются поблочно,
+/// как только блок пришел целиком. См. [`BlockDecoder`]
+#[derive(Default)]
+pub struct LineDecoder {
+    blocks: BlockDecoder,
+}
+
+impl LineDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.blocks.push(data);
+    }
+
+    /// Строки следующего блока, None - нужны данные
+    pub fn next_block(&mut self) -> Option<Result<Vec<String>, Error>> {
+        self.blocks.next_block().map(|block| {
+            block.map(|lines| {
+                lines
+                    .iter()
+                    .map(|line| String::from_utf8_lossy(line).into_owned())
+                    .collect()
+            })
+        })
+    }
+
+    /// Конец потока: Err(Truncated), если последний блок недописан
+    pub fn finish(&self) -> Result<(), Error> {
+        self.blocks.finish()
+    }
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    use std::string::String;
+    use std::sync::{Arc, Mutex};
+    use std::vec::Vec;
+
+    use log::{Level, LevelFilter, Log, Record};
+
+    use crate::logger::{decode, HeatshrinkLogger, LineDecoder};
+    use crate::record_stream::Error;
+
+    fn log(logger: &dyn Log, level: Level, message: &str) {
+        logger.log(
+            &Record::builder()
+                .level(level)
+                .target("app::sensor")
+                .args(format_args!("{}", message))
+                .build(),
+        );
+    }
+
+    fn lines(captured: &Mutex<Vec<u8>>) -> Vec<String> {
+        decode(&captured.lock().unwrap())
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn periodic_flush() {
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let sink = captured.clone();
+        let logger =
+            HeatshrinkLogger::new(move |data: &[u8]| sink.lock().unwrap().extend_from_slice(data))
+                .with_flush_interval(4)
+                .with_level(LevelFilter::Info);
+
+        for n in 0..10 {
+            log(&logger, Level::Info, &std::format!("temperature={}", n));
+        }
+        log(&logger, Level::Debug, "filtered");
+        // 8 строк в двух закрытых блоках, 2 ждут в RAM
+        let res = lines(&captured);
+        assert_eq!(res.len(), 8);
+        assert_eq!(res[0], "INFO  app::sensor: temperature=0");
+
+        logger.flush();
+        let res = lines(&captured);
+        assert_eq!(res.len(), 10);
+        assert_eq!(res[9], "INFO  app::sensor: temperature=9");
+
+        // Error уходит сразу
+        log(&logger, Level::Error, "overheat");
+        assert_eq!(lines(&captured)[10], "ERROR app::sensor: overheat");
+    }
+
+    #[test]
+    fn timestamp_and_truncated() {
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let sink = captured.clone();
+        let logger =
+            HeatshrinkLogger::new(move |data: &[u8]| sink.lock().unwrap().extend_from_slice(data))
+                .with_timestamp(|| 1500);
+        for _ in 0..20 {
+            log(&logger, Level::Warn, "low battery");
+        }
+        let captured = captured.lock().unwrap();
+        let res = decode(&captured).collect::<Vec<_>>();
+        assert_eq!(res.len(), 16);
+        assert_eq!(
+            res[0],
+            Ok(String::from("[1500] WARN  app::sensor: low battery"))
+        );
+
+        // захват оборван на середине блока
+        let res = decode(&captured[..captured.len() - 3]).collect::<Vec<_>>();
+        assert_eq!(res, [Err(Error::Truncated)]);
+    }
+
+    #[test]
+    fn busy_locks() {
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let sink = captured.clone();
+        let logger =
+            HeatshrinkLogger::new(move |data: &[u8]| sink.lock().unwrap().extend_from_slice(data));
+
+        // кодер занят, например прерванный контекст: строка выбрасывается
+        let encoder = logger.encoder.lock();
+        log(&logger, Level::Error, "lost");
+        drop(encoder);
+        assert_eq!(logger.dropped(), 1);
+        assert!(captured.lock().unwrap().is_empty());
+
+        // sink занят: блок ждет в кодере и уходит со следующей строкой
+        let sink = logger.sink.lock();
+        log(&logger, Level::Error, "first");
+        drop(sink);
+        assert!(captured.lock().unwrap().is_empty());
+        log(&logger, Level::Error, "second");
+        assert_eq!(
+            lines(&captured),
+            ["ERROR app::sensor: first", "ERROR app::sensor: second"]
+        );
+        assert_eq!(logger.dropped(), 1);
+    }
+
+    #[test]
+    fn line_decoder() {
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let sink = captured.clone();
+        let logger =
+            HeatshrinkLogger::new(move |data: &[u8]| sink.lock().unwrap().extend_from_slice(data))
+                .with_flush_interval(2);
+        for n in 0..5 {
+            log(&logger, Level::Info, &std::format!("n={}", n));
+        }
+        let captured = captured.lock().unwrap();
+
+        let mut decoder = LineDecoder::new();
+        decoder.push(&captured[..captured.len() - 1]);
+        assert_eq!(
+            decoder.next_block(),
+            Some(Ok(std::vec![
+                String::from("INFO  app::sensor: n=0"),
+                String::from("INFO  app::sensor: n=1")
+            ]))
+        );
+        // второй блок пришел не целиком
+        assert_eq!(decoder.next_block(), None);
+        assert_eq!(decoder.finish(), Err(Error::Truncated));
+        decoder.push(&captured[captured.len() - 1..]);
+        assert_eq!(decoder.next_block().unwrap().unwrap().len(), 2);
+        assert_eq!(decoder.next_block(), None);
+        assert_eq!(decoder.finish(), Ok(()));
+    }
+}
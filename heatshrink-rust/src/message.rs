@@ -0,0 +1,327 @@
+#![allow(non_upper_case_globals)]
+
+//! Сжатие потока коротких сообщений (пакеты радиоканала) с общим окном.
+//!
+//! Каждое сообщение - самостоятельный кадр: `[заголовок][сжатые данные]`, сжатые данные
+//! выровнены до байта (sync flush), но ссылаются на предыдущие сообщения.
+//! Заголовок - 1 байт: старший бит [`RESET`] - окно начато заново, младшие 7 бит - номер кадра.
+//! Потерянный кадр [`MessageDecoder`] замечает по номеру, после этого нужен
+//! [`MessageEncoder::reset`] на передающей стороне.
+
+use alloc::vec::Vec;
+
+use crate::decoder::{
+    _heatshrink_decoder, heatshrink_decoder_poll, heatshrink_decoder_reset,
+    heatshrink_decoder_sink, HSD_poll_res_HSDR_POLL_EMPTY, HSD_poll_res_HSDR_POLL_MORE,
+    HSD_sink_res_HSDR_SINK_FULL, HSD_sink_res_HSDR_SINK_OK,
+};
+use crate::encoder_common::Context;
+use crate::{compress_bound, Level, STATIC_LOOKAHEAD_BITS, STATIC_WINDOW_BITS};
+
+/// Флаг заголовка: кадр начинает новое окно
+pub const RESET: u8 = 0x80;
+/// Маска номера кадра в заголовке
+pub const SEQUENCE_MASK: u8 = 0x7f;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Error {
+    /// Кадр без заголовка
+    Empty,
+    /// Пропущен кадр: ждали `expected`, пришел `got`
+    Lost { expected: u8, got: u8 },
+    /// После потери кадра принимаются только кадры с [`RESET`]
+    NeedReset,
+    /// Кадр распаковывается больше чем в `limit` байт, см. [`MessageDecoder::with_limit`]
+    OutputLimit { limit: usize },
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Empty => write!(f, "empty frame"),
+            Error::Lost { expected, got } => {
+                write!(f, "lost frame: expected #{}, got #{}", expected, got)
+            }
+            Error::NeedReset => write!(f, "history lost, waiting for reset frame"),
+            Error::OutputLimit { limit } => {
+                write!(f, "decompressed message exceeds {} bytes", limit)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+pub struct MessageEncoder {
+    ctx: Context,
+    sequence: u8,
+    reset: bool,
+}
+
+impl Default for MessageEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageEncoder {
+    pub fn new() -> Self {
+        Self {
+            ctx: Context::new(Level::Default),
+            sequence: 0,
+            reset: true,
+        }
+    }
+
+    /// Сжатие с уровнем `level`, см. [`Level`]: Best здесь - как Default.
+    /// Вызывать сразу после конструктора
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.ctx = Context::new(level);
+        self
+    }
+
+    /// Ответ на запрос resync: следующий кадр с [`RESET`], окно и нумерация заново
+    pub fn reset(&mut self) {
+        self.ctx.reset();
+        self.sequence = 0;
+        self.reset = true;
+    }
+
+    /// Номер следующего кадра
+    pub fn sequence(&self) -> u8 {
+        self.sequence
+    }
+
+    /// Сжать сообщение в отдельный кадр
+    pub fn encode(&mut self, message: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(
+            1 + compress_bound(message.len(), STATIC_WINDOW_BITS, STATIC_LOOKAHEAD_BITS),
+        );
+        frame.push(self.sequence | if self.reset { RESET } else { 0 });
+
+        self.ctx.sink_all(message, &mut frame);
+        // sync flush: все принятое выдать с выравниванием до байта
+        self.ctx.finish_all(&mut frame);
+        self.ctx.resume();
+
+        self.sequence = (self.sequence + 1) & SEQUENCE_MASK;
+        self.reset = false;
+        frame
+    }
+}
+
+pub struct MessageDecoder {
+    ctx: _heatshrink_decoder,
+    expected: u8,
+    lost: bool,
+    limit: usize,
+}
+
+impl Default for MessageDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageDecoder {
+    pub fn new() -> Self {
+        let mut res = Self {
+            ctx: _heatshrink_decoder::default(),
+            expected: 0,
+            lost: false,
+            limit: usize::MAX,
+        };
+        unsafe {
+            heatshrink_decoder_reset(&mut res.ctx);
+        }
+        res
+    }
+
+    /// Сообщение не больше `max_output` байт: кадр длиннее - [`Error::OutputLimit`],
+    /// окно распаковано наполовину, так что дальше как после потери кадра - нужен reset.
+    /// Для кадров из недоверенного канала
+    pub fn with_limit(mut self, max_output: usize) -> Self {
+        self.limit = max_output;
+        self
+    }
+
+    /// Кадр потерян, история недостоверна: нужно попросить передатчик о reset
+    pub fn needs_reset(&self) -> bool {
+        self.lost
+    }
+
+    /// Распаковать кадр. Кадры должны идти подряд, кадр с [`RESET`] принимается всегда
+    pub fn decode(&mut self, frame: &[u8]) -> Result<Vec<u8>, Error> {
+        let (&header, mut data) = frame.split_first().ok_or(Error::Empty)?;
+        let sequence = header & SEQUENCE_MASK;
+
+        if header & RESET != 0 {
+            unsafe {
+                heatshrink_decoder_reset(&mut self.ctx);
+            }
+            self.lost = false;
+        } else if self.lost {
+            return Err(Error::NeedReset);
+        } else if sequence != self.expected {
+            self.lost = true;
+            return Err(Error::Lost {
+                expected: self.expected,
+                got: sequence,
+            });
+        }
+
+        let mut res = Vec::with_capacity((data.len() * 2).min(self.limit));
+        while !data.is_empty() {
+            let mut writen = 0;
+            // в биндингах *mut, но вход декодер не меняет
+            let ptr = data.as_ptr() as *mut u8;
+            match unsafe { heatshrink_decoder_sink(&mut self.ctx, ptr, data.len(), &mut writen) } {
+                HSD_sink_res_HSDR_SINK_OK | HSD_sink_res_HSDR_SINK_FULL => {}
+                _ => panic!(),
+            }
+            data = &data[writen..];
+            if let Err(e) = self.poll_to(&mut res) {
+                self.lost = true;
+                return Err(e);
+            }
+        }
+        // весь вход разобран, остаток - дополнение байта
+        self.ctx.sync();
+
+        self.expected = (sequence + 1) & SEQUENCE_MASK;
+        Ok(res)
+    }
+
+    fn poll_to(&mut self, res: &mut Vec<u8>) -> Result<(), Error> {
+        let mut buf = [0u8; 32];
+        loop {
+            let mut out_writen = 0;
+            let poll_res = unsafe {
+                heatshrink_decoder_poll(&mut self.ctx, buf.as_mut_ptr(), buf.len(), &mut out_writen)
+            };
+            if out_writen > self.limit - res.len() {
+                return Err(Error::OutputLimit { limit: self.limit });
+            }
+            res.extend_from_slice(&buf[..out_writen]);
+            match poll_res {
+                HSD_poll_res_HSDR_POLL_EMPTY => return Ok(()),
+                HSD_poll_res_HSDR_POLL_MORE => {}
+                _ => panic!(),
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::message::{Error, MessageDecoder, MessageEncoder, RESET};
+    use crate::Level;
+
+    fn packet(n: u32) -> Vec<u8> {
+        format!("id=42 t={} rssi=-71 status=ok", 20 + n % 3).into_bytes()
+    }
+
+    #[test]
+    fn messages() {
+        let mut encoder = MessageEncoder::new();
+        let mut decoder = MessageDecoder::new();
+
+        let mut sizes = Vec::new();
+        for n in 0..300 {
+            let frame = encoder.encode(&packet(n));
+            assert_eq!(frame[0] & RESET != 0, n == 0);
+            sizes.push(frame.len());
+            assert_eq!(decoder.decode(&frame), Ok(packet(n)));
+        }
+        // окно общее: повторяющиеся пакеты сжимаются в пару обратных ссылок
+        assert!(sizes[10] < sizes[0] / 3, "{:?}", &sizes[..11]);
+
+        let frame = encoder.encode(b"");
+        assert_eq!(frame.len(), 1);
+        assert_eq!(decoder.decode(&frame), Ok(Vec::new()));
+        assert_eq!(decoder.decode(&[]), Err(Error::Empty));
+    }
+
+    #[test]
+    fn fast_level() {
+        let mut fast = MessageEncoder::new().with_level(Level::Fast);
+        let mut default = MessageEncoder::new();
+        let mut decoder = MessageDecoder::new();
+        for n in 0..50 {
+            let frame = fast.encode(&packet(n));
+            assert_eq!(frame, default.encode(&packet(n)));
+            assert_eq!(decoder.decode(&frame), Ok(packet(n)));
+        }
+    }
+
+    #[test]
+    fn lost_frame() {
+        let mut encoder = MessageEncoder::new();
+        let mut decoder = MessageDecoder::new();
+
+        for n in 0..3 {
+            assert_eq!(decoder.decode(&encoder.encode(&packet(n))), Ok(packet(n)));
+        }
+        let _lost = encoder.encode(&packet(3));
+        assert_eq!(
+            decoder.decode(&encoder.encode(&packet(4))),
+            Err(Error::Lost {
+                expected: 3,
+                got: 4
+            })
+        );
+        assert!(decoder.needs_reset());
+        assert_eq!(
+            decoder.decode(&encoder.encode(&packet(5))),
+            Err(Error::NeedReset)
+        );
+
+        // передатчик получил запрос resync
+        encoder.reset();
+        for n in 6..9 {
+            assert_eq!(decoder.decode(&encoder.encode(&packet(n))), Ok(packet(n)));
+        }
+        assert!(!decoder.needs_reset());
+    }
+
+    #[test]
+    fn output_limit() {
+        let mut encoder = MessageEncoder::new();
+        let mut decoder = MessageDecoder::new().with_limit(100);
+
+        assert_eq!(
+            decoder.decode(&encoder.encode(&[7; 100])),
+            Ok([7; 100].to_vec())
+        );
+        // ~1 КиБ сжатых данных - 10 КиБ
+        let bomb = encoder.encode(&[7; 10_000]);
+        assert!(bomb.len() < 10_000 / 8);
+        assert_eq!(
+            decoder.decode(&bomb),
+            Err(Error::OutputLimit { limit: 100 })
+        );
+        assert!(decoder.needs_reset());
+
+        encoder.reset();
+        assert_eq!(decoder.decode(&encoder.encode(&packet(0))), Ok(packet(0)));
+    }
+
+    #[test]
+    fn sequence_wraps() {
+        let mut encoder = MessageEncoder::new();
+        let mut decoder = MessageDecoder::new();
+
+        for n in 0..200 {
+            assert_eq!(encoder.sequence(), n % 128);
+            let message = [n; 40];
+            assert_eq!(
+                decoder.decode(&encoder.encode(&message)),
+                Ok(message.to_vec())
+            );
+        }
+    }
+}
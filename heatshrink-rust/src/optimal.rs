@@ -0,0 +1,274 @@
+//! Кодер с оптимальным разбором на чистом Rust - для сжатия заранее (макросы, CLI `-L best`),
+//! когда важен размер, а не скорость.
+//!
+//! Литерал стоит 9 бит, ссылка назад - `1 + window_bits + lookahead_bits` при любых смещении
+//! и длине. Минимальная стоимость хвоста с позиции `i` не меньше, чем с `i + 1` (первый токен
+//! можно укоротить на байт), поэтому в каждой позиции из ссылок достаточно самой длинной,
+//! а динамика от конца к началу - литерал или самая длинная ссылка - дает минимум бит.
+//! Совпадения ищутся по всему окну без отсечений. Окно в начале, как у декодера, - нули
+//! (и словарь в конце окна), и ссылки на нули до начала данных берутся так же, как у жадного
+//! кодера C, поэтому результат никогда не длиннее жадного. Строгий режим декодера
+//! ([`crate::decoder::HeatshrinkDecoder::strict`]) такие ссылки считает ошибкой.
+//! Поток читается обычными [`crate::decoder::HeatshrinkDecoder`] / `DynamicDecoder`.
+//!
+//! Память: кроме входа и результата - копия окна и входа, 9 байт на байт входа (ссылка,
+//! стоимость хвоста и выбор в каждой позиции) и таблицы поиска по `4 * (2 << window_bits)`
+//! байт, две штуки. Для окна 2^8: 4 КиБ таблиц и около 11 КиБ на 1 КиБ входа.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{dictionary, valid_params};
+
+const NONE: u32 = u32::MAX;
+
+/// Запись битов от старшего к младшему, хвост дополняется нулями
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u8,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn push(&mut self, value: u16, count: u8) {
+        for i in (0..count).rev() {
+            self.acc = (self.acc << 1) | ((value >> i) & 1) as u8;
+            self.bits += 1;
+            if self.bits == 8 {
+                self.out.push(self.acc);
+                self.acc = 0;
+                self.bits = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push(self.acc << (8 - self.bits));
+        }
+        self.out
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Match {
+    len: u16,
+    offset: u16,
+}
+
+// Самая длинная ссылка для каждой позиции data[start..], история - data[..start]
+fn longest_matches(data: &[u8], start: usize, window_bits: u8, lookahead_bits: u8) -> Vec<Match> {
+    let window = 1usize << window_bits;
+    let max_len = 1usize << lookahead_bits;
+    // цепочки позиций с тем же хешем двух байт, от ближней к дальней. Таблицы по размеру окна:
+    // дальше окна цепочка не идет, и prev[] - кольцо, ячейку p перезапишет только p + 2 * window
+    let table_bits = window_bits as u32 + 1;
+    let mask = (2 << window_bits) - 1;
+    let mut head = vec![NONE; 2 << window_bits];
+    let mut prev = vec![NONE; 2 << window_bits];
+    // последняя позиция каждого байта - для ссылок длины 1
+    let mut last = [NONE; 256];
+    let key = |p: usize| {
+        let pair = (data[p] as u32) << 8 | data[p + 1] as u32;
+        (pair.wrapping_mul(0x9e37_79b1) >> (32 - table_bits)) as usize
+    };
+
+    let mut res = Vec::with_capacity(data.len() - start);
+    for p in 0..data.len() {
+        let has_key = p + 1 < data.len();
+        if p >= start {
+            let limit = max_len.min(data.len() - p);
+            let mut best = Match::default();
+            let mut cand = if has_key { head[key(p)] } else { NONE };
+            while cand != NONE && p - cand as usize <= window {
+                let c = cand as usize;
+                let len = (0..limit)
+                    .take_while(|&k| data[c + k] == data[p + k])
+                    .count();
+                if len > best.len as usize {
+                    best = Match {
+                        len: len as u16,
+                        offset: (p - c) as u16,
+                    };
+                    if len == limit {
+                        break;
+                    }
+                }
+                cand = prev[c & mask];
+            }
+            let l = last[data[p] as usize];
+            if best.len == 0 && l != NONE && p - l as usize <= window {
+                best = Match {
+                    len: 1,
+                    offset: (p - l as usize) as u16,
+                };
+            }
+            res.push(best);
+        }
+        if has_key {
+            prev[p & mask] = head[key(p)];
+            head[key(p)] = p as u32;
+        }
+        last[data[p] as usize] = p as u32;
+    }
+    res
+}
+
+// history - конец окна перед src (словарь или уже сжатое), остальное окно - нули
+fn encode(history: &[u8], src: &[u8], window_bits: u8, lookahead_bits: u8) -> Vec<u8> {
+    let window = 1usize << window_bits;
+    let history = &history[history.len().saturating_sub(window)..];
+    let mut data = Vec::with_capacity(window + src.len());
+    data.resize(window - history.len(), 0);
+    data.extend_from_slice(history);
+    data.extend_from_slice(src);
+    let data = &data[..];
+    let start = window;
+
+    let matches = longest_matches(data, start, window_bits, lookahead_bits);
+    let n = matches.len();
+    let backref = 1 + window_bits as u32 + lookahead_bits as u32;
+
+    // cost[i] - минимум бит на data[start + i..], use_ref[i] - выбрана ли ссылка
+    let mut cost = vec![0u32; n + 1];
+    let mut use_ref = vec![false; n];
+    for i in (0..n).rev() {
+        cost[i] = 9 + cost[i + 1];
+        let m = matches[i];
+        if m.len > 0 && backref + cost[i + m.len as usize] < cost[i] {
+            cost[i] = backref + cost[i + m.len as usize];
+            use_ref[i] = true;
+        }
+    }
+
+    let mut out = BitWriter {
+        out: Vec::with_capacity((cost[0] as usize).div_ceil(8)),
+        acc: 0,
+        bits: 0,
+    };
+    let mut i = 0;
+    while i < n {
+        if use_ref[i] {
+            let m = matches[i];
+            out.push(0, 1);
+            out.push(m.offset - 1, window_bits);
+            out.push(m.len - 1, lookahead_bits);
+            i += m.len as usize;
+        } else {
+            out.push(1, 1);
+            out.push(data[start + i] as u16, 8);
+            i += 1;
+        }
+    }
+    out.finish()
+}
+
+/// Сжать `src`, None - недопустимые параметры, см. [`crate::valid_params`]
+pub fn compress(src: &[u8], window_bits: u8, lookahead_bits: u8) -> Option<Vec<u8>> {
+    if !valid_params(window_bits, lookahead_bits) {
+        return None;
+    }
+    Some(encode(&[], src, window_bits, lookahead_bits))
+}
+
+/// Сжать `src` с предустановленным словарем, см. [`crate::dictionary`]
+pub fn compress_with_dictionary(
+    src: &[u8],
+    dict: &[u8],
+    window_bits: u8,
+    lookahead_bits: u8,
+) -> Option<Vec<u8>> {
+    if !valid_params(window_bits, lookahead_bits) {
+        return None;
+    }
+    Some(encode(
+        dictionary::tail(dict, window_bits),
+        src,
+        window_bits,
+        lookahead_bits,
+    ))
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::decoder::HeatshrinkDecoder;
+    use crate::encoder::HeatshrinkEncoder;
+    use crate::optimal::{compress, compress_with_dictionary};
+    use crate::{STATIC_LOOKAHEAD_BITS, STATIC_WINDOW_BITS};
+
+    fn optimal(src: &[u8]) -> Vec<u8> {
+        compress(src, STATIC_WINDOW_BITS, STATIC_LOOKAHEAD_BITS).unwrap()
+    }
+
+    fn decode(packed: &[u8], len: usize) -> Vec<u8> {
+        let mut decoder = HeatshrinkDecoder::source(packed.iter().cloned()).with_limit(len);
+        let res = decoder.by_ref().collect::<Vec<_>>();
+        assert_eq!(decoder.error(), None);
+        res
+    }
+
+    #[test]
+    fn smaller_than_greedy() {
+        let text = include_bytes!("encoder_common.rs");
+        // с нулями в начале: оба кодера ссылаются на нули окна до начала данных
+        let table = (0..5000u32)
+            .flat_map(|n| (n * n / 13).to_le_bytes())
+            .collect::<Vec<_>>();
+        for src in [&text[..], &table[..], b"abababababcabcabcabc", &[0u8; 100]] {
+            let packed = optimal(src);
+            let greedy = HeatshrinkEncoder::source(src.iter().cloned()).collect::<Vec<_>>();
+            assert!(
+                packed.len() <= greedy.len(),
+                "{} {}",
+                packed.len(),
+                greedy.len()
+            );
+            assert_eq!(decode(&packed, src.len()), src);
+        }
+    }
+
+    #[test]
+    fn edge_cases() {
+        assert_eq!(optimal(b""), b"");
+        assert_eq!(decode(&optimal(b"a"), 1), b"a");
+        // длина ссылки ограничена 1 << lookahead_bits, ссылки перекрываются
+        let zeros = [0u8; 1000];
+        assert_eq!(decode(&optimal(&zeros), 1000), zeros);
+        assert!(optimal(&zeros).len() < 1000 / 16 * 2 + 2);
+        assert_eq!(compress(b"abc", 3, 2), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn dynamic_params() {
+        use std::io::Write;
+
+        use crate::io::DecoderWriter;
+
+        let src = include_bytes!("decoder.rs");
+        for (window_bits, lookahead_bits) in [(4, 3), (10, 5), (15, 14)] {
+            let packed = compress(src, window_bits, lookahead_bits).unwrap();
+            let mut decoder = DecoderWriter::new(Vec::new(), window_bits, lookahead_bits)
+                .unwrap()
+                .strict();
+            decoder.write_all(&packed).unwrap();
+            let res = decoder.finish().unwrap();
+            assert_eq!(res, &src[..], "-w {} -l {}", window_bits, lookahead_bits);
+        }
+    }
+
+    #[test]
+    fn with_dictionary() {
+        let dict = br#"{"id":,"temperature":,"humidity":,"status":"ok"}"#;
+        let src = br#"{"id":17,"temperature":21.5,"humidity":40,"status":"ok"}"#;
+        let packed =
+            compress_with_dictionary(src, dict, STATIC_WINDOW_BITS, STATIC_LOOKAHEAD_BITS).unwrap();
+        assert!(packed.len() < optimal(src).len() * 2 / 3);
+        let decoded =
+            HeatshrinkDecoder::with_dictionary(packed.iter().cloned(), dict).collect::<Vec<_>>();
+        assert_eq!(decoded, src);
+    }
+}
@@ -0,0 +1,155 @@
+use alloc::vec::Vec;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::decoder::HeatshrinkDecoder;
+use crate::encoder::HeatshrinkEncoder;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    // postcard не смог сериализовать значение
+    Serialize(postcard::Error),
+
+    // распакованные данные не разбираются postcard'ом
+    Deserialize(postcard::Error),
+
+    // упакованные данные не влезли в выходной буфер
+    Overflow,
+
+    // на входе нет даже байта версии
+    Empty,
+
+    // данные упакованы другой версией структуры
+    VersionMismatch { expected: u8, found: u8 },
+
+    // распакованные данные длиннее `limit` байт, см. unpack_with_limit()
+    OutputLimit { limit: usize },
+}
+
+/// Структура, которую можно сериализовать postcard'ом и сжать.
+///
+/// Формат: `[VERSION][heatshrink(postcard(self))]`.
+/// Обычно реализуется через `#[derive(HeatshrinkPacked)]` из heatshrink-rust-macro,
+/// версия задается атрибутом `#[heatshrink(version = N)]`.
+pub trait HeatshrinkPacked: Serialize + DeserializeOwned {
+    /// Версия раскладки структуры, при изменении полей ее нужно увеличить
+    const VERSION: u8;
+
+    /// Упаковать в `dest`, возвращает количество записанных байт
+    fn pack(&self, dest: &mut [u8]) -> Result<usize, Error> {
+        pack(self, Self::VERSION, dest)
+    }
+
+    /// Распаковать то, что было записано [`HeatshrinkPacked::pack`]
+    fn unpack(src: &[u8]) -> Result<Self, Error> {
+        unpack(Self::VERSION, src)
+    }
+
+    /// [`HeatshrinkPacked::unpack`] из недоверенного источника, см. [`unpack_with_limit`]
+    fn unpack_with_limit(src: &[u8], max_output: usize) -> Result<Self, Error> {
+        unpack_with_limit(Self::VERSION, src, max_output)
+    }
+}
+
+pub fn pack<T: Serialize + ?Sized>(
+    value: &T,
+    version: u8,
+    dest: &mut [u8],
+) -> Result<usize, Error> {
+    let (header, body) = dest.split_first_mut().ok_or(Error::Overflow)?;
+    *header = version;
+
+    let serialized = postcard::to_allocvec(value).map_err(Error::Serialize)?;
+
+    let mut writen = 0;
+    for b in HeatshrinkEncoder::source(serialized.into_iter()) {
+        *body.get_mut(writen).ok_or(Error::Overflow)? = b;
+        writen += 1;
+    }
+
+    Ok(1 + writen)
+}
+
+pub fn unpack<T: DeserializeOwned>(version: u8, src: &[u8]) -> Result<T, Error> {
+    unpack_with_limit(version, src, usize::MAX)
+}
+
+/// [`unpack`], но сериализованное значение не больше `max_output` байт, дальше -
+/// [`Error::OutputLimit`]: пара сотен байт на входе не распакуется в мегабайты
+pub fn unpack_with_limit<T: DeserializeOwned>(
+    version: u8,
+    src: &[u8],
+    max_output: usize,
+) -> Result<T, Error> {
+    let (&found, body) = src.split_first().ok_or(Error::Empty)?;
+    if found != version {
+        return Err(Error::VersionMismatch {
+            expected: version,
+            found,
+        });
+    }
+
+    let mut decoder = HeatshrinkDecoder::source(body.iter().cloned()).with_limit(max_output);
+    let serialized = decoder.by_ref().collect::<Vec<_>>();
+    if decoder.error().is_some() {
+        return Err(Error::OutputLimit { limit: max_output });
+    }
+    postcard::from_bytes(&serialized).map_err(Error::Deserialize)
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::packed::{pack, unpack, unpack_with_limit, Error};
+
+    #[test]
+    fn pack_unpack() {
+        let value = (0x1234_5678u32, [7u8; 32], -15i16);
+        let mut buf = [0u8; 64];
+
+        let size = pack(&value, 1, &mut buf).unwrap();
+        assert_eq!(buf[0], 1);
+
+        let unpacked: (u32, [u8; 32], i16) = unpack(1, &buf[..size]).unwrap();
+        assert_eq!(unpacked, value);
+    }
+
+    #[test]
+    fn version_mismatch() {
+        let mut buf = [0u8; 16];
+        let size = pack(&42u32, 1, &mut buf).unwrap();
+
+        assert_eq!(
+            unpack::<u32>(2, &buf[..size]),
+            Err(Error::VersionMismatch {
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(unpack::<u32>(2, &[]), Err(Error::Empty));
+    }
+
+    #[test]
+    fn output_limit() {
+        let value = [0u8; 1000];
+        let mut buf = [0u8; 256];
+        let size = pack(&value[..], 1, &mut buf).unwrap();
+
+        // postcard: длина среза varint'ом + байты
+        let unpacked: Vec<u8> = unpack_with_limit(1, &buf[..size], 1002).unwrap();
+        assert_eq!(unpacked, value);
+        assert_eq!(
+            unpack_with_limit::<Vec<u8>>(1, &buf[..size], 1001),
+            Err(Error::OutputLimit { limit: 1001 })
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let mut buf = [0u8; 4];
+        assert_eq!(pack(&[0x5au8; 32], 0, &mut buf), Err(Error::Overflow));
+        assert_eq!(pack(&0u8, 0, &mut []), Err(Error::Overflow));
+    }
+}
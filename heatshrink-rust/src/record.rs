@@ -0,0 +1,134 @@
+//! Записи фиксированного размера в сжатом потоке: упаковываются
+//! [`crate::encoder_to_vec::HeatshrinkEncoderToVec::push_record`], читаются [`RecordReader`].
+//!
+//! Запись - байты `T` как в памяти, без выравнивания и разделителей, так что читать надо
+//! тем же типом на машине с тем же порядком байт. Байты копятся во внутреннем буфере,
+//! пока не наберется целая запись, так что выравнивание источника не нужно.
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem::size_of;
+
+/// Итератор записей `T` поверх распакованных байт, например [`crate::decoder::HeatshrinkDecoder`]
+/// или `chunks.iter().flatten().copied()`
+pub struct RecordReader<T, I> {
+    src: I,
+    partial: Vec<u8>,
+    _record: PhantomData<T>,
+}
+
+impl<T: bytemuck::AnyBitPattern, I: Iterator<Item = u8>> RecordReader<T, I> {
+    /// Паника, если `T` нулевого размера
+    pub fn new(src: I) -> Self {
+        assert!(size_of::<T>() > 0, "zero-sized record");
+        Self {
+            src,
+            partial: Vec::with_capacity(size_of::<T>()),
+            _record: PhantomData,
+        }
+    }
+
+    /// Байты неполной записи в конце потока: не пусто - поток обрезан или не того типа
+    pub fn remainder(&self) -> &[u8] {
+        &self.partial
+    }
+
+    pub fn into_inner(self) -> I {
+        self.src
+    }
+}
+
+impl<T: bytemuck::AnyBitPattern, I: Iterator<Item = u8>> Iterator for RecordReader<T, I> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        // после конца потока в partial остается хвост, его не трогаем
+        if self.partial.len() == size_of::<T>() {
+            self.partial.clear();
+        }
+        while self.partial.len() < size_of::<T>() {
+            self.partial.push(self.src.next()?);
+        }
+        Some(bytemuck::pod_read_unaligned(&self.partial))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.src.size_hint();
+        let buffered = self.partial.len() % size_of::<T>();
+        (
+            (lower + buffered) / size_of::<T>(),
+            upper.map(|u| (u + buffered) / size_of::<T>()),
+        )
+    }
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use bytemuck::{Pod, Zeroable};
+
+    use crate::decoder::HeatshrinkDecoder;
+    use crate::encoder_to_vec::{HeatshrinkEncoderToVec, Result};
+    use crate::record::RecordReader;
+
+    #[derive(Debug, PartialEq, Clone, Copy, Pod, Zeroable)]
+    #[repr(C)]
+    struct Sample {
+        time: u32,
+        value: i16,
+        channel: u8,
+        flags: u8,
+    }
+
+    fn samples() -> Vec<Sample> {
+        (0..300u32)
+            .map(|n| Sample {
+                time: n * 10,
+                value: (n as i16 % 50) * 3 - 70,
+                channel: (n % 4) as u8,
+                flags: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let src = samples();
+        let mut encoder = HeatshrinkEncoderToVec::dest(Vec::with_capacity(4096), 0);
+        for s in &src {
+            assert!(matches!(encoder.push_record(s), Result::Ok));
+        }
+        assert!(matches!(encoder.finish(), Result::Done));
+        let packed = encoder.result();
+        assert!(packed.len() < src.len() * 8);
+
+        let mut reader =
+            RecordReader::<Sample, _>::new(HeatshrinkDecoder::source(packed.iter().cloned()));
+        let res = reader.by_ref().collect::<Vec<_>>();
+        assert_eq!(res, src);
+        assert!(reader.remainder().is_empty());
+    }
+
+    #[test]
+    fn partial_records() {
+        // 7 байт на запись, поток с середины записи: в конце остается неполная
+        let src = (0..100u8).map(|n| [n; 7]).collect::<Vec<_>>();
+        let bytes = src.iter().flatten().copied().collect::<Vec<_>>();
+        let mut reader = RecordReader::<[u8; 7], _>::new(bytes[3..].iter().copied());
+        assert_eq!(reader.size_hint(), (src.len() - 1, Some(src.len() - 1)));
+        assert_eq!(reader.next(), Some([0, 0, 0, 0, 1, 1, 1]));
+        assert_eq!(reader.by_ref().count(), src.len() - 2);
+        assert_eq!(reader.remainder(), [99; 4]);
+        let reader = RecordReader::<[u8; 7], _>::new(bytes.iter().copied());
+        assert_eq!(reader.collect::<Vec<_>>(), src);
+
+        // обрезанный поток: целые записи и хвост
+        let mut reader = RecordReader::<u32, _>::new([1, 0, 0, 0, 2, 0].iter().copied());
+        assert_eq!(reader.next(), Some(1));
+        assert_eq!(reader.next(), None);
+        assert_eq!(reader.next(), None);
+        assert_eq!(reader.remainder(), [2, 0]);
+    }
+}
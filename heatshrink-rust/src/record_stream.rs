@@ -0,0 +1,481 @@
+//! Поток записей переменной длины, например сжатый лог: записи дописываются по одной,
+//! читаются по порядку, с точек синхронизации - с середины.
+//!
+//! ```text
+//! поток: блок*
+//! блок: varint(длина сжатых данных) | сжатые данные (отдельный поток heatshrink)
+//! распакованный блок: (varint(длина записи) | запись)*
+//! ```
+//! varint - LEB128: по 7 бит от младших, старший бит - продолжение, не больше 5 байт.
+//! Каждый блок сжимается с чистым окном, граница блока - точка синхронизации:
+//! [`RecordDecoder::seek_block`] переходит к блоку N по длинам, не распаковывая предыдущие.
+//! Блоки закрывает [`RecordEncoder::sync`] или каждые N записей
+//! ([`RecordEncoder::with_sync_interval`]), чем чаще - тем хуже сжатие.
+//! Поток, приходящий по частям, читает [`BlockDecoder`] - по блоку, как только блок пришел.
+//! Распакованный блок декодеры держат в памяти целиком, поэтому он ограничен
+//! [`DEFAULT_BLOCK_LIMIT`] (`with_limit`): без точек синхронизации весь поток - один блок.
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::decoder::HeatshrinkDecoder;
+use crate::encoder_common::Context;
+use crate::Level;
+
+pub(crate) const VARINT_MAX: usize = 5;
+
+/// Предел распакованного блока у декодеров по умолчанию, 1 МиБ
+pub const DEFAULT_BLOCK_LIMIT: usize = 1 << 20;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Error {
+    /// Блок или запись выходят за конец данных
+    Truncated,
+    /// varint длиннее 5 байт или больше u32
+    BadLength,
+    /// Блока с таким номером нет, в потоке всего `blocks`
+    NoBlock { blocks: usize },
+    /// Блок распаковывается больше чем в `limit` байт, см. [`RecordDecoder::with_limit`]
+    OutputLimit { limit: usize },
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Truncated => write!(f, "truncated block or record"),
+            Error::BadLength => write!(f, "bad varint length"),
+            Error::NoBlock { blocks } => write!(f, "no such block, stream has {}", blocks),
+            Error::OutputLimit { limit } => {
+                write!(f, "decompressed block exceeds {} bytes", limit)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+pub(crate) fn write_varint(out: &mut Vec<u8>, mut v: u32) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+// (значение, следующая позиция)
+pub(crate) fn read_varint(src: &[u8], pos: usize) -> Result<(usize, usize), Error> {
+    let mut v = 0u64;
+    for i in 0..VARINT_MAX {
+        let b = *src.get(pos + i).ok_or(Error::Truncated)?;
+        v |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 {
+            let v = u32::try_from(v).map_err(|_| Error::BadLength)?;
+            return Ok((v as usize, pos + i + 1));
+        }
+    }
+    Err(Error::BadLength)
+}
+
+pub struct RecordEncoder {
+    ctx: Context,
+    // сжатые данные текущего блока, длина станет известна при закрытии
+    block: Vec<u8>,
+    in_block: usize,
+    // готовые блоки, еще не забранные take_output()
+    out: Vec<u8>,
+    sync_interval: Option<usize>,
+}
+
+impl Default for RecordEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecordEncoder {
+    /// Без точек синхронизации: один блок до [`RecordEncoder::finish`]
+    pub fn new() -> Self {
+        Self {
+            ctx: Context::new(Level::Default),
+            block: Vec::new(),
+            in_block: 0,
+            out: Vec::new(),
+            sync_interval: None,
+        }
+    }
+
+    /// Закрывать блок каждые `records` записей (0 - не закрывать)
+    pub fn with_sync_interval(mut self, records: usize) -> Self {
+        self.sync_interval = if records == 0 { None } else { Some(records) };
+        self
+    }
+
+    /// Паника, если запись длиннее u32::MAX
+    pub fn push(&mut self, record: &[u8]) {
+        let len = u32::try_from(record.len()).expect("record too long");
+        let mut prefix = Vec::with_capacity(VARINT_MAX);
+        write_varint(&mut prefix, len);
+        self.ctx.sink_all(&prefix, &mut self.block);
+        self.ctx.sink_all(record, &mut self.block);
+        self.in_block += 1;
+        if Some(self.in_block) == self.sync_interval {
+            self.sync();
+        }
+    }
+
+    /// Точка синхронизации: закрыть текущий блок, следующая запись - в новом.
+    /// Пустой блок не пишется
+    pub fn sync(&mut self) {
+        if self.in_block == 0 {
+            return;
+        }
+        self.ctx.finish_all(&mut self.block);
+        write_varint(&mut self.out, self.block.len() as u32);
+        self.out.append(&mut self.block);
+        self.in_block = 0;
+        self.ctx.reset();
+    }
+
+    /// Забрать закрытые блоки, например дописать в файл. Текущий блок остается в кодере
+    pub fn take_output(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.out)
+    }
+
+    /// Закрыть последний блок, остаток потока
+    pub fn finish(mut self) -> Vec<u8> {
+        self.sync();
+        self.out
+    }
+}
+
+/// Чтение потока [`RecordEncoder`]: [`RecordDecoder::next_record`] без копирования записи
+/// или итератор `Result<Vec<u8>, Error>`. После ошибки записей больше нет
+pub struct RecordDecoder<'a> {
+    src: &'a [u8],
+    // начало следующего блока в src
+    pos: usize,
+    block: Vec<u8>,
+    rp: usize,
+    limit: usize,
+}
+
+impl<'a> RecordDecoder<'a> {
+    pub fn new(src: &'a [u8]) -> Self {
+        Self {
+            src,
+            pos: 0,
+            block: Vec::new(),
+            rp: 0,
+            limit: DEFAULT_BLOCK_LIMIT,
+        }
+    }
+
+    /// Блок больше `max_block` байт в распакованном виде - [`Error::OutputLimit`]
+    /// (по умолчанию [`DEFAULT_BLOCK_LIMIT`])
+    pub fn with_limit(mut self, max_block: usize) -> Self {
+        self.limit = max_block;
+        self
+    }
+
+    // (начало, конец) сжатых данных блока с позиции pos
+    fn block_at(&self, pos: usize) -> Result<(usize, usize), Error> {
+        let (len, start) = read_varint(self.src, pos)?;
+        if len > self.src.len() - start {
+            return Err(Error::Truncated);
+        }
+        Ok((start, start + len))
+    }
+
+    /// Сколько блоков (точек синхронизации) в потоке
+    pub fn block_count(&self) -> Result<usize, Error> {
+        let mut pos = 0;
+        let mut count = 0;
+        while pos < self.src.len() {
+            pos = self.block_at(pos)?.1;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Читать с начала блока `n`, предыдущие не распаковываются
+    pub fn seek_block(&mut self, n: usize) -> Result<(), Error> {
+        let mut pos = 0;
+        for i in 0..n {
+            if pos == self.src.len() {
+                return Err(Error::NoBlock { blocks: i });
+            }
+            pos = self.block_at(pos)?.1;
+        }
+        self.pos = pos;
+        self.block.clear();
+        self.rp = 0;
+        Ok(())
+    }
+
+    /// Следующая запись, ссылка во внутренний буфер
+    pub fn next_record(&mut self) -> Option<Result<&[u8], Error>> {
+        match self.advance() {
+            Ok(Some((start, end))) => Some(Ok(&self.block[start..end])),
+            Ok(None) => None,
+            Err(e) => {
+                self.pos = self.src.len();
+                self.block.clear();
+                self.rp = 0;
+                Some(Err(e))
+            }
+        }
+    }
+
+    // границы следующей записи в self.block
+    fn advance(&mut self) -> Result<Option<(usize, usize)>, Error> {
+        while self.rp == self.block.len() {
+            if self.pos == self.src.len() {
+                return Ok(None);
+            }
+            let (start, end) = self.block_at(self.pos)?;
+            let mut decoder = HeatshrinkDecoder::source(self.src[start..end].iter().cloned())
+                .with_limit(self.limit);
+            self.block = decoder.by_ref().collect();
+            if decoder.error().is_some() {
+                return Err(Error::OutputLimit { limit: self.limit });
+            }
+            self.pos = end;
+            self.rp = 0;
+        }
+        let (len, start) = read_varint(&self.block, self.rp)?;
+        if len > self.block.len() - start {
+            return Err(Error::Truncated);
+        }
+        self.rp = start + len;
+        Ok(Some((start, self.rp)))
+    }
+}
+
+impl Iterator for RecordDecoder<'_> {
+    type Item = Result<Vec<u8>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().map(|r| r.map(|r| r.to_vec()))
+    }
+}
+
+/// Поток по частям, например захват с UART: байты передаются [`BlockDecoder::push`]
+/// по мере прихода, [`BlockDecoder::next_block`] отдает записи каждого пришедшего целиком блока
+pub struct BlockDecoder {
+    // данные, начиная с первого не отданного блока
+    buf: Vec<u8>,
+    limit: usize,
+}
+
+impl Default for BlockDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockDecoder {
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            limit: DEFAULT_BLOCK_LIMIT,
+        }
+    }
+
+    /// Предел распакованного блока, см. [`RecordDecoder::with_limit`]
+    pub fn with_limit(mut self, max_block: usize) -> Self {
+        self.limit = max_block;
+        self
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Записи следующего блока. None - блок еще не пришел целиком, нужны данные.
+    /// Ошибка - поток испорчен, дальше читать нельзя
+    pub fn next_block(&mut self) -> Option<Result<Vec<Vec<u8>>, Error>> {
+        let (len, start) = match read_varint(&self.buf, 0) {
+            Ok(header) => header,
+            Err(Error::Truncated) => return None,
+            Err(e) => return Some(Err(e)),
+        };
+        if len > self.buf.len() - start {
+            return None;
+        }
+        let end = start + len;
+        let records = RecordDecoder::new(&self.buf[..end])
+            .with_limit(self.limit)
+            .collect();
+        self.buf.drain(..end);
+        Some(records)
+    }
+
+    /// Конец потока: Err(Truncated), если последний блок недописан
+    pub fn finish(&self) -> Result<(), Error> {
+        if self.buf.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Truncated)
+        }
+    }
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    use std::format;
+    use std::vec::Vec;
+
+    use crate::record_stream::{
+        read_varint, write_varint, BlockDecoder, Error, RecordDecoder, RecordEncoder,
+        DEFAULT_BLOCK_LIMIT,
+    };
+
+    fn records() -> Vec<Vec<u8>> {
+        (0..500u32)
+            .map(|n| {
+                format!(
+                    "t={} sensor={} value={}{}",
+                    n * 100,
+                    n % 3,
+                    n * 7 % 101,
+                    "!".repeat((n % 200) as usize)
+                )
+                .into_bytes()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn varint() {
+        for v in [0, 1, 127, 128, 300, 16383, 16384, u32::MAX] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, v);
+            assert_eq!(read_varint(&buf, 0), Ok((v as usize, buf.len())));
+        }
+        assert_eq!(read_varint(&[0x80, 0x80], 0), Err(Error::Truncated));
+        assert_eq!(read_varint(&[0xff; 6], 0), Err(Error::BadLength));
+        assert_eq!(
+            read_varint(&[0xff, 0xff, 0xff, 0xff, 0x1f], 0),
+            Err(Error::BadLength)
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let src = records();
+        let mut encoder = RecordEncoder::new();
+        encoder.push(b"");
+        src.iter().for_each(|r| encoder.push(r));
+        let packed = encoder.finish();
+        let raw = src.iter().map(|r| r.len() + 2).sum::<usize>();
+        assert!(packed.len() < raw / 3, "{} {}", packed.len(), raw);
+
+        let mut decoder = RecordDecoder::new(&packed);
+        assert_eq!(decoder.block_count(), Ok(1));
+        assert_eq!(decoder.next_record(), Some(Ok(&b""[..])));
+        assert_eq!(decoder.map(Result::unwrap).collect::<Vec<_>>(), src);
+
+        assert_eq!(RecordEncoder::new().finish(), b"");
+        assert_eq!(RecordDecoder::new(b"").next(), None);
+    }
+
+    #[test]
+    fn sync_points() {
+        let src = records();
+        let mut encoder = RecordEncoder::new().with_sync_interval(100);
+        // дописывание по частям, как в файл
+        let mut packed = Vec::new();
+        for r in &src {
+            encoder.push(r);
+            packed.extend(encoder.take_output());
+        }
+        packed.extend(encoder.finish());
+
+        let mut decoder = RecordDecoder::new(&packed);
+        assert_eq!(decoder.block_count(), Ok(5));
+        for n in 0..5 {
+            decoder.seek_block(n).unwrap();
+            let res = decoder.by_ref().map(Result::unwrap).collect::<Vec<_>>();
+            assert_eq!(res, &src[n * 100..]);
+        }
+        decoder.seek_block(5).unwrap();
+        assert_eq!(decoder.next(), None);
+        assert_eq!(decoder.seek_block(6), Err(Error::NoBlock { blocks: 5 }));
+    }
+
+    #[test]
+    fn truncated() {
+        let mut encoder = RecordEncoder::new();
+        records().iter().for_each(|r| encoder.push(r));
+        let packed = encoder.finish();
+
+        let mut decoder = RecordDecoder::new(&packed[..packed.len() - 1]);
+        assert_eq!(decoder.block_count(), Err(Error::Truncated));
+        assert_eq!(decoder.next(), Some(Err(Error::Truncated)));
+        assert_eq!(decoder.next(), None);
+    }
+
+    #[test]
+    fn blocks_in_parts() {
+        let src = records();
+        let mut encoder = RecordEncoder::new().with_sync_interval(100);
+        src.iter().for_each(|r| encoder.push(r));
+        let packed = encoder.finish();
+
+        // по 7 байт, как из порта
+        let mut decoder = BlockDecoder::new();
+        let mut res = Vec::new();
+        let mut blocks = 0;
+        for part in packed.chunks(7) {
+            decoder.push(part);
+            while let Some(block) = decoder.next_block() {
+                let block = block.unwrap();
+                assert_eq!(block.len(), 100);
+                res.extend(block);
+                blocks += 1;
+            }
+        }
+        assert_eq!(blocks, 5);
+        assert_eq!(res, src);
+        assert_eq!(decoder.finish(), Ok(()));
+
+        let mut decoder = BlockDecoder::new();
+        decoder.push(&packed[..packed.len() - 1]);
+        while let Some(block) = decoder.next_block() {
+            block.unwrap();
+        }
+        assert_eq!(decoder.finish(), Err(Error::Truncated));
+
+        let mut decoder = BlockDecoder::new();
+        decoder.push(&[0xff; 6]);
+        assert_eq!(decoder.next_block(), Some(Err(Error::BadLength)));
+    }
+
+    #[test]
+    fn block_limit() {
+        let src = records();
+        let mut encoder = RecordEncoder::new().with_sync_interval(100);
+        src.iter().for_each(|r| encoder.push(r));
+        let packed = encoder.finish();
+        let block = src[..100].iter().map(|r| r.len() + 2).sum::<usize>();
+        assert!(block < DEFAULT_BLOCK_LIMIT);
+
+        let mut decoder = RecordDecoder::new(&packed).with_limit(block);
+        assert_eq!(decoder.by_ref().take(100).count(), 100);
+        assert_eq!(
+            decoder.next(),
+            Some(Err(Error::OutputLimit { limit: block }))
+        );
+        assert_eq!(decoder.next(), None);
+
+        let mut decoder = BlockDecoder::new().with_limit(block);
+        decoder.push(&packed);
+        assert_eq!(decoder.next_block().unwrap().unwrap().len(), 100);
+        assert_eq!(
+            decoder.next_block(),
+            Some(Err(Error::OutputLimit { limit: block }))
+        );
+    }
+}
@@ -0,0 +1,492 @@
+//! Формат с произвольным доступом: вход режется на блоки по `block_size` байт, каждый
+//! сжимается отдельно, в заголовке - индекс концов блоков.
+//!
+//! ```text
+//! "HK" | (window_bits << 4) | lookahead_bits | флаги | block_size: u32 LE | size: u32 LE |
+//! blocks: u32 LE | конец блока i в сжатых данных: u32 LE * blocks | сжатые блоки
+//! ```
+//! Блок, который сжатием не уменьшается, записывается как есть: тогда в заголовке
+//! [`FLAG_STORED`], а у конца такого блока в индексе выставлен старший бит.
+//! Чтобы прочитать байт, распаковывается только его блок, до нужного места.
+//! Блоки независимы, с feature `rayon` они сжимаются и распаковываются параллельно,
+//! результат побайтно тот же.
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::decoder::HeatshrinkDecoder;
+use crate::encoder::HeatshrinkEncoder;
+use crate::{STATIC_LOOKAHEAD_BITS, STATIC_WINDOW_BITS};
+
+pub const MAGIC: [u8; 2] = *b"HK";
+const HEADER_SIZE: usize = 16;
+/// Есть несжатые блоки, старший бит в индексе - признак такого блока
+pub const FLAG_STORED: u8 = 0x01;
+const STORED: u32 = 1 << 31;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Error {
+    /// Данные кончились раньше заголовка или индекса
+    Truncated,
+    /// Не начинается с [`MAGIC`]
+    BadMagic,
+    /// Сжато не с параметрами основной библиотеки или неизвестные флаги
+    Unsupported,
+    /// Индекс не сходится с размером данных
+    BadIndex,
+    /// Блок распаковался не в тот размер
+    Corrupt { block: usize },
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Truncated => write!(f, "truncated header or index"),
+            Error::BadMagic => write!(f, "not a seekable heatshrink file"),
+            Error::Unsupported => write!(f, "unsupported parameters or flags"),
+            Error::BadIndex => write!(f, "block index does not match data"),
+            Error::Corrupt { block } => write!(f, "block {} is corrupt", block),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+fn read_u32(src: &[u8], pos: usize) -> Result<u32, Error> {
+    let b = src.get(pos..pos + 4).ok_or(Error::Truncated)?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+// (данные блока, записан ли как есть)
+fn compress_block(block: &[u8]) -> (Vec<u8>, bool) {
+    let packed = HeatshrinkEncoder::source(block.iter().cloned()).collect::<Vec<_>>();
+    if packed.len() < block.len() {
+        (packed, false)
+    } else {
+        (block.to_vec(), true)
+    }
+}
+
+// заголовок, индекс и блоки
+fn assemble(size: usize, block_size: u32, blocks: &[(Vec<u8>, bool)]) -> Vec<u8> {
+    let data_len = blocks.iter().map(|(b, _)| b.len()).sum::<usize>();
+    let any_stored = blocks.iter().any(|(_, stored)| *stored);
+    assert!(!any_stored || data_len < STORED as usize);
+
+    let mut res = Vec::with_capacity(HEADER_SIZE + 4 * blocks.len() + data_len);
+    res.extend_from_slice(&MAGIC);
+    res.push(STATIC_WINDOW_BITS << 4 | STATIC_LOOKAHEAD_BITS);
+    res.push(if any_stored { FLAG_STORED } else { 0 });
+    res.extend_from_slice(&block_size.to_le_bytes());
+    res.extend_from_slice(&(size as u32).to_le_bytes());
+    res.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+    let mut end = 0u32;
+    for (block, stored) in blocks {
+        end = u32::try_from(block.len())
+            .ok()
+            .and_then(|len| end.checked_add(len))
+            .expect("compressed data over 4 GiB");
+        let entry = if *stored { end | STORED } else { end };
+        res.extend_from_slice(&entry.to_le_bytes());
+    }
+    for (block, _) in blocks {
+        res.extend_from_slice(block);
+    }
+    res
+}
+
+/// Сжать `src` блоками по `block_size` байт, несжимаемые блоки - как есть
+pub fn compress(src: &[u8], block_size: u32) -> Vec<u8> {
+    assert!(block_size > 0);
+    assert!(src.len() <= u32::MAX as usize);
+
+    let blocks = src
+        .chunks(block_size as usize)
+        .map(compress_block)
+        .collect::<Vec<_>>();
+    assemble(src.len(), block_size, &blocks)
+}
+
+/// То же, что [`compress`], блоки сжимаются в пуле потоков rayon
+#[cfg(feature = "rayon")]
+pub fn compress_parallel(src: &[u8], block_size: u32) -> Vec<u8> {
+    use rayon::prelude::*;
+
+    assert!(block_size > 0);
+    assert!(src.len() <= u32::MAX as usize);
+
+    let blocks = src
+        .par_chunks(block_size as usize)
+        .map(compress_block)
+        .collect::<Vec<_>>();
+    assemble(src.len(), block_size, &blocks)
+}
+
+/// Чтение по произвольному смещению из того, что записано [`compress`].
+/// Данные не копируются, подходит для таблиц во flash
+pub struct SeekableReader<'a> {
+    // маска признака несжатого блока в индексе, 0 без FLAG_STORED
+    stored_mask: u32,
+    block_size: usize,
+    size: usize,
+    index: &'a [u8],
+    data: &'a [u8],
+    // позиция для std::io::Read / Seek
+    #[cfg(feature = "std")]
+    pos: u64,
+}
+
+impl<'a> SeekableReader<'a> {
+    pub fn new(src: &'a [u8]) -> Result<Self, Error> {
+        if src.len() < HEADER_SIZE {
+            return Err(Error::Truncated);
+        }
+        if src[..2] != MAGIC {
+            return Err(Error::BadMagic);
+        }
+        if src[2] != STATIC_WINDOW_BITS << 4 | STATIC_LOOKAHEAD_BITS || src[3] & !FLAG_STORED != 0 {
+            return Err(Error::Unsupported);
+        }
+        let block_size = read_u32(src, 4)? as usize;
+        let size = read_u32(src, 8)? as usize;
+        let blocks = read_u32(src, 12)? as usize;
+        if block_size == 0 || blocks != size.div_ceil(block_size) {
+            return Err(Error::BadIndex);
+        }
+
+        // blocks из заголовка: на 32-битной цели 4 * blocks может переполниться
+        let index_end = blocks
+            .checked_mul(4)
+            .and_then(|n| n.checked_add(HEADER_SIZE))
+            .ok_or(Error::BadIndex)?;
+        let index = src.get(HEADER_SIZE..index_end).ok_or(Error::Truncated)?;
+        let data = &src[index_end..];
+        let stored_mask = if src[3] & FLAG_STORED != 0 { STORED } else { 0 };
+        let mut prev = 0;
+        for i in 0..blocks {
+            let end = (read_u32(index, 4 * i)? & !stored_mask) as usize;
+            if end < prev || end > data.len() {
+                return Err(Error::BadIndex);
+            }
+            prev = end;
+        }
+
+        Ok(Self {
+            stored_mask,
+            block_size,
+            size,
+            index,
+            data,
+            #[cfg(feature = "std")]
+            pos: 0,
+        })
+    }
+
+    /// Размер исходных данных
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn block_count(&self) -> usize {
+        self.index.len() / 4
+    }
+
+    fn entry(&self, block: usize) -> u32 {
+        read_u32(self.index, 4 * block).unwrap()
+    }
+
+    // данные блока, индекс проверен в new()
+    fn block(&self, block: usize) -> &'a [u8] {
+        let end = |i: usize| (self.entry(i) & !self.stored_mask) as usize;
+        let start = if block == 0 { 0 } else { end(block - 1) };
+        &self.data[start..end(block)]
+    }
+
+    // блок записан как есть
+    fn stored(&self, block: usize) -> bool {
+        self.entry(block) & self.stored_mask != 0
+    }
+
+    // исходный размер блока
+    fn block_len(&self, block: usize) -> usize {
+        self.block_size.min(self.size - block * self.block_size)
+    }
+
+    fn decompress_block(&self, block: usize) -> Result<Vec<u8>, Error> {
+        let len = self.block_len(block);
+        let res = if self.stored(block) {
+            self.block(block).to_vec()
+        } else {
+            HeatshrinkDecoder::source(self.block(block).iter().cloned())
+                .with_limit(len)
+                .collect::<Vec<_>>()
+        };
+        if res.len() != len {
+            return Err(Error::Corrupt { block });
+        }
+        Ok(res)
+    }
+
+    /// Распаковать все данные
+    pub fn decompress(&self) -> Result<Vec<u8>, Error> {
+        let mut res = Vec::with_capacity(self.size);
+        for block in 0..self.block_count() {
+            res.extend_from_slice(&self.decompress_block(block)?);
+        }
+        Ok(res)
+    }
+
+    /// То же, что [`Self::decompress`], блоки распаковываются в пуле потоков rayon
+    #[cfg(feature = "rayon")]
+    pub fn decompress_parallel(&self) -> Result<Vec<u8>, Error> {
+        use rayon::prelude::*;
+
+        let blocks = (0..self.block_count())
+            .into_par_iter()
+            .map(|block| self.decompress_block(block))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut res = Vec::with_capacity(self.size);
+        for block in blocks {
+            res.extend_from_slice(&block);
+        }
+        Ok(res)
+    }
+
+    /// Прочитать с `offset` сколько влезет в `buf`, вернуть сколько прочитано
+    /// (меньше `buf.len()` только в конце данных)
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, Error> {
+        if offset >= self.size {
+            return Ok(0);
+        }
+        let len = buf.len().min(self.size - offset);
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done;
+            let block = pos / self.block_size;
+            let skip = pos % self.block_size;
+            let block_len = self.block_len(block);
+            let want = (len - done).min(block_len - skip);
+
+            if self.stored(block) {
+                let data = self.block(block);
+                if data.len() != block_len {
+                    return Err(Error::Corrupt { block });
+                }
+                buf[done..done + want].copy_from_slice(&data[skip..skip + want]);
+                done += want;
+                continue;
+            }
+            let mut decoder = HeatshrinkDecoder::source(self.block(block).iter().cloned())
+                .with_limit(block_len)
+                .skip(skip);
+            for b in &mut buf[done..done + want] {
+                *b = decoder.next().ok_or(Error::Corrupt { block })?;
+            }
+            done += want;
+        }
+        Ok(done)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Read for SeekableReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.size as u64 {
+            return Ok(0);
+        }
+        let n = self
+            .read_at(self.pos as usize, buf)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Seek for SeekableReader<'_> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            std::io::SeekFrom::Start(n) => Some(n),
+            std::io::SeekFrom::End(n) => (self.size as u64).checked_add_signed(n),
+            std::io::SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+        };
+        self.pos = pos.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "seek before start of data",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::seekable::{compress, Error, SeekableReader, FLAG_STORED};
+
+    fn table() -> Vec<u8> {
+        (0..10_000u32)
+            .flat_map(|n| (n * n / 7).to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn read_at() {
+        let src = table();
+        let packed = compress(&src, 512);
+        assert!(packed.len() < src.len());
+
+        let reader = SeekableReader::new(&packed).unwrap();
+        assert_eq!(reader.len(), src.len());
+        assert_eq!(reader.block_count(), src.len().div_ceil(512));
+
+        let mut buf = [0u8; 700];
+        for offset in [0, 1, 511, 512, 1000, 20_000, src.len() - 700] {
+            assert_eq!(reader.read_at(offset, &mut buf), Ok(700));
+            assert_eq!(buf[..], src[offset..offset + 700], "{}", offset);
+        }
+        // хвост
+        assert_eq!(reader.read_at(src.len() - 10, &mut buf), Ok(10));
+        assert_eq!(buf[..10], src[src.len() - 10..]);
+        assert_eq!(reader.read_at(src.len(), &mut buf), Ok(0));
+        assert_eq!(reader.read_at(usize::MAX, &mut buf), Ok(0));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn read_seek() {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let src = b"seekable ".repeat(300);
+        let packed = compress(&src, 100);
+        let mut reader = SeekableReader::new(&packed).unwrap();
+
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).unwrap();
+        assert_eq!(all, src);
+
+        let mut buf = [0u8; 8];
+        assert_eq!(reader.seek(SeekFrom::Start(1000)).unwrap(), 1000);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[..], src[1000..1008]);
+        assert_eq!(reader.seek(SeekFrom::Current(-4)).unwrap(), 1004);
+        assert_eq!(
+            reader.seek(SeekFrom::End(-8)).unwrap(),
+            src.len() as u64 - 8
+        );
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[..], src[src.len() - 8..]);
+        assert!(reader.seek(SeekFrom::Current(-10_000)).is_err());
+    }
+
+    #[test]
+    fn decompress() {
+        let src = table();
+        let packed = compress(&src, 1000);
+        let reader = SeekableReader::new(&packed).unwrap();
+        assert_eq!(reader.decompress(), Ok(src));
+
+        // конец первого блока сдвинут: индекс цел, а блок распаковывается не целиком
+        let mut bad = compress(b"0123456789", 4);
+        bad[16..20].copy_from_slice(&1u32.to_le_bytes());
+        let reader = SeekableReader::new(&bad).unwrap();
+        assert_eq!(reader.decompress(), Err(Error::Corrupt { block: 0 }));
+    }
+
+    #[test]
+    fn stored_blocks() {
+        // сжимаемое вперемешку с несжимаемым
+        let mut src = Vec::new();
+        for n in 0..8u32 {
+            if n % 2 == 0 {
+                src.extend(b"compressible ".repeat(40).iter().take(512));
+            } else {
+                let mut x = n.wrapping_mul(0x9e37_79b9);
+                src.extend((0..512).map(|_| {
+                    x ^= x << 13;
+                    x ^= x >> 17;
+                    x ^= x << 5;
+                    x as u8
+                }));
+            }
+        }
+        let packed = compress(&src, 512);
+        assert_eq!(packed[3], FLAG_STORED);
+        // несжатые блоки не растут
+        assert!(packed.len() < 16 + 4 * 8 + 4 * 512 + 4 * 200);
+
+        let reader = SeekableReader::new(&packed).unwrap();
+        assert_eq!(reader.decompress(), Ok(src.clone()));
+        let mut buf = [0u8; 700];
+        for offset in [0, 500, 1000, 1500, src.len() - 700] {
+            assert_eq!(reader.read_at(offset, &mut buf), Ok(700));
+            assert_eq!(buf[..], src[offset..offset + 700], "{}", offset);
+        }
+
+        // без несжатых блоков флага нет
+        assert_eq!(compress(&table(), 512)[3], 0);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel() {
+        use crate::seekable::compress_parallel;
+
+        let src = table();
+        for block_size in [1, 100, 4096, 1 << 20] {
+            let packed = compress_parallel(&src[..20_000], block_size);
+            assert_eq!(packed, compress(&src[..20_000], block_size));
+            let reader = SeekableReader::new(&packed).unwrap();
+            assert_eq!(reader.decompress_parallel(), reader.decompress());
+        }
+        let packed = compress_parallel(&src, 512);
+        let reader = SeekableReader::new(&packed).unwrap();
+        assert_eq!(reader.decompress_parallel(), Ok(src));
+    }
+
+    #[test]
+    fn invalid() {
+        let packed = compress(b"0123456789", 4);
+        assert_eq!(SeekableReader::new(&packed).unwrap().block_count(), 3);
+
+        assert!(matches!(
+            SeekableReader::new(&packed[..10]),
+            Err(Error::Truncated)
+        ));
+        assert!(matches!(
+            SeekableReader::new(b"XX\x84\x00000000000000"),
+            Err(Error::BadMagic)
+        ));
+        let mut bad = packed.clone();
+        bad.truncate(bad.len() - 1);
+        assert!(matches!(SeekableReader::new(&bad), Err(Error::BadIndex)));
+
+        // блоков на весь u32: на 32-битной цели индекс не помещается в адреса
+        let mut huge = packed[..16].to_vec();
+        huge[4..8].copy_from_slice(&1u32.to_le_bytes());
+        huge[8..16].copy_from_slice(&[0xff; 8]);
+        let expected = if usize::BITS == 32 {
+            Error::BadIndex
+        } else {
+            Error::Truncated
+        };
+        assert_eq!(SeekableReader::new(&huge).err(), Some(expected));
+
+        let empty = compress(b"", 4);
+        let reader = SeekableReader::new(&empty).unwrap();
+        assert!(reader.is_empty());
+        assert_eq!(reader.read_at(0, &mut [0u8; 4]), Ok(0));
+    }
+}
@@ -0,0 +1,168 @@
+//! Статистика сжатия: считается разбором выхода кодера, сам выход не меняется.
+
+use core::fmt;
+
+use crate::inspect::{StreamParser, TokenKind};
+use crate::{STATIC_LOOKAHEAD_BITS, STATIC_WINDOW_BITS};
+
+/// Максимальная длина ссылки назад
+pub const MAX_MATCH_LENGTH: usize = 1 << STATIC_LOOKAHEAD_BITS;
+/// Число корзин гистограммы смещений
+pub const OFFSET_BUCKETS: usize = STATIC_WINDOW_BITS as usize + 1;
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct Stats {
+    pub input_bytes: usize,
+    pub output_bytes: usize,
+    pub literals: usize,
+    pub backrefs: usize,
+    /// `match_lengths[n - 1]` - число ссылок длиной `n`
+    pub match_lengths: [u32; MAX_MATCH_LENGTH],
+    /// `offsets[i]` - число ссылок со смещением в `[2^i, 2^(i+1))`
+    pub offsets: [u32; OFFSET_BUCKETS],
+    /// Ссылки максимальной длины: совпадение могло быть длиннее lookahead
+    pub lookahead_saturated: usize,
+}
+
+impl Stats {
+    /// Сжатый размер / исходный
+    pub fn ratio(&self) -> f32 {
+        if self.input_bytes == 0 {
+            return 1.0;
+        }
+        self.output_bytes as f32 / self.input_bytes as f32
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} -> {} bytes, literals: {}, backrefs: {}, lookahead saturated: {}",
+            self.input_bytes,
+            self.output_bytes,
+            self.literals,
+            self.backrefs,
+            self.lookahead_saturated
+        )?;
+        f.write_str("lengths:")?;
+        for (i, n) in self.match_lengths.iter().enumerate() {
+            write!(f, " {}:{}", i + 1, n)?;
+        }
+        f.write_str("\noffsets:")?;
+        for (i, n) in self.offsets.iter().enumerate() {
+            write!(f, " {}..{}:{}", 1 << i, (2 << i) - 1, n)?;
+        }
+        Ok(())
+    }
+}
+
+/// Побайтный разбор выхода кодера со статическими параметрами
+pub(crate) struct Collector {
+    stats: Stats,
+    parser: StreamParser,
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Self {
+            stats: Stats::default(),
+            parser: StreamParser::new(STATIC_WINDOW_BITS, STATIC_LOOKAHEAD_BITS),
+        }
+    }
+}
+
+impl Collector {
+    pub(crate) fn push(&mut self, byte: u8) {
+        let stats = &mut self.stats;
+        stats.output_bytes += 1;
+        self.parser.push(byte, |token| match token {
+            TokenKind::Literal(_) => {
+                stats.literals += 1;
+                stats.input_bytes += 1;
+            }
+            TokenKind::BackRef { offset, length } => {
+                stats.backrefs += 1;
+                stats.input_bytes += length;
+                stats.match_lengths[length - 1] += 1;
+                stats.offsets[(usize::BITS - 1 - offset.leading_zeros()) as usize] += 1;
+                if length == MAX_MATCH_LENGTH {
+                    stats.lookahead_saturated += 1;
+                }
+            }
+        });
+    }
+
+    /// Точка flush, см. [`crate::encoder_to_vec::HeatshrinkEncoderToVec::flush`]
+    pub(crate) fn sync(&mut self) {
+        self.parser.sync();
+    }
+
+    /// Неразобранный остаток в конце - дополнение последнего байта нулями
+    pub(crate) fn stats(&self) -> &Stats {
+        &self.stats
+    }
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use alloc::vec::Vec;
+
+    use crate::encoder::HeatshrinkEncoder;
+    use crate::encoder_to_vec::{self, HeatshrinkEncoderToVec};
+    use crate::inspect::{tokens, TokenKind};
+    use crate::stats::MAX_MATCH_LENGTH;
+    use crate::{STATIC_LOOKAHEAD_BITS, STATIC_WINDOW_BITS};
+
+    #[test]
+    fn same_as_inspect() {
+        let src = b"statistics: literals, backrefs, lengths, offsets; ".repeat(20);
+        let mut encoder = HeatshrinkEncoder::source(src.iter().cloned());
+        let packed = encoder.by_ref().collect::<Vec<_>>();
+        let stats = *encoder.stats().unwrap();
+
+        assert_eq!(stats.input_bytes, src.len());
+        assert_eq!(stats.output_bytes, packed.len());
+
+        let mut tokens = tokens(&packed, STATIC_WINDOW_BITS, STATIC_LOOKAHEAD_BITS).unwrap();
+        let mut lengths = [0; MAX_MATCH_LENGTH];
+        for token in tokens.by_ref() {
+            if let TokenKind::BackRef { length, .. } = token.kind {
+                lengths[length - 1] += 1;
+            }
+        }
+        let summary = tokens.summary();
+        assert_eq!(stats.literals, summary.literals);
+        assert_eq!(stats.backrefs, summary.backrefs);
+        assert_eq!(stats.match_lengths, lengths);
+        assert_eq!(stats.offsets.iter().sum::<u32>() as usize, stats.backrefs);
+        assert_eq!(
+            stats.lookahead_saturated,
+            lengths[MAX_MATCH_LENGTH - 1] as usize
+        );
+    }
+
+    #[test]
+    fn encoder_to_vec() {
+        let src = [7u8; 100];
+        let mut encoder = HeatshrinkEncoderToVec::dest(Vec::with_capacity(1024), 3);
+        encoder.push_bytes(&src);
+        assert!(encoder.stats().is_none());
+        assert!(matches!(encoder.finish(), encoder_to_vec::Result::Done));
+
+        let stats = *encoder.stats().unwrap();
+        let mut expected = HeatshrinkEncoder::source(src.iter().cloned());
+        expected.by_ref().for_each(drop);
+        assert_eq!(&stats, expected.stats().unwrap());
+
+        // один литерал и ссылки на него, почти все максимальной длины
+        assert_eq!(stats.input_bytes, 100);
+        assert_eq!(stats.output_bytes, encoder.result().len() - 3);
+        assert_eq!(stats.literals, 1);
+        assert_eq!(stats.offsets[0] as usize, stats.backrefs);
+        assert_eq!(stats.lookahead_saturated, 99 / MAX_MATCH_LENGTH);
+    }
+}
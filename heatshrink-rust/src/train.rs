@@ -0,0 +1,222 @@
+//! Построение словаря ([`crate::dictionary`]) по образцам сообщений.
+//!
+//! Жадный выбор фрагментов, как COVER у zstd: каждый d-грам (d - минимальная выгодная
+//! длина ссылки назад) весит столько, во скольких образцах он встречается. Образцы делятся
+//! на эпохи, из каждой берется фрагмент с наибольшим весом еще не покрытых d-грамов.
+//! Фрагмент не длиннее максимальной длины ссылки `1 << lookahead_bits`, самые ценные
+//! ложатся в конец словаря - ближе к началу данных, короче смещения.
+
+use std::collections::HashMap;
+use std::vec::Vec;
+
+use crate::dynamic::DynamicEncoder;
+
+// длиннее фрагменты редко повторяются целиком
+const MAX_SEGMENT: usize = 32;
+
+/// Сравнение сжатия образцов без словаря и со словарем, каждый образец сжимается отдельно
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub samples: usize,
+    pub input_size: usize,
+    pub dictionary_size: usize,
+    pub plain_size: usize,
+    pub with_dictionary_size: usize,
+}
+
+impl Evaluation {
+    /// Сжатый размер / исходный без словаря, меньше - лучше
+    pub fn plain_ratio(&self) -> f64 {
+        ratio(self.plain_size, self.input_size)
+    }
+
+    /// Сжатый размер / исходный со словарем
+    pub fn dictionary_ratio(&self) -> f64 {
+        ratio(self.with_dictionary_size, self.input_size)
+    }
+}
+
+fn ratio(compressed: usize, input: usize) -> f64 {
+    if input == 0 {
+        return 1.0;
+    }
+    compressed as f64 / input as f64
+}
+
+// Минимальная длина, с которой ссылка назад короче литералов (см. heatshrink_encoder.c)
+fn min_match(window_bits: u8, lookahead_bits: u8) -> usize {
+    (1 + window_bits as usize + lookahead_bits as usize) / 8 + 1
+}
+
+fn dmer(data: &[u8]) -> u64 {
+    data.iter().fold(0, |acc, b| acc << 8 | *b as u64)
+}
+
+/// Словарь не больше окна `1 << window_bits` из фрагментов образцов `samples`.
+/// None - недопустимые параметры, см. [`crate::valid_params`]
+pub fn train(samples: &[&[u8]], window_bits: u8, lookahead_bits: u8) -> Option<Vec<u8>> {
+    if !crate::valid_params(window_bits, lookahead_bits) {
+        return None;
+    }
+    let dict_size = 1usize << window_bits;
+    let d = min_match(window_bits, lookahead_bits);
+    let segment = (1usize << lookahead_bits).min(MAX_SEGMENT).max(d);
+
+    // в скольких образцах встречается d-грам; в одном - словарь не поможет
+    let mut weight = HashMap::<u64, u32>::new();
+    for sample in samples {
+        let mut seen = sample.windows(d).map(dmer).collect::<Vec<_>>();
+        seen.sort_unstable();
+        seen.dedup();
+        for key in seen {
+            *weight.entry(key).or_insert(0) += 1;
+        }
+    }
+    if samples.len() > 1 {
+        weight.retain(|_, w| *w > 1);
+    }
+
+    // начала всех возможных фрагментов
+    let starts = samples
+        .iter()
+        .enumerate()
+        .flat_map(|(i, s)| (0..=s.len().saturating_sub(segment)).map(move |p| (i, p)))
+        .filter(|(i, p)| samples[*i].len() >= p + d)
+        .collect::<Vec<_>>();
+    let epochs = (dict_size / segment).max(1);
+    let epoch_len = starts.len().div_ceil(epochs).max(1);
+
+    let mut chosen = Vec::new();
+    for epoch in starts.chunks(epoch_len) {
+        let best = epoch
+            .iter()
+            .map(|&(i, p)| {
+                let s = &samples[i][p..(p + segment).min(samples[i].len())];
+                let score = s
+                    .windows(d)
+                    .map(|w| *weight.get(&dmer(w)).unwrap_or(&0) as u64)
+                    .sum::<u64>();
+                (score, i, p)
+            })
+            .max_by_key(|(score, _, _)| *score);
+        if let Some((score, i, p)) = best.filter(|(score, _, _)| *score > 0) {
+            let s = &samples[i][p..(p + segment).min(samples[i].len())];
+            // покрытое не считается второй раз
+            for w in s.windows(d) {
+                weight.remove(&dmer(w));
+            }
+            chosen.push((score, s));
+        }
+    }
+
+    // самое ценное - в конец
+    chosen.sort_by_key(|(score, _)| *score);
+    let mut dict = chosen
+        .iter()
+        .flat_map(|(_, s)| s.iter().cloned())
+        .collect::<Vec<_>>();
+    if dict.len() > dict_size {
+        dict.drain(..dict.len() - dict_size);
+    }
+    Some(dict)
+}
+
+fn compressed_size(encoder: &mut DynamicEncoder, mut src: &[u8], dict: &[u8]) -> usize {
+    let mut buf = [0u8; 1024];
+    let mut res = 0;
+    encoder.reset();
+    encoder.set_dictionary(dict);
+    loop {
+        src = &src[encoder.sink(src)..];
+        if src.is_empty() && encoder.finish() {
+            return res;
+        }
+        loop {
+            let (n, more) = encoder.poll(&mut buf);
+            res += n;
+            if !more {
+                break;
+            }
+        }
+    }
+}
+
+/// Сжать каждый образец без словаря и со словарем `dict`.
+/// None - недопустимые параметры
+pub fn evaluate(
+    samples: &[&[u8]],
+    dict: &[u8],
+    window_bits: u8,
+    lookahead_bits: u8,
+) -> Option<Evaluation> {
+    let mut encoder = DynamicEncoder::new(window_bits, lookahead_bits)?;
+    let mut res = Evaluation {
+        samples: samples.len(),
+        input_size: 0,
+        dictionary_size: dict.len().min(1 << window_bits),
+        plain_size: 0,
+        with_dictionary_size: 0,
+    };
+    for sample in samples {
+        res.input_size += sample.len();
+        res.plain_size += compressed_size(&mut encoder, sample, &[]);
+        res.with_dictionary_size += compressed_size(&mut encoder, sample, dict);
+    }
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::format;
+    use std::vec::Vec;
+
+    use crate::train::{evaluate, train};
+
+    fn samples() -> Vec<Vec<u8>> {
+        (0..200u32)
+            .map(|n| {
+                format!(
+                    r#"{{"device":"sensor-{}","temperature":{}.{},"humidity":{},"status":"{}"}}"#,
+                    n % 17,
+                    15 + n % 13,
+                    n % 10,
+                    30 + n % 41,
+                    if n % 9 == 0 { "alarm" } else { "ok" }
+                )
+                .into_bytes()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn train_json() {
+        let samples = samples();
+        let samples = samples.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+        for (window_bits, lookahead_bits) in [(8, 4), (10, 5), (6, 3)] {
+            let dict = train(&samples[..150], window_bits, lookahead_bits).unwrap();
+            assert!(!dict.is_empty() && dict.len() <= 1 << window_bits);
+
+            // на образцах, которых не было при обучении
+            let eval = evaluate(&samples[150..], &dict, window_bits, lookahead_bits).unwrap();
+            assert_eq!(eval.samples, 50);
+            assert!(
+                eval.dictionary_ratio() < eval.plain_ratio() * 0.7,
+                "-w {} -l {}: {:?}",
+                window_bits,
+                lookahead_bits,
+                eval
+            );
+        }
+    }
+
+    #[test]
+    fn degenerate() {
+        assert_eq!(train(&[b"abc"], 3, 2), None);
+        assert_eq!(train(&[], 8, 4), Some(Vec::new()));
+        assert_eq!(train(&[b""], 8, 4), Some(Vec::new()));
+        // одного образца хватает, повторы внутри него
+        let dict = train(&[b"abcabcabcabc"], 8, 4).unwrap();
+        assert!(!dict.is_empty());
+    }
+}
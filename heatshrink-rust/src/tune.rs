@@ -0,0 +1,204 @@
+//! Подбор window/lookahead по образцам данных: сжимаем образцы всеми допустимыми
+//! комбинациями и сравниваем степень сжатия, расход памяти и скорость.
+
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+use crate::dynamic::{DynamicDecoder, DynamicEncoder};
+use crate::{
+    decoder_ram, encoder_ram, MAX_WINDOW_BITS, MIN_LOOKAHEAD_BITS, MIN_WINDOW_BITS,
+    STATIC_INPUT_BUFFER_SIZE,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub window_bits: u8,
+    pub lookahead_bits: u8,
+    /// Суммарный размер образцов
+    pub input_size: usize,
+    /// Суммарный размер сжатых образцов (каждый сжимается отдельно)
+    pub compressed_size: usize,
+    /// Память под контекст кодера, см. [`crate::encoder_ram`]
+    pub encoder_ram: usize,
+    /// Память под контекст декодера с входным буфером [`STATIC_INPUT_BUFFER_SIZE`]
+    pub decoder_ram: usize,
+    pub encode_time: Duration,
+    pub decode_time: Duration,
+    /// `encoder_ram + decoder_ram` не больше заданного бюджета
+    pub fits: bool,
+}
+
+impl Report {
+    /// Сжатый размер / исходный, меньше - лучше
+    pub fn ratio(&self) -> f64 {
+        if self.input_size == 0 {
+            return 1.0;
+        }
+        self.compressed_size as f64 / self.input_size as f64
+    }
+
+    /// Память кодера и декодера вместе
+    pub fn ram(&self) -> usize {
+        self.encoder_ram + self.decoder_ram
+    }
+
+    /// Байт исходных данных в секунду
+    pub fn encode_throughput(&self) -> f64 {
+        self.input_size as f64 / self.encode_time.as_secs_f64().max(1e-9)
+    }
+
+    /// Байт распакованных данных в секунду
+    pub fn decode_throughput(&self) -> f64 {
+        self.input_size as f64 / self.decode_time.as_secs_f64().max(1e-9)
+    }
+}
+
+fn encode(encoder: &mut DynamicEncoder, mut src: &[u8], dest: &mut Vec<u8>) {
+    let mut buf = [0u8; 1024];
+    encoder.reset();
+    loop {
+        src = &src[encoder.sink(src)..];
+        if src.is_empty() && encoder.finish() {
+            return;
+        }
+        loop {
+            let (n, more) = encoder.poll(&mut buf);
+            dest.extend_from_slice(&buf[..n]);
+            if !more {
+                break;
+            }
+        }
+    }
+}
+
+// возвращает размер распакованных данных
+fn decode(decoder: &mut DynamicDecoder, mut src: &[u8]) -> usize {
+    let mut buf = [0u8; 1024];
+    let mut res = 0;
+    decoder.reset();
+    loop {
+        src = &src[decoder.sink(src)..];
+        loop {
+            let (n, more) = decoder.poll(&mut buf);
+            res += n;
+            if !more {
+                break;
+            }
+        }
+        if src.is_empty() && decoder.finish() {
+            return res;
+        }
+    }
+}
+
+fn measure(samples: &[&[u8]], window_bits: u8, lookahead_bits: u8, max_ram: usize) -> Report {
+    let mut encoder = DynamicEncoder::new(window_bits, lookahead_bits).unwrap();
+    let mut decoder = DynamicDecoder::with_input_buffer(
+        STATIC_INPUT_BUFFER_SIZE as u16,
+        window_bits,
+        lookahead_bits,
+    )
+    .unwrap();
+
+    let start = Instant::now();
+    let compressed = samples
+        .iter()
+        .map(|sample| {
+            let mut res = Vec::with_capacity(sample.len());
+            encode(&mut encoder, sample, &mut res);
+            res
+        })
+        .collect::<Vec<_>>();
+    let encode_time = start.elapsed();
+
+    let start = Instant::now();
+    for (sample, compressed) in samples.iter().zip(compressed.iter()) {
+        assert_eq!(decode(&mut decoder, compressed), sample.len());
+    }
+    let decode_time = start.elapsed();
+
+    let encoder_ram = encoder_ram(window_bits);
+    let decoder_ram = decoder_ram(window_bits, STATIC_INPUT_BUFFER_SIZE);
+    Report {
+        window_bits,
+        lookahead_bits,
+        input_size: samples.iter().map(|s| s.len()).sum(),
+        compressed_size: compressed.iter().map(|c| c.len()).sum(),
+        encoder_ram,
+        decoder_ram,
+        encode_time,
+        decode_time,
+        fits: encoder_ram + decoder_ram <= max_ram,
+    }
+}
+
+/// Проверить все допустимые window/lookahead на образцах `samples`.
+///
+/// Результат отсортирован: сначала то, что помещается в `max_ram`
+/// (память кодера + декодера), далее по размеру сжатых данных, затем по памяти.
+/// Первый элемент с `fits == true` - рекомендация, см. [`recommend`].
+pub fn tune(samples: &[&[u8]], max_ram: usize) -> Vec<Report> {
+    let mut reports = Vec::new();
+    for window_bits in MIN_WINDOW_BITS..=MAX_WINDOW_BITS {
+        for lookahead_bits in MIN_LOOKAHEAD_BITS..window_bits {
+            reports.push(measure(samples, window_bits, lookahead_bits, max_ram));
+        }
+    }
+
+    reports.sort_by_key(|r| (!r.fits, r.compressed_size, r.ram(), r.window_bits));
+    reports
+}
+
+/// Лучшая конфигурация из результата [`tune`], влезающая в бюджет
+pub fn recommend(reports: &[Report]) -> Option<&Report> {
+    reports
+        .iter()
+        .filter(|r| r.fits)
+        .min_by_key(|r| (r.compressed_size, r.ram()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::tune::{recommend, tune};
+    use crate::{decoder_ram, encoder_ram, STATIC_INPUT_BUFFER_SIZE};
+
+    #[test]
+    fn all_params() {
+        let text = b"The quick brown fox jumps over the lazy dog. ".repeat(40);
+        let table = (0..2000u32)
+            .flat_map(|n| (n / 3).to_le_bytes())
+            .collect::<Vec<_>>();
+        let reports = tune(&[&text, &table], usize::MAX);
+
+        // 4..=15 окно, 3..окно lookahead
+        assert_eq!(reports.len(), 78);
+        assert!(reports.iter().all(|r| r.fits));
+        assert!(reports
+            .iter()
+            .all(|r| r.input_size == text.len() + table.len()));
+        assert!(reports
+            .windows(2)
+            .all(|w| w[0].compressed_size <= w[1].compressed_size));
+        assert!(reports[0].ratio() < 0.5);
+        assert_eq!(recommend(&reports), Some(&reports[0]));
+    }
+
+    #[test]
+    fn ram_budget() {
+        let text = b"abcdefgh".repeat(100);
+        let budget = encoder_ram(8) + decoder_ram(8, STATIC_INPUT_BUFFER_SIZE);
+        let reports = tune(&[&text], budget);
+
+        let best = recommend(&reports).unwrap();
+        assert!(best.window_bits <= 8);
+        assert!(best.ram() <= budget);
+        assert!(reports
+            .iter()
+            .filter(|r| r.window_bits > 8)
+            .all(|r| !r.fits));
+
+        assert_eq!(recommend(&tune(&[&text], 100)), None);
+    }
+}
@@ -0,0 +1,264 @@
+//! Проверка сжатого образа без распаковки в память: декодер прогоняется вхолостую,
+//! считаются размер, CRC-32 и ссылки раньше начала данных. Например, перед стиранием flash под OTA.
+
+#![allow(non_upper_case_globals)]
+
+use core::fmt;
+
+use crate::decoder::{
+    _heatshrink_decoder, heatshrink_decoder_finish, heatshrink_decoder_poll,
+    heatshrink_decoder_reset, heatshrink_decoder_sink, HSD_finish_res_HSDR_FINISH_DONE,
+    HSD_finish_res_HSDR_FINISH_MORE, HSD_poll_res_HSDR_POLL_EMPTY, HSD_poll_res_HSDR_POLL_MORE,
+    HSD_sink_res_HSDR_SINK_FULL, HSD_sink_res_HSDR_SINK_OK,
+};
+use crate::inspect::{StreamParser, TokenKind};
+use crate::{STATIC_LOOKAHEAD_BITS, STATIC_WINDOW_BITS};
+
+// CRC-32 (IEEE 802.3, как у zlib), таблица считается при компиляции
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+// crc без начальной/конечной инверсии
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for b in data {
+        crc = CRC32_TABLE[((crc ^ *b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+/// CRC-32 исходных данных, для сравнения с [`Summary::crc32`]
+pub fn crc32(data: &[u8]) -> u32 {
+    !crc32_update(!0, data)
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct Summary {
+    /// Размер распакованных данных
+    pub output_size: usize,
+    /// CRC-32 распакованных данных
+    pub crc32: u32,
+    pub literals: usize,
+    pub backrefs: usize,
+    /// Ссылки раньше начала данных: копируют нули из не заполненного окна.
+    /// Кодер heatshrink так сжимает нули в начале потока, но в чужом потоке это подозрительно
+    pub before_start: usize,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Error {
+    /// Поток оборван: после последнего целого токена `trailing_bits` бит,
+    /// а не дополнение последнего байта нулями
+    Truncated { trailing_bits: u8 },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Truncated { trailing_bits } => write!(
+                f,
+                "truncated stream: {} bits after the last token",
+                trailing_bits
+            ),
+        }
+    }
+}
+
+/// Потоковая проверка: данные кусками через [`Validator::push`], итог - [`Validator::finish`]
+pub struct Validator {
+    ctx: _heatshrink_decoder,
+    parser: StreamParser,
+    summary: Summary,
+    // позиция в распакованных данных по токенам
+    out_pos: usize,
+    crc: u32,
+}
+
+impl Default for Validator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        let mut res = Self {
+            ctx: _heatshrink_decoder::default(),
+            parser: StreamParser::new(STATIC_WINDOW_BITS, STATIC_LOOKAHEAD_BITS),
+            summary: Summary::default(),
+            out_pos: 0,
+            crc: !0,
+        };
+        unsafe {
+            heatshrink_decoder_reset(&mut res.ctx);
+        }
+        res
+    }
+
+    pub fn push(&mut self, mut data: &[u8]) {
+        for b in data {
+            self.parse(*b);
+        }
+
+        while !data.is_empty() {
+            let mut writen = 0;
+            // в биндингах *mut, но вход декодер не меняет
+            let ptr = data.as_ptr() as *mut u8;
+            match unsafe { heatshrink_decoder_sink(&mut self.ctx, ptr, data.len(), &mut writen) } {
+                // FULL: входной буфер полон, сначала poll()
+                HSD_sink_res_HSDR_SINK_OK | HSD_sink_res_HSDR_SINK_FULL => {}
+                _ => panic!(),
+            }
+            data = &data[writen..];
+            self.drain();
+        }
+    }
+
+    pub fn finish(mut self) -> Result<Summary, Error> {
+        loop {
+            match unsafe { heatshrink_decoder_finish(&mut self.ctx) } {
+                HSD_finish_res_HSDR_FINISH_DONE => break,
+                HSD_finish_res_HSDR_FINISH_MORE => self.drain(),
+                _ => panic!(),
+            }
+        }
+
+        match self.parser.trailing() {
+            (bits, 0) if bits < 8 => {}
+            (trailing_bits, _) => return Err(Error::Truncated { trailing_bits }),
+        }
+        self.summary.crc32 = !self.crc;
+        Ok(self.summary)
+    }
+
+    fn parse(&mut self, byte: u8) {
+        let summary = &mut self.summary;
+        let out_pos = &mut self.out_pos;
+        self.parser.push(byte, |token| match token {
+            TokenKind::Literal(_) => {
+                summary.literals += 1;
+                *out_pos += 1;
+            }
+            TokenKind::BackRef { offset, length } => {
+                summary.backrefs += 1;
+                if offset > *out_pos {
+                    summary.before_start += 1;
+                }
+                *out_pos += length;
+            }
+        });
+    }
+
+    // выход декодера только в CRC и счетчик
+    fn drain(&mut self) {
+        let mut buf = [0u8; 64];
+        loop {
+            let mut out_writen = 0;
+            let res = unsafe {
+                heatshrink_decoder_poll(&mut self.ctx, buf.as_mut_ptr(), buf.len(), &mut out_writen)
+            };
+            self.crc = crc32_update(self.crc, &buf[..out_writen]);
+            self.summary.output_size += out_writen;
+            match res {
+                HSD_poll_res_HSDR_POLL_EMPTY => return,
+                HSD_poll_res_HSDR_POLL_MORE => {}
+                _ => panic!(),
+            }
+        }
+    }
+}
+
+/// Проверить поток, сжатый основной (no_std) библиотекой
+pub fn validate(data: &[u8]) -> Result<Summary, Error> {
+    let mut validator = Validator::new();
+    validator.push(data);
+    validator.finish()
+}
+
+#[cfg(unix)]
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use alloc::vec::Vec;
+
+    use crate::encoder::HeatshrinkEncoder;
+    use crate::validate::{crc32, validate, Error, Summary, Validator};
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn zeros() {
+        assert_eq!(
+            validate(&[0x00, 0x38]),
+            Ok(Summary {
+                output_size: 8,
+                crc32: crc32(&[0; 8]),
+                literals: 0,
+                backrefs: 1,
+                before_start: 1,
+            })
+        );
+        assert_eq!(validate(&[]).unwrap().output_size, 0);
+    }
+
+    #[test]
+    fn streaming() {
+        use rand::Rng;
+
+        let mut rng = rand::thread_rng();
+        let src = (0..5000)
+            .map(|i| if i % 7 == 0 { rng.gen() } else { b'a' })
+            .collect::<Vec<u8>>();
+        let packed = HeatshrinkEncoder::source(src.iter().cloned()).collect::<Vec<_>>();
+
+        let whole = validate(&packed).unwrap();
+        assert_eq!(whole.output_size, src.len());
+        assert_eq!(whole.crc32, crc32(&src));
+
+        let mut validator = Validator::new();
+        for chunk in packed.chunks(rng.gen_range(1..100)) {
+            validator.push(chunk);
+        }
+        assert_eq!(validator.finish(), Ok(whole));
+    }
+
+    #[test]
+    fn truncated() {
+        let src = b"truncated truncated truncated".repeat(10);
+        let packed = HeatshrinkEncoder::source(src.iter().cloned()).collect::<Vec<_>>();
+        assert!(validate(&packed).is_ok());
+
+        // оборванный поток: либо неполный токен, либо меньше данных
+        for len in 1..packed.len() {
+            match validate(&packed[..len]) {
+                Err(Error::Truncated { .. }) => {}
+                Ok(summary) => assert!(summary.output_size < src.len()),
+            }
+        }
+        assert!(matches!(
+            validate(&packed[..packed.len() - 1]),
+            Err(Error::Truncated { .. })
+        ));
+    }
+}